@@ -0,0 +1,60 @@
+use std::{io::ErrorKind, os::unix::net::UnixStream, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::protocol::{
+    CONTROL_SOCKET_PATH, CompanionResponse, CompanionStatus, Hello, read_message, write_message,
+};
+
+/// companion 控制 socket 接受的请求，序列化格式与 companion 的 `CompanionRequest` 一致。
+#[derive(Serialize)]
+#[serde(tag = "cmd", content = "payload")]
+pub enum ControlRequest {
+    Hello(Hello),
+    Restore { package: String },
+    Status,
+    RestoreAll,
+}
+
+/// 连接 companion 控制 socket。companion 未运行时返回 `None`。
+pub fn connect() -> Result<Option<UnixStream>> {
+    match UnixStream::connect(CONTROL_SOCKET_PATH) {
+        Ok(stream) => Ok(Some(stream)),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            Ok(None)
+        }
+        Err(e) => {
+            Err(e).with_context(|| format!("cannot connect to companion at {CONTROL_SOCKET_PATH}"))
+        }
+    }
+}
+
+/// 在一条新连接上握手并发送请求。
+pub fn send(request: &ControlRequest) -> Result<CompanionResponse> {
+    let stream = connect()?.with_context(|| {
+        format!(
+            "cannot connect to companion at {CONTROL_SOCKET_PATH} (no app has used it since boot?)"
+        )
+    })?;
+    send_on(stream, request)
+}
+
+/// 在已建立的连接上握手并发送请求。
+pub fn send_on(mut stream: UnixStream, request: &ControlRequest) -> Result<CompanionResponse> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    write_message(&mut stream, &ControlRequest::Hello(Hello::new(&[])))?;
+    read_message::<CompanionResponse>(&mut stream)
+        .context("companion did not acknowledge the handshake (reboot after updating the module)")?
+        .into_result()?;
+
+    write_message(&mut stream, request)?;
+    Ok(read_message::<CompanionResponse>(&mut stream)?.into_result()?)
+}
+
+pub fn fetch_status() -> Result<CompanionStatus> {
+    send(&ControlRequest::Status)?
+        .companion_status
+        .context("companion reply carries no status")
+}
@@ -1,7 +1,18 @@
 use anyhow::Result;
 use argh::FromArgs;
 
+mod control;
 mod converter;
+// 与 companion 共用日志格式；CLI 只读取和清除日志。
+#[allow(dead_code)]
+#[path = "../../src/journal.rs"]
+mod journal;
+// 与 companion 共用线协议与状态结构。
+#[allow(dead_code)]
+#[path = "../../src/protocol.rs"]
+mod protocol;
+mod restore;
+mod status;
 
 /// Device Faker configuration tool
 #[derive(FromArgs)]
@@ -19,6 +30,14 @@ enum Command {
     ConvertProps(ConvertPropsArgs),
     /// Read current device properties via getprop and export TOML configuration
     DumpDevice(DumpDeviceArgs),
+    /// Restore all properties recorded in the companion journal
+    RestoreAll(RestoreAllArgs),
+    /// Show active property sessions, watchers and overlay mounts
+    Status(StatusArgs),
+    /// Show active property sessions and their original values
+    Sessions(SessionsArgs),
+    /// Force-restore the property sessions of one app
+    Restore(RestoreArgs),
 }
 
 /// Convert Magisk module ZIP to TOML configuration
@@ -56,6 +75,30 @@ struct DumpDeviceArgs {
     output: String,
 }
 
+/// End all property sessions, or restore from the journal when companion is not running
+#[derive(FromArgs)]
+#[argh(subcommand, name = "restore-all")]
+struct RestoreAllArgs {}
+
+/// Show active property sessions, watchers and overlay mounts
+#[derive(FromArgs)]
+#[argh(subcommand, name = "status")]
+struct StatusArgs {}
+
+/// Show active property sessions and their original values
+#[derive(FromArgs)]
+#[argh(subcommand, name = "sessions")]
+struct SessionsArgs {}
+
+/// Force-restore the property sessions of one app
+#[derive(FromArgs)]
+#[argh(subcommand, name = "restore")]
+struct RestoreArgs {
+    /// package name, optionally with @user (e.g. com.example.app@0)
+    #[argh(positional)]
+    package: String,
+}
+
 fn main() -> Result<()> {
     let cli: Cli = argh::from_env();
 
@@ -69,6 +112,18 @@ fn main() -> Result<()> {
         Command::DumpDevice(args) => {
             converter::dump_current_device_config(&args.output)?;
         }
+        Command::RestoreAll(_) => {
+            restore::restore_all()?;
+        }
+        Command::Status(_) => {
+            status::show_status()?;
+        }
+        Command::Sessions(_) => {
+            status::show_sessions()?;
+        }
+        Command::Restore(args) => {
+            restore::restore_package(&args.package)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,100 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, Result, bail};
+
+use crate::control::{ControlRequest, connect, fetch_status, send, send_on};
+use crate::journal::Journal;
+
+/// 各 root 实现自带的 resetprop 位置，找不到时使用 PATH 中的 resetprop。
+const RESETPROP_CANDIDATES: &[&str] = &[
+    "/data/adb/ksu/bin/resetprop",
+    "/data/adb/ap/bin/resetprop",
+    "/data/adb/magisk/resetprop",
+];
+
+fn resetprop_binary() -> &'static str {
+    RESETPROP_CANDIDATES
+        .iter()
+        .copied()
+        .find(|path| Path::new(path).exists())
+        .unwrap_or("resetprop")
+}
+
+/// 恢复所有被伪装的属性。
+///
+/// companion 在运行时由它结束全部会话并写回原值，避免与仍在管理属性的会话冲突；
+/// 没有 companion 时按它写入的日志直接恢复，全部成功后才清除日志。
+pub fn restore_all() -> Result<()> {
+    if let Some(stream) = connect()? {
+        send_on(stream, &ControlRequest::RestoreAll)?;
+        println!("Companion restored all sessions");
+        return Ok(());
+    }
+
+    let journal = Journal::system();
+    let pending = journal.pending()?;
+    if pending.is_empty() {
+        println!("No pending property originals to restore");
+        return Ok(());
+    }
+
+    let resetprop = resetprop_binary();
+    let mut keys: Vec<_> = pending.keys().collect();
+    keys.sort();
+
+    let mut failed = 0;
+    for key in keys {
+        // 原本不存在的属性重新删除，其余写回原值（包括空值）
+        let mut command = Command::new(resetprop);
+        match pending[key].value() {
+            Some(value) => command.args(["-n", key, value]),
+            None => command.args(["-d", key]),
+        };
+        let status = command
+            .status()
+            .with_context(|| format!("failed to execute {}", resetprop))?;
+        if status.success() {
+            match pending[key].value() {
+                Some(value) => println!("Restored {}={}", key, value),
+                None => println!("Deleted {}", key),
+            }
+        } else {
+            eprintln!("Failed to restore {} ({})", key, status);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} properties could not be restored; journal kept", failed);
+    }
+    journal.clear()?;
+    println!("Restored {} properties", pending.len());
+    Ok(())
+}
+
+/// 通过 companion 强制还原某个应用的全部会话。
+///
+/// `package` 可以是 `包名@用户`，也可以只写包名以匹配该应用在所有用户下的会话。
+pub fn restore_package(package: &str) -> Result<()> {
+    let status = fetch_status()?;
+    let prefix = format!("{package}@");
+    let mut targets: Vec<&str> = status
+        .sessions
+        .iter()
+        .map(|session| session.package.as_str())
+        .filter(|name| *name == package || name.starts_with(&prefix))
+        .collect();
+    targets.sort();
+    targets.dedup();
+    if targets.is_empty() {
+        bail!("No active session for {}", package);
+    }
+
+    for target in targets {
+        send(&ControlRequest::Restore {
+            package: target.to_string(),
+        })?;
+        println!("Restored {}", target);
+    }
+    Ok(())
+}
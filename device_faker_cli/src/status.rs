@@ -0,0 +1,131 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::control::fetch_status;
+use crate::journal::Journal;
+use crate::protocol::{CompanionStatus, SessionStatus};
+
+/// 覆盖文件源目录，与 companion 的 `CPU_SPOOF_STATE_DIR` 一致。
+const CPU_SPOOF_STATE_DIR: &str = "/data/adb/device_faker/cpu";
+
+/// 打印属性会话、进程组与覆盖文件挂载。companion 不可用时改为读取状态文件。
+pub fn show_status() -> Result<()> {
+    let status = match fetch_status() {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Companion unavailable: {e:#}");
+            eprintln!("Showing state files instead");
+            print_journal()?;
+            print_overlay_sources()?;
+            return Ok(());
+        }
+    };
+
+    print_sessions(&status.sessions);
+    print_watchers(&status);
+    print_mounts(&status);
+    Ok(())
+}
+
+/// 只打印属性会话。
+pub fn show_sessions() -> Result<()> {
+    match fetch_status() {
+        Ok(status) => print_sessions(&status.sessions),
+        Err(e) => {
+            eprintln!("Companion unavailable: {e:#}");
+            print_journal()?;
+        }
+    }
+    Ok(())
+}
+
+fn describe_original(original: Option<&Option<String>>) -> &str {
+    match original {
+        Some(Some(value)) if value.is_empty() => "<empty>",
+        Some(Some(value)) => value,
+        Some(None) => "<absent>",
+        None => "<unknown>",
+    }
+}
+
+fn print_sessions(sessions: &[SessionStatus]) {
+    println!("Sessions ({}):", sessions.len());
+    for session in sessions {
+        println!(
+            "  #{} {} pid {} [{}]",
+            session.id,
+            session.package,
+            session.pid,
+            if session.active { "active" } else { "restored" }
+        );
+        for key in &session.keys {
+            println!(
+                "      {key} (original: {})",
+                describe_original(session.originals.get(key))
+            );
+        }
+    }
+}
+
+fn print_watchers(status: &CompanionStatus) {
+    println!("Watchers ({}):", status.watchers.len());
+    for watcher in &status.watchers {
+        println!(
+            "  {} uid {} pids {:?} session #{} policy {}",
+            watcher.package, watcher.uid, watcher.pids, watcher.session, watcher.restore_policy
+        );
+    }
+}
+
+fn print_mounts(status: &CompanionStatus) {
+    println!("Mounts ({}):", status.mounts.len());
+    for mount in &status.mounts {
+        match mount.ns_ino {
+            Some(ino) => println!(
+                "  pid {} mnt:[{ino}] remounts {}",
+                mount.pid, mount.remounts
+            ),
+            None => println!("  pid {} (exited)", mount.pid),
+        }
+        for (source, target) in mount.sources.iter().zip(&mount.targets) {
+            println!("      {source} -> {target}");
+        }
+    }
+}
+
+/// 日志中尚未恢复的原值（不含会话信息）。
+fn print_journal() -> Result<()> {
+    let pending = Journal::system().pending()?;
+    let mut keys: Vec<_> = pending.keys().collect();
+    keys.sort();
+    println!("Journal originals ({}):", keys.len());
+    for key in keys {
+        let original = pending[key].value().map(str::to_string);
+        println!("  {key} (original: {})", describe_original(Some(&original)));
+    }
+    Ok(())
+}
+
+/// 覆盖文件源目录中的文件。文件按内容命名，多个 app 可能共用同一个文件。
+fn print_overlay_sources() -> Result<()> {
+    let entries = match fs::read_dir(CPU_SPOOF_STATE_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("Overlay sources (0):");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    println!("Overlay sources ({}):", names.len());
+    for name in names {
+        println!("  {CPU_SPOOF_STATE_DIR}/{name}");
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/protocol.rs"]
+mod protocol;
+
+// 任意字节流按帧连续解析：不得 panic，不得按对端声明的长度超限分配，
+// 成功读出的负载重新编码后必须能原样读回。
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Cursor::new(data);
+    while let Ok(payload) = protocol::read_frame(&mut reader) {
+        assert!(!payload.is_empty() && payload.len() <= protocol::MAX_FRAME_SIZE);
+        let _ = serde_json::from_slice::<protocol::Hello>(&payload);
+        let _ = serde_json::from_slice::<protocol::CompanionError>(&payload);
+
+        let mut encoded = Vec::new();
+        protocol::write_frame(&mut encoded, &payload).unwrap();
+        assert_eq!(
+            protocol::read_frame(&mut Cursor::new(encoded)).unwrap(),
+            payload
+        );
+    }
+});
@@ -0,0 +1,302 @@
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicI32, Ordering},
+};
+
+use jni::{
+    Env, EnvUnowned, jni_sig, jni_str,
+    objects::{JObject, JString, JValue},
+    strings::JNIStr,
+    sys::JNINativeMethod,
+};
+use zygisk_api::api::{V4, ZygiskApi};
+
+use crate::{display_spoof, feature_spoof, memory_spoof};
+
+// 很多 PackageManager/DisplayManager/ActivityManager 查询没有 native 实现，只能在 BinderProxy.transactNative
+// 完成真实事务后改写回复。各伪装模块提供按 (接口描述符, 事务号) 过滤的改写函数，
+// 这里只负责安装 hook 和分派。
+
+/// BinderProxy.transactNative 原始函数签名。
+type OriginalTransactNative = unsafe extern "C" fn(
+    env: *mut jni::sys::JNIEnv,
+    this: jni::sys::jobject,
+    code: jni::sys::jint,
+    data: jni::sys::jobject,
+    reply: jni::sys::jobject,
+    flags: jni::sys::jint,
+) -> jni::sys::jboolean;
+
+static ORIGINAL_TRANSACT_NATIVE: OnceLock<OriginalTransactNative> = OnceLock::new();
+/// 接口描述符之前的 int 个数（strict mode 策略、work source、`SYST` 头），随 Android 版本变化。
+static INTERFACE_HEADER_INTS: AtomicI32 = AtomicI32::new(-1);
+/// `Parcelable.PARCELABLE_WRITE_RETURN_VALUE`，AIDL 写返回值和 out 参数时使用
+const PARCELABLE_WRITE_RETURN_VALUE: i32 = 1;
+
+/// hook 中读写 Parcel 所需的最小接口，测试中用内存实现代替 `android.os.Parcel`。
+pub(crate) trait Parcel {
+    fn position(&mut self) -> Result<i32, jni::errors::Error>;
+    fn set_position(&mut self, position: i32) -> Result<(), jni::errors::Error>;
+    fn read_int(&mut self) -> Result<i32, jni::errors::Error>;
+    fn read_string(&mut self) -> Result<Option<String>, jni::errors::Error>;
+    fn write_int(&mut self, value: i32) -> Result<(), jni::errors::Error>;
+    /// 从头读取回复的异常头，回复带有异常时返回 `false`。
+    fn read_reply_header(&mut self) -> Result<bool, jni::errors::Error>;
+}
+
+/// 读取请求的接口描述符，读取后位置停在第一个参数处。
+pub(crate) fn read_interface(
+    data: &mut dyn Parcel,
+    header_ints: i32,
+) -> Result<Option<String>, jni::errors::Error> {
+    data.set_position(0)?;
+    for _ in 0..header_ints {
+        data.read_int()?;
+    }
+    data.read_string()
+}
+
+/// 当前系统的接口头长度，尚未探测时为 `None`。
+pub(crate) fn interface_header_ints() -> Option<i32> {
+    let header_ints = INTERFACE_HEADER_INTS.load(Ordering::Relaxed);
+    (header_ints >= 0).then_some(header_ints)
+}
+
+/// `android.os.Parcel` 的 JNI 实现。
+pub(crate) struct JniParcel<'a, 'local, 'obj> {
+    pub env: &'a mut Env<'local>,
+    pub parcel: &'a JObject<'obj>,
+}
+
+impl Parcel for JniParcel<'_, '_, '_> {
+    fn position(&mut self) -> Result<i32, jni::errors::Error> {
+        self.env
+            .call_method(self.parcel, jni_str!("dataPosition"), jni_sig!("()I"), &[])?
+            .i()
+    }
+
+    fn set_position(&mut self, position: i32) -> Result<(), jni::errors::Error> {
+        self.env.call_method(
+            self.parcel,
+            jni_str!("setDataPosition"),
+            jni_sig!("(I)V"),
+            &[JValue::Int(position)],
+        )?;
+        Ok(())
+    }
+
+    fn read_int(&mut self) -> Result<i32, jni::errors::Error> {
+        self.env
+            .call_method(self.parcel, jni_str!("readInt"), jni_sig!("()I"), &[])?
+            .i()
+    }
+
+    fn read_string(&mut self) -> Result<Option<String>, jni::errors::Error> {
+        let value = self
+            .env
+            .call_method(
+                self.parcel,
+                jni_str!("readString"),
+                jni_sig!("()Ljava/lang/String;"),
+                &[],
+            )?
+            .l()?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        let value = unsafe { JString::from_raw(self.env, value.into_raw()) };
+        Ok(Some(value.mutf8_chars(self.env)?.to_string()))
+    }
+
+    fn write_int(&mut self, value: i32) -> Result<(), jni::errors::Error> {
+        self.env.call_method(
+            self.parcel,
+            jni_str!("writeInt"),
+            jni_sig!("(I)V"),
+            &[JValue::Int(value)],
+        )?;
+        Ok(())
+    }
+
+    fn read_reply_header(&mut self) -> Result<bool, jni::errors::Error> {
+        self.set_position(0)?;
+        // readException 同时处理 strict mode 等回复头，有异常时抛出，这里只清除不上抛。
+        if self
+            .env
+            .call_method(self.parcel, jni_str!("readException"), jni_sig!("()V"), &[])
+            .is_ok()
+        {
+            Ok(true)
+        } else {
+            self.env.exception_clear();
+            Ok(false)
+        }
+    }
+}
+
+/// 改写形如「无异常 + 非空标记 + Parcelable」的回复：`read` 从当前位置读出对象，
+/// `patch` 修改后用对象自己的 `writeToParcel` 原位写回，不依赖各版本的字段布局。
+/// 回复带有异常或对象为 null 时保持原样。
+pub(crate) fn rewrite_reply_parcelable<'local>(
+    env: &mut Env<'local>,
+    reply: &JObject,
+    read: impl FnOnce(&mut Env<'local>) -> Result<JObject<'local>, jni::errors::Error>,
+    patch: impl FnOnce(&mut Env<'local>, &JObject<'local>) -> Result<(), jni::errors::Error>,
+) -> Result<(), jni::errors::Error> {
+    let mut parcel = JniParcel { env, parcel: reply };
+    let reply_position = parcel.position()?;
+    if !parcel.read_reply_header()? || parcel.read_int()? == 0 {
+        return parcel.set_position(reply_position);
+    }
+    let object_position = parcel.position()?;
+    let object = read(env)?;
+    patch(env, &object)?;
+
+    JniParcel { env, parcel: reply }.set_position(object_position)?;
+    env.call_method(
+        &object,
+        jni_str!("writeToParcel"),
+        jni_sig!("(Landroid/os/Parcel;I)V"),
+        &[
+            JValue::Object(reply),
+            JValue::Int(PARCELABLE_WRITE_RETURN_VALUE),
+        ],
+    )?;
+    // 改写后的对象可能比原来短，截掉残留数据
+    let end = JniParcel { env, parcel: reply }.position()?;
+    env.call_method(
+        reply,
+        jni_str!("setDataSize"),
+        jni_sig!("(I)V"),
+        &[JValue::Int(end)],
+    )?;
+    JniParcel { env, parcel: reply }.set_position(reply_position)
+}
+
+/// 安装 BinderProxy.transactNative hook，多次调用只安装一次。
+pub fn install(api: &mut ZygiskApi<V4>, env: &mut EnvUnowned) -> anyhow::Result<()> {
+    if ORIGINAL_TRANSACT_NATIVE.get().is_some() {
+        return Ok(());
+    }
+
+    // 用一个真实写入的接口头探测描述符前的 int 个数，避免按 SDK 版本硬编码。
+    let header_ints = env
+        .with_env(|jenv| -> Result<i32, jni::errors::Error> {
+            let parcel_class = jenv.find_class(jni_str!("android/os/Parcel"))?;
+            let probe = jenv
+                .call_static_method(
+                    &parcel_class,
+                    jni_str!("obtain"),
+                    jni_sig!("()Landroid/os/Parcel;"),
+                    &[],
+                )?
+                .l()?;
+            let token = jenv.new_string("device_faker.probe")?;
+            jenv.call_method(
+                &probe,
+                jni_str!("writeInterfaceToken"),
+                jni_sig!("(Ljava/lang/String;)V"),
+                &[JValue::Object(&token)],
+            )?;
+            let mut parcel = JniParcel {
+                env: jenv,
+                parcel: &probe,
+            };
+            let mut found = -1;
+            for header_ints in 0..=4 {
+                if read_interface(&mut parcel, header_ints)?.as_deref()
+                    == Some("device_faker.probe")
+                {
+                    found = header_ints;
+                    break;
+                }
+            }
+            jenv.call_method(&probe, jni_str!("recycle"), jni_sig!("()V"), &[])?;
+            Ok(found)
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+    if header_ints < 0 {
+        anyhow::bail!("Failed to probe Parcel interface token layout");
+    }
+    INTERFACE_HEADER_INTS.store(header_ints, Ordering::Relaxed);
+
+    let mut methods = [JNINativeMethod {
+        name: c"transactNative".as_ptr().cast_mut(),
+        signature: c"(ILandroid/os/Parcel;Landroid/os/Parcel;I)Z"
+            .as_ptr()
+            .cast_mut(),
+        fnPtr: transact_native_hook as *mut std::ffi::c_void,
+    }];
+
+    let class_name = unsafe { JNIStr::from_ptr(c"android/os/BinderProxy".as_ptr()) };
+
+    env.with_env(|jenv| -> Result<(), jni::errors::Error> {
+        let env_unowned = unsafe { EnvUnowned::from_raw(jenv.get_raw()) };
+        unsafe {
+            api.hook_jni_native_methods(env_unowned, class_name, &mut methods);
+        }
+        Ok(())
+    })
+    .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+
+    if methods[0].fnPtr.is_null() || methods[0].fnPtr == transact_native_hook as *mut _ {
+        anyhow::bail!("Failed to hook android.os.BinderProxy.transactNative");
+    }
+    let original_fn_ptr = unsafe {
+        std::mem::transmute::<*mut std::ffi::c_void, OriginalTransactNative>(methods[0].fnPtr)
+    };
+    let _ = ORIGINAL_TRANSACT_NATIVE.set(original_fn_ptr);
+
+    Ok(())
+}
+
+/// 为 Hook 提供的 BinderProxy.transactNative 替身实现。
+///
+/// 先完成真实事务，再交给各伪装模块按事务号和接口描述符改写回复。其余事务原样返回。
+unsafe extern "C" fn transact_native_hook(
+    env: *mut jni::sys::JNIEnv,
+    this: jni::sys::jobject,
+    code: jni::sys::jint,
+    data: jni::sys::jobject,
+    reply: jni::sys::jobject,
+    flags: jni::sys::jint,
+) -> jni::sys::jboolean {
+    let Some(orig_fn) = ORIGINAL_TRANSACT_NATIVE.get() else {
+        return false;
+    };
+    let result = unsafe { orig_fn(env, this, code, data, reply, flags) };
+    if !result
+        || data.is_null()
+        || reply.is_null()
+        || !(feature_spoof::wants_transaction(code)
+            || display_spoof::wants_transaction(code)
+            || memory_spoof::wants_transaction(code))
+    {
+        return result;
+    }
+    let Some(header_ints) = interface_header_ints() else {
+        return result;
+    };
+
+    let mut env_wrapper = unsafe { EnvUnowned::from_raw(env) };
+    env_wrapper
+        .with_env(|jenv| -> Result<(), jni::errors::Error> {
+            let data = unsafe { JObject::from_raw(jenv, data) };
+            let reply = unsafe { JObject::from_raw(jenv, reply) };
+            // 这里处于 app 的 Binder 调用路径上，任何失败都只能放弃改写，不能向上抛异常。
+            let patched = feature_spoof::patch_transaction(jenv, code, &data, &reply, header_ints)
+                .and_then(|()| {
+                    display_spoof::patch_transaction(jenv, code, &data, &reply, header_ints)
+                })
+                .and_then(|()| {
+                    memory_spoof::patch_transaction(jenv, code, &data, &reply, header_ints)
+                });
+            if patched.is_err() {
+                jenv.exception_clear();
+            }
+            Ok(())
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+
+    result
+}
@@ -1,23 +1,115 @@
 use std::{
     collections::HashMap,
     fs::{self, OpenOptions},
-    io::{Read, Write},
+    io::Write,
     os::unix::net::UnixStream,
-    thread,
-    time::{Duration, Instant},
+    sync::{LazyLock, Mutex, Once},
 };
 
+use anyhow::Context;
 use log::{error, info, warn};
 use prop_rs_android::{resetprop::ResetProp, sys_prop};
 use serde::{Deserialize, Serialize};
 use zygisk_api::api::{V4, ZygiskApi};
 
+use crate::config::{BatteryConfig, RestorePolicy};
+use crate::journal::Journal;
+use crate::process_registry::{ProcessRegistry, Registration};
+use crate::prop_session::{PropBackend, SessionId, SessionManager};
+use crate::protocol::{
+    CompanionError, CompanionResponse, CompanionStatus, ErrorCode, FrameError, Hello,
+    PROTOCOL_VERSION, WatcherStatus, read_message, write_message,
+};
 use crate::state::{ACTIVE_RESET_SESSION, ActiveResetSession};
 
+// 能力名称，握手时由 companion 声明，模块发送请求前逐项检查。
+const CAP_RESETPROP: &str = "resetprop";
+const CAP_WRITE_LOG: &str = "write_log";
+const CAP_OVERLAY_CPUINFO: &str = "overlay.cpuinfo";
+const CAP_OVERLAY_MEMINFO: &str = "overlay.meminfo";
+const CAP_OVERLAY_BATTERY: &str = "overlay.battery";
+const CAP_OVERLAY_THERMAL: &str = "overlay.thermal";
+const CAP_PROP_ISOLATION: &str = "prop_isolation";
+const CAP_STATUS: &str = "status";
+
+/// 本端支持的全部能力。
+const CAPABILITIES: &[&str] = &[
+    CAP_RESETPROP,
+    CAP_WRITE_LOG,
+    CAP_OVERLAY_CPUINFO,
+    CAP_OVERLAY_MEMINFO,
+    CAP_OVERLAY_BATTERY,
+    CAP_OVERLAY_THERMAL,
+    CAP_PROP_ISOLATION,
+    CAP_STATUS,
+];
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CpuSpoofRequest {
     pub pid: u32,
+    /// /proc/cpuinfo 内容，为空时不覆盖 cpuinfo
     pub content: String,
+    /// 伪装的总内存（MB），设置后同时覆盖 /proc/meminfo
+    #[serde(default)]
+    pub ram_total_mb: Option<u64>,
+    /// 电池参数，设置后覆盖 power_supply sysfs 中的对应文件
+    #[serde(default)]
+    pub battery: Option<BatteryConfig>,
+    /// 温控区名称覆盖：真实 type → 伪装 type
+    #[serde(default)]
+    pub thermal_zones: HashMap<String, String>,
+    /// isolated 模式：写入 app 私有属性区副本的属性，不修改全局属性
+    #[serde(default)]
+    pub props: HashMap<String, String>,
+    /// isolated 模式：在私有属性区副本中删除的属性
+    #[serde(default)]
+    pub delete_props: Vec<String>,
+    /// app 首次读取 /proc/cpuinfo 后卸载其覆盖挂载
+    #[serde(default)]
+    pub unmount_cpuinfo_after_read: bool,
+    /// 覆盖文件名称（cpuinfo/meminfo/battery/thermal_zones）→ 源文件的 SELinux label
+    #[serde(default)]
+    pub overlay_labels: HashMap<String, String>,
+}
+
+impl CpuSpoofRequest {
+    /// 配置中 `overlay` 的 SELinux label，未配置时为 `None`
+    pub fn overlay_label(&self, overlay: &str) -> Option<&str> {
+        self.overlay_labels.get(overlay).map(String::as_str)
+    }
+
+    /// 是否有需要 bind mount 的覆盖文件
+    pub fn has_overlays(&self) -> bool {
+        !self.content.is_empty()
+            || self.ram_total_mb.is_some()
+            || self.battery.is_some()
+            || !self.thermal_zones.is_empty()
+            || self.isolates_props()
+    }
+
+    fn isolates_props(&self) -> bool {
+        !self.props.is_empty() || !self.delete_props.is_empty()
+    }
+
+    fn required_capabilities(&self) -> Vec<&'static str> {
+        let mut capabilities = Vec::new();
+        if !self.content.is_empty() {
+            capabilities.push(CAP_OVERLAY_CPUINFO);
+        }
+        if self.ram_total_mb.is_some() {
+            capabilities.push(CAP_OVERLAY_MEMINFO);
+        }
+        if self.battery.is_some() {
+            capabilities.push(CAP_OVERLAY_BATTERY);
+        }
+        if !self.thermal_zones.is_empty() {
+            capabilities.push(CAP_OVERLAY_THERMAL);
+        }
+        if self.isolates_props() {
+            capabilities.push(CAP_PROP_ISOLATION);
+        }
+        capabilities
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -30,34 +122,26 @@ pub fn spoof_system_props_via_companion(
     prop_map: &HashMap<String, String>,
     delete_props: &[String],
     package_name: &str,
+    uid: u32,
+    restore_policy: RestorePolicy,
 ) -> anyhow::Result<()> {
     if prop_map.is_empty() && delete_props.is_empty() {
         return Ok(());
     }
 
     let request = CompanionRequest::Apply(ResetpropSessionRequest {
+        package: package_name.to_string(),
         pid: std::process::id(),
+        uid,
         props: prop_map.clone(),
         delete_props: delete_props.to_vec(),
+        restore_policy,
     });
 
-    let response = send_companion_command(api, &request)?;
-    if response.status != 0 {
-        anyhow::bail!(
-            response
-                .message
-                .unwrap_or_else(|| "companion resetprop failed".to_string())
-        );
-    }
-
-    if let Some(backups) = response.backups {
-        *ACTIVE_RESET_SESSION.lock().unwrap() = Some(ActiveResetSession {
-            package: package_name.to_string(),
-            backups,
-        });
-    } else {
-        warn!("Companion did not return property backups; automatic restore may be skipped");
-    }
+    send_companion_command(api, &request)?;
+    *ACTIVE_RESET_SESSION.lock().unwrap() = Some(ActiveResetSession {
+        package: package_name.to_string(),
+    });
 
     Ok(())
 }
@@ -71,7 +155,7 @@ pub fn restore_previous_resetprop_if_needed(
 
     match pending {
         Some(session) if session.package != current_package => {
-            if let Err(e) = restore_props_via_companion(api, &session.backups) {
+            if let Err(e) = restore_props_via_companion(api, &session.package) {
                 error!("Failed to restore previous resetprop session: {e}");
             }
         }
@@ -83,65 +167,94 @@ pub fn restore_previous_resetprop_if_needed(
     Ok(())
 }
 
-fn restore_props_via_companion(
-    api: &mut ZygiskApi<V4>,
-    backups: &HashMap<String, String>,
-) -> anyhow::Result<()> {
-    if backups.is_empty() {
-        return Ok(());
-    }
-
+fn restore_props_via_companion(api: &mut ZygiskApi<V4>, package: &str) -> anyhow::Result<()> {
     let request = CompanionRequest::Restore(RestoreRequest {
-        props: backups.clone(),
+        package: package.to_string(),
     });
 
-    let response = send_companion_command(api, &request)?;
-    if response.status != 0 {
-        anyhow::bail!(
-            response
-                .message
-                .unwrap_or_else(|| "companion restore failed".to_string())
-        );
-    }
-
+    send_companion_command(api, &request)?;
     Ok(())
 }
 
+/// 发送请求并返回成功的响应；companion 返回的错误转换为 `CompanionError`。
 pub fn send_companion_command(
     api: &mut ZygiskApi<V4>,
     request: &CompanionRequest,
 ) -> anyhow::Result<CompanionResponse> {
-    let payload = serde_json::to_vec(request)?;
-    let response = api
-        .with_companion(|stream| -> anyhow::Result<CompanionResponse> {
-            stream.write_all(&(payload.len() as u32).to_le_bytes())?;
-            stream.write_all(&payload)?;
-            stream.flush()?;
-
-            let mut len_buf = [0u8; 4];
-            stream.read_exact(&mut len_buf)?;
-            let resp_len = u32::from_le_bytes(len_buf) as usize;
-            let mut resp_buf = vec![0u8; resp_len];
-            stream.read_exact(&mut resp_buf)?;
-
-            let resp = serde_json::from_slice::<CompanionResponse>(&resp_buf)?;
-            Ok(resp)
-        })
-        .map_err(|e| anyhow::anyhow!("Failed to talk to companion: {e}"))??;
+    api.with_companion(|stream| exchange(stream, request))
+        .map_err(|e| anyhow::anyhow!("Failed to talk to companion: {e}"))?
+}
 
+/// 在一条新的 companion 连接上完成握手并发送请求。
+///
+/// companion 的协议版本不一致或缺少请求所需的能力时直接返回错误，
+/// 不发送请求本身，避免旧 companion 以错误的方式解释新字段。
+pub(crate) fn exchange(
+    stream: &mut UnixStream,
+    request: &CompanionRequest,
+) -> anyhow::Result<CompanionResponse> {
+    let hello = handshake(stream)?;
+    if let Some(missing) = request
+        .required_capabilities()
+        .into_iter()
+        .find(|capability| !hello.supports(capability))
+    {
+        return Err(CompanionError::new(
+            ErrorCode::MissingCapability,
+            format!(
+                "companion (protocol v{}) does not support {missing}",
+                hello.version
+            ),
+        )
+        .into());
+    }
+
+    write_message(stream, request)?;
+    let response = read_message::<CompanionResponse>(stream)?.into_result()?;
     Ok(response)
 }
 
-pub fn handle_companion_request(stream: &mut UnixStream) {
+/// 发送 Hello 并返回 companion 声明的协议版本与能力。
+fn handshake(stream: &mut UnixStream) -> anyhow::Result<Hello> {
+    write_message(stream, &CompanionRequest::Hello(Hello::new(CAPABILITIES)))?;
+    let ack = read_message::<CompanionResponse>(stream)
+        .context("Companion did not acknowledge the protocol handshake (reboot after updating the module)")?
+        .into_result()?;
+    ack.hello
+        .context("Companion handshake reply carries no hello")
+}
+
+/// system_server 启动时唤起 companion：只完成握手，让 companion 在任何 app 请求之前
+/// 完成一次性初始化。
+pub fn start_companion(api: &mut ZygiskApi<V4>) -> anyhow::Result<()> {
+    api.with_companion(|stream| handshake(stream).map(|_| ()))
+        .map_err(|e| anyhow::anyhow!("Failed to talk to companion: {e}"))?
+}
+
+static STARTUP: Once = Once::new();
+
+/// companion 进程的一次性初始化：按日志恢复上一个 companion 遗留的伪装属性，
+/// 清理残留的覆盖文件，启动控制 socket。
+fn init_companion() {
     // companion 进程不会调用 ZygiskModule::on_load，因此需要自行初始化日志。
     #[cfg(target_os = "android")]
     crate::file_logger::init();
 
-    let request = match read_companion_request(stream) {
-        Ok(request) => request,
+    LazyLock::force(&PROP_SESSIONS);
+    crate::overlay_store::init();
+    crate::control::start();
+}
+
+pub fn handle_companion_request(stream: &mut UnixStream) {
+    STARTUP.call_once(init_companion);
+
+    let request = match accept_handshake(stream).and_then(|_| read_companion_request(stream)) {
+        Ok(Some(request)) => request,
+        // start_companion 的连接只握手
+        Ok(None) => return,
         Err(err) => {
-            error!("Companion failed to parse request: {err}");
-            let response = CompanionResponse::err("invalid request");
+            error!("Companion rejected request: {err}");
+            let response = CompanionResponse::err(err.code, err.message);
             if let Err(e) = write_companion_response(stream, &response) {
                 warn!("Failed to write companion response: {e}");
             }
@@ -152,10 +265,10 @@ pub fn handle_companion_request(stream: &mut UnixStream) {
     match request {
         CompanionRequest::Apply(request) => {
             let response = match apply_resetprop_session(request) {
-                Ok(backups) => CompanionResponse::ok_with_backups(backups),
+                Ok(()) => CompanionResponse::ok(),
                 Err(err) => {
                     error!("Companion failed to apply resetprop session: {err}");
-                    CompanionResponse::err(err.to_string())
+                    CompanionResponse::err(ErrorCode::PropertyFailed, err.to_string())
                 }
             };
             if let Err(e) = write_companion_response(stream, &response) {
@@ -163,13 +276,7 @@ pub fn handle_companion_request(stream: &mut UnixStream) {
             }
         }
         CompanionRequest::Restore(request) => {
-            let response = match restore_properties(request) {
-                Ok(_) => CompanionResponse::ok(),
-                Err(err) => {
-                    error!("Companion failed to restore properties: {err}");
-                    CompanionResponse::err(err.to_string())
-                }
-            };
+            let response = restore_response(request);
             if let Err(e) = write_companion_response(stream, &response) {
                 warn!("Failed to write companion response: {e}");
             }
@@ -182,38 +289,134 @@ pub fn handle_companion_request(stream: &mut UnixStream) {
                 Ok(_) => CompanionResponse::ok(),
                 Err(err) => {
                     error!("Companion failed to write log: {err}");
-                    CompanionResponse::err(err.to_string())
+                    CompanionResponse::err(ErrorCode::LogFailed, err.to_string())
                 }
             };
             if let Err(e) = write_companion_response(stream, &response) {
                 warn!("Failed to write companion response: {e}");
             }
         }
+        CompanionRequest::Status => {
+            if let Err(e) = write_companion_response(stream, &status_response()) {
+                warn!("Failed to write companion response: {e}");
+            }
+        }
+        CompanionRequest::RestoreAll => {
+            if let Err(e) = write_companion_response(stream, &restore_all_response()) {
+                warn!("Failed to write companion response: {e}");
+            }
+        }
+        // read_companion_request 已拒绝握手之后的 Hello
+        CompanionRequest::Hello(_) => {}
+    }
+}
+
+/// 处理 CLI 经控制 socket 发来的请求，只接受 `Status`、`Restore` 与 `RestoreAll`。
+pub(crate) fn handle_control_request(stream: &mut UnixStream) {
+    let response = match accept_handshake(stream).and_then(|_| read_companion_request(stream)) {
+        Ok(None) => return,
+        Ok(Some(CompanionRequest::Status)) => status_response(),
+        Ok(Some(CompanionRequest::Restore(request))) => {
+            info!("control: restore requested for {}", request.package);
+            restore_response(request)
+        }
+        Ok(Some(CompanionRequest::RestoreAll)) => {
+            info!("control: restore requested for all sessions");
+            restore_all_response()
+        }
+        Ok(_) => CompanionResponse::err(
+            ErrorCode::InvalidRequest,
+            "only Status, Restore and RestoreAll are accepted on the control socket",
+        ),
+        Err(err) => CompanionResponse::err(err.code, err.message),
+    };
+    if let Err(e) = write_companion_response(stream, &response) {
+        warn!("Failed to write control response: {e}");
     }
 }
 
-fn read_companion_request(stream: &mut UnixStream) -> anyhow::Result<CompanionRequest> {
-    let mut len_buf = [0u8; 4];
-    stream.read_exact(&mut len_buf)?;
-    let payload_len = u32::from_le_bytes(len_buf) as usize;
-    if payload_len == 0 {
-        anyhow::bail!("empty request payload");
+fn restore_response(request: RestoreRequest) -> CompanionResponse {
+    match restore_properties(request) {
+        Ok(_) => CompanionResponse::ok(),
+        Err(err) => {
+            error!("Companion failed to restore properties: {err}");
+            CompanionResponse::err(ErrorCode::PropertyFailed, err.to_string())
+        }
+    }
+}
+
+fn restore_all_response() -> CompanionResponse {
+    match restore_all_properties() {
+        Ok(_) => CompanionResponse::ok(),
+        Err(err) => {
+            error!("Companion failed to restore all properties: {err}");
+            CompanionResponse::err(ErrorCode::PropertyFailed, err.to_string())
+        }
     }
+}
 
-    let mut payload = vec![0u8; payload_len];
-    stream.read_exact(&mut payload)?;
-    let request = serde_json::from_slice::<CompanionRequest>(&payload)?;
-    Ok(request)
+fn status_response() -> CompanionResponse {
+    CompanionResponse {
+        companion_status: Some(companion_status()),
+        ..CompanionResponse::default()
+    }
+}
+
+/// companion 侧握手：校验模块的协议版本并回复本端能力。
+fn accept_handshake(stream: &mut UnixStream) -> Result<(), CompanionError> {
+    let hello = match read_message::<CompanionRequest>(stream) {
+        Ok(CompanionRequest::Hello(hello)) => hello,
+        Ok(_) => {
+            return Err(CompanionError::new(
+                ErrorCode::InvalidRequest,
+                "protocol handshake required",
+            ));
+        }
+        Err(e) => {
+            return Err(CompanionError::new(
+                ErrorCode::InvalidRequest,
+                e.to_string(),
+            ));
+        }
+    };
+
+    if hello.version != PROTOCOL_VERSION {
+        return Err(CompanionError::new(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "module speaks protocol v{}, companion v{PROTOCOL_VERSION}; reboot to reload the companion",
+                hello.version
+            ),
+        ));
+    }
+
+    write_companion_response(stream, &CompanionResponse::hello(Hello::new(CAPABILITIES)))
+        .map_err(|e| CompanionError::new(ErrorCode::InvalidRequest, e.to_string()))
+}
+
+/// 读取握手之后的请求，对端握手后直接关闭连接时返回 `None`。
+fn read_companion_request(
+    stream: &mut UnixStream,
+) -> Result<Option<CompanionRequest>, CompanionError> {
+    match read_message::<CompanionRequest>(stream) {
+        Ok(CompanionRequest::Hello(_)) => Err(CompanionError::new(
+            ErrorCode::InvalidRequest,
+            "unexpected second hello",
+        )),
+        Ok(request) => Ok(Some(request)),
+        Err(FrameError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(CompanionError::new(
+            ErrorCode::InvalidRequest,
+            e.to_string(),
+        )),
+    }
 }
 
 pub(crate) fn write_companion_response(
     stream: &mut UnixStream,
     response: &CompanionResponse,
 ) -> anyhow::Result<()> {
-    let bytes = serde_json::to_vec(response)?;
-    stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
-    stream.write_all(&bytes)?;
-    stream.flush()?;
+    write_message(stream, response)?;
     Ok(())
 }
 
@@ -234,80 +437,137 @@ fn rebuild_all_contexts(keys_iter: impl Iterator<Item = impl AsRef<str>>) {
     }
 }
 
-fn apply_resetprop_session(
-    request: ResetpropSessionRequest,
-) -> anyhow::Result<HashMap<String, String>> {
-    if request.props.is_empty() && request.delete_props.is_empty() {
-        return Ok(HashMap::new());
+/// 所有 app 共享的属性会话。restore watcher 以线程方式运行在 companion 进程内，
+/// 与请求处理线程共用同一份会话状态。
+///
+/// 由 `init_companion` 初始化，先按日志恢复上一个 companion 进程遗留的伪装属性。
+static PROP_SESSIONS: LazyLock<Mutex<SessionManager<ResetpropBackend>>> = LazyLock::new(|| {
+    let mut manager =
+        SessionManager::new(ResetpropBackend::default()).with_journal(Journal::system());
+    if let Err(e) = manager.recover() {
+        error!("Failed to recover properties from journal: {e}");
+    }
+    Mutex::new(manager)
+});
+
+/// 基于 resetprop 的属性后端。`ResetProp` 实例与系统属性 API 在首次使用时初始化一次，
+/// 之后由 companion 进程内的所有批次复用。
+#[derive(Default)]
+struct ResetpropBackend {
+    resetprop: Option<ResetProp>,
+}
+
+impl ResetpropBackend {
+    fn resetprop(&mut self) -> anyhow::Result<&ResetProp> {
+        if self.resetprop.is_none() {
+            self.resetprop = Some(new_resetprop()?);
+        }
+        Ok(self.resetprop.as_ref().unwrap())
     }
+}
 
-    let mut backups = Vec::with_capacity(request.props.len() + request.delete_props.len());
-
-    for key in request.props.keys() {
-        let original = backup_property(key)?;
-        backups.push(PropBackup {
-            key: key.clone(),
-            original_value: original,
-        });
+impl PropBackend for ResetpropBackend {
+    fn get(&mut self, key: &str) -> anyhow::Result<Option<String>> {
+        self.resetprop()?;
+        Ok(sys_prop::get(key))
     }
 
-    for key in &request.delete_props {
-        let original = backup_property(key)?;
-        backups.push(PropBackup {
-            key: key.clone(),
-            original_value: original,
-        });
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        if self.resetprop()?.set(key, value).is_err() {
+            anyhow::bail!("resetprop failed for {key}");
+        }
+        Ok(())
     }
 
-    let backups_for_response: HashMap<String, String> = backups
-        .iter()
-        .map(|entry| (entry.key.clone(), entry.original_value.clone()))
-        .collect();
-
-    for (key, value) in &request.props {
-        apply_resetprop(key, value)?;
+    fn delete(&mut self, key: &str) -> anyhow::Result<()> {
+        match self.resetprop()?.delete(key) {
+            // Ok(false)：属性本就不存在，与目标状态一致
+            Ok(_) => Ok(()),
+            Err(_) => anyhow::bail!("resetprop delete failed for {key}"),
+        }
     }
 
-    for key in &request.delete_props {
-        resetprop_delete(key)?;
+    fn flush(&mut self, keys: &[String]) {
+        // Rebuild prop area to reclaim holes left by deletes/overwrites.
+        rebuild_all_contexts(keys.iter());
     }
-
-    // Rebuild prop area to reclaim holes left by deletes/overwrites.
-    rebuild_all_contexts(request.props.keys().chain(request.delete_props.iter()));
-
-    spawn_restore_watcher(request.pid, request.props, request.delete_props, backups)?;
-
-    Ok(backups_for_response)
 }
 
-fn restore_properties(request: RestoreRequest) -> anyhow::Result<()> {
-    if request.props.is_empty() {
+fn apply_resetprop_session(request: ResetpropSessionRequest) -> anyhow::Result<()> {
+    if request.props.is_empty() && request.delete_props.is_empty() {
+        return Ok(());
+    }
+
+    let session = PROP_SESSIONS.lock().unwrap().open(
+        &request.package,
+        request.pid,
+        &request.props,
+        &request.delete_props,
+    )?;
+
+    if request.restore_policy == RestorePolicy::Never {
+        // 不登记进程组，会话在进程退出后保留；同一应用再次启动时由新会话接替，
+        // 属性仍被新会话声明，关闭旧会话不会触发还原。
+        PROCESS_REGISTRY.lock().unwrap().remove(&request.package);
+        PROP_SESSIONS
+            .lock()
+            .unwrap()
+            .close_package_except(&request.package, session);
         return Ok(());
     }
 
-    for (key, value) in &request.props {
-        apply_resetprop(key, value)?;
+    // 在回复之前打开 pidfd：app 等待回复期间不会退出，pid 不会在 supervisor 接手前被复用
+    let pidfd = crate::supervisor::pidfd_open(request.pid)
+        .inspect_err(|e| warn!("pidfd_open({}) failed: {e}", request.pid))
+        .ok();
+    let mut registry = PROCESS_REGISTRY.lock().unwrap();
+    match registry.register(
+        &request.package,
+        request.uid,
+        request.pid,
+        pidfd,
+        session,
+        request.restore_policy,
+    ) {
+        Registration::Created { evicted, .. } => {
+            drop(registry);
+            if let Some(evicted) = evicted {
+                PROP_SESSIONS.lock().unwrap().close(evicted.session);
+            }
+        }
+        Registration::Joined { replaced, .. } => {
+            drop(registry);
+            // 新会话已声明同一应用的属性，关闭旧会话不会触发还原
+            PROP_SESSIONS.lock().unwrap().close(replaced);
+        }
     }
+    crate::supervisor::wake();
 
-    // Rebuild after restoring originals to reclaim any holes.
-    rebuild_all_contexts(request.props.keys());
+    Ok(())
+}
 
+fn restore_properties(request: RestoreRequest) -> anyhow::Result<()> {
+    PROCESS_REGISTRY.lock().unwrap().remove(&request.package);
+    PROP_SESSIONS
+        .lock()
+        .unwrap()
+        .close_package(&request.package);
     Ok(())
 }
 
-fn backup_property(key: &str) -> anyhow::Result<String> {
-    let output = std::process::Command::new("getprop").arg(key).output()?;
-    if !output.status.success() {
-        anyhow::bail!("getprop failed for {key}");
+/// 结束全部进程组和会话。会话关闭后日志中不再有原值时才算成功。
+fn restore_all_properties() -> anyhow::Result<()> {
+    PROCESS_REGISTRY.lock().unwrap().clear();
+    let mut sessions = PROP_SESSIONS.lock().unwrap();
+    sessions.close_all();
+    let remaining = sessions.pending_originals();
+    if remaining > 0 {
+        anyhow::bail!("{remaining} properties could not be restored");
     }
-
-    let value = String::from_utf8_lossy(&output.stdout)
-        .trim_end_matches(['\n', '\r'])
-        .to_string();
-    Ok(value)
+    Ok(())
 }
 
-fn new_resetprop() -> anyhow::Result<ResetProp> {
+pub(crate) fn new_resetprop() -> anyhow::Result<ResetProp> {
     sys_prop::init()
         .map_err(|e| anyhow::anyhow!("failed to initialize system property API: {e}"))?;
 
@@ -326,314 +586,41 @@ fn new_resetprop() -> anyhow::Result<ResetProp> {
     })
 }
 
-fn apply_resetprop(key: &str, value: &str) -> anyhow::Result<()> {
-    let rp = new_resetprop()?;
-
-    if rp.set(key, value).is_err() {
-        anyhow::bail!("resetprop failed for {key}");
-    }
-    Ok(())
-}
-
-fn resetprop_delete(key: &str) -> anyhow::Result<()> {
-    let rp = new_resetprop()?;
-
-    match rp.delete(key) {
-        Ok(true) => Ok(()),
-        Ok(false) => anyhow::bail!("resetprop delete failed for {key}: property not found"),
-        Err(_) => anyhow::bail!("resetprop delete failed for {key}"),
-    }
-}
-
-fn spawn_restore_watcher(
-    pid: u32,
-    props: HashMap<String, String>,
-    delete_props: Vec<String>,
-    backups: Vec<PropBackup>,
-) -> anyhow::Result<()> {
-    unsafe {
-        match libc::fork() {
-            -1 => anyhow::bail!("fork failed: {}", std::io::Error::last_os_error()),
-            0 => {
-                if libc::setsid() == -1 {
-                    libc::_exit(1);
-                }
-                if let Err(e) =
-                    watch_process_state_and_sync_props(pid, &props, &delete_props, &backups)
-                {
-                    error!("Watcher failed for pid {}: {}", pid, e);
-                }
-                libc::_exit(0);
-            }
-            _ => Ok(()),
-        }
-    }
-}
-
-fn watch_process_state_and_sync_props(
-    pid: u32,
-    props: &HashMap<String, String>,
-    delete_props: &[String],
-    backups: &[PropBackup],
-) -> anyhow::Result<()> {
-    // 优先使用 inotify 监听 oom_score_adj（事件驱动，零轮询）。
-    // 回退到 /proc/<pid>/cgroup 轮询（inotify 在部分设备/内核上不可用）。
-    match watch_via_inotify(pid, props, delete_props, backups) {
-        Ok(()) => return Ok(()),
-        Err(e) => {
-            warn!("inotify on oom_score_adj unavailable ({e}), falling back to cgroup polling");
-        }
-    }
-
-    watch_via_cgroup_polling(pid, props, delete_props, backups)
-}
-
-/// 事件驱动方案：inotify 监听 /proc/<pid>/oom_score_adj + pidfd 监听进程退出。
-///
-/// Android 的 OomAdjuster 在 app 前后台切换时写入 oom_score_adj：
-/// - 前台: 0
-/// - 可见: 100
-/// - 后台/缓存: 200-900+
+/// 所有应用的进程组。Apply 请求登记进程，supervisor 移除已退出的进程。
 ///
-/// inotify IN_MODIFY 在 procfs 的 oom_score_adj 上已验证可用（Android 内核）。
-/// 使用 epoll 同时监听 inotify fd 和 pidfd，阻塞直到事件到达，零轮询。
-fn watch_via_inotify(
-    pid: u32,
-    props: &HashMap<String, String>,
-    delete_props: &[String],
-    backups: &[PropBackup],
-) -> anyhow::Result<()> {
-    const BACKGROUND_THRESHOLD: i32 = 200;
-    const BACKGROUND_DEBOUNCE: Duration = Duration::from_secs(2);
-
-    // pidfd：事件驱动检测 app 退出
-    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0u32) };
-    if pidfd < 0 {
-        anyhow::bail!("pidfd_open failed");
-    }
-    let pidfd = pidfd as i32;
-
-    // inotify：监听 oom_score_adj 变化
-    let ifd = unsafe { libc::inotify_init() };
-    if ifd < 0 {
-        unsafe { libc::close(pidfd) };
-        anyhow::bail!("inotify_init failed");
-    }
-    let oom_path = format!("/proc/{pid}/oom_score_adj\0");
-    let wd = unsafe {
-        libc::inotify_add_watch(
-            ifd,
-            oom_path.as_ptr() as *const libc::c_char,
-            libc::IN_MODIFY,
-        )
-    };
-    if wd < 0 {
-        unsafe {
-            libc::close(ifd);
-            libc::close(pidfd);
-        }
-        anyhow::bail!("inotify_add_watch on oom_score_adj failed");
-    }
-    let wd = wd as u32;
-
-    // epoll：同时监听 pidfd 和 inotify fd
-    let efd = unsafe { libc::epoll_create1(0) };
-    if efd < 0 {
-        unsafe {
-            libc::inotify_rm_watch(ifd, wd);
-            libc::close(ifd);
-            libc::close(pidfd);
-        }
-        anyhow::bail!("epoll_create1 failed");
-    }
-    let mut ev = libc::epoll_event {
-        events: libc::EPOLLIN as u32,
-        u64: pidfd as u64,
-    };
-    unsafe { libc::epoll_ctl(efd, libc::EPOLL_CTL_ADD, pidfd, &mut ev) };
-    ev.u64 = ifd as u64;
-    unsafe { libc::epoll_ctl(efd, libc::EPOLL_CTL_ADD, ifd, &mut ev) };
-
-    let mut is_spoof_applied = true;
-    let mut background_since: Option<Instant> = None;
-    let mut events = [libc::epoll_event { events: 0, u64: 0 }; 2];
-
-    info!("restore watcher: inotify monitoring oom_score_adj for pid {pid}");
-
-    loop {
-        let timeout = if let Some(bg_start) = background_since {
-            // 后台 debounce 等待中，计算剩余时间
-            let remaining = BACKGROUND_DEBOUNCE
-                .checked_sub(bg_start.elapsed())
-                .unwrap_or(Duration::ZERO);
-            remaining.as_millis() as i32
-        } else {
-            -1 // 无限阻塞
-        };
-
-        let nfds = unsafe { libc::epoll_wait(efd, events.as_mut_ptr(), 2, timeout) };
-
-        // debounce 到期检查
-        if let Some(bg_start) = background_since
-            && bg_start.elapsed() >= BACKGROUND_DEBOUNCE
-        {
-            if is_spoof_applied {
-                restore_props_batch(backups)?;
-                is_spoof_applied = false;
-                info!("restore watcher restored props for pid {pid}");
-            }
-            background_since = None;
-        }
-
-        if nfds < 0 {
-            let err = std::io::Error::last_os_error();
-            if err.kind() == std::io::ErrorKind::Interrupted {
-                continue;
-            }
-            warn!("restore watcher: epoll_wait error: {err}");
-            break;
-        }
-
-        if nfds == 0 {
-            // timeout — debounce 可能已处理
-            continue;
-        }
-
-        // 检查是否有进程退出事件
-        let process_exited = events
-            .iter()
-            .take(nfds as usize)
-            .any(|e| e.u64 == pidfd as u64);
-        if process_exited {
-            if is_spoof_applied {
-                restore_props_batch(backups)?;
-            }
-            info!("restore watcher: app pid {pid} exited (pidfd event)");
-            break;
-        }
-
-        // oom_score_adj 变化
-        for ev in events.iter().take(nfds as usize) {
-            if ev.u64 == ifd as u64 {
-                let mut buf = [0u8; 512];
-                let _ =
-                    unsafe { libc::read(ifd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
-
-                let oom_val = read_oom_score_adj(pid);
-                if oom_val >= BACKGROUND_THRESHOLD {
-                    let bg_start = *background_since.get_or_insert_with(Instant::now);
-                    if is_spoof_applied && bg_start.elapsed() >= BACKGROUND_DEBOUNCE {
-                        restore_props_batch(backups)?;
-                        is_spoof_applied = false;
-                        info!("restore watcher restored props for pid {pid} (oom={oom_val})");
-                        background_since = None;
-                    }
-                } else {
-                    background_since = None;
-                    if !is_spoof_applied {
-                        apply_props_batch(props, delete_props)?;
-                        is_spoof_applied = true;
-                        info!(
-                            "restore watcher re-applied spoof props for pid {pid} (oom={oom_val})"
-                        );
-                    }
-                }
-            }
-        }
-    }
+/// 与 `PROP_SESSIONS` 不嵌套加锁。
+pub(crate) static PROCESS_REGISTRY: LazyLock<Mutex<ProcessRegistry>> =
+    LazyLock::new(|| Mutex::new(ProcessRegistry::default()));
 
-    unsafe {
-        libc::epoll_ctl(efd, libc::EPOLL_CTL_DEL, ifd, std::ptr::null_mut());
-        libc::epoll_ctl(efd, libc::EPOLL_CTL_DEL, pidfd, std::ptr::null_mut());
-        libc::inotify_rm_watch(ifd, wd);
-        libc::close(efd);
-        libc::close(ifd);
-        libc::close(pidfd);
-    }
-    Ok(())
+/// 前后台切换时更新会话状态，由会话管理器决定属性的最终取值。
+pub(crate) fn set_session_active(session: SessionId, active: bool) -> anyhow::Result<()> {
+    PROP_SESSIONS.lock().unwrap().set_active(session, active)
 }
 
-/// 读取 /proc/<pid>/oom_score_adj，失败返回 0（视为前台）。
-fn read_oom_score_adj(pid: u32) -> i32 {
-    let path = format!("/proc/{pid}/oom_score_adj");
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| s.trim().parse::<i32>().ok())
-        .unwrap_or(0)
+/// 进程组的进程全部退出后关闭其会话。
+pub(crate) fn close_session(session: SessionId) {
+    PROP_SESSIONS.lock().unwrap().close(session);
 }
 
-/// 轮询回退方案：/proc/<pid>/cgroup 检查 top-app（与原实现相同）。
-fn watch_via_cgroup_polling(
-    pid: u32,
-    props: &HashMap<String, String>,
-    delete_props: &[String],
-    backups: &[PropBackup],
-) -> anyhow::Result<()> {
-    const POLL_INTERVAL: Duration = Duration::from_millis(200);
-    const BACKGROUND_DEBOUNCE: Duration = Duration::from_secs(2);
-
-    let proc_path = format!("/proc/{pid}");
-    let mut is_spoof_applied = true;
-    let mut background_since: Option<Instant> = None;
-
-    info!("restore watcher: cgroup polling for pid {pid}");
-
-    loop {
-        if !std::path::Path::new(&proc_path).exists() {
-            if is_spoof_applied {
-                restore_props_batch(backups)?;
-            }
-            break;
-        }
-
-        if is_process_in_top_app(pid) {
-            background_since = None;
-            if !is_spoof_applied {
-                apply_props_batch(props, delete_props)?;
-                is_spoof_applied = true;
-                info!("restore watcher re-applied spoof props for pid {pid}");
-            }
-        } else {
-            let bg_start = background_since.get_or_insert_with(Instant::now);
-            if is_spoof_applied && bg_start.elapsed() >= BACKGROUND_DEBOUNCE {
-                restore_props_batch(backups)?;
-                is_spoof_applied = false;
-                info!("restore watcher restored props for pid {pid}");
-            }
-        }
-
-        thread::sleep(POLL_INTERVAL);
-    }
-
-    Ok(())
-}
-
-fn apply_props_batch(
-    props: &HashMap<String, String>,
-    delete_props: &[String],
-) -> anyhow::Result<()> {
-    for (key, value) in props {
-        apply_resetprop(key, value)?;
-    }
-
-    for key in delete_props {
-        resetprop_delete(key)?;
-    }
-
-    rebuild_all_contexts(props.keys().chain(delete_props.iter()));
-
-    Ok(())
-}
-
-fn restore_props_batch(backups: &[PropBackup]) -> anyhow::Result<()> {
-    for entry in backups {
-        apply_resetprop(&entry.key, &entry.original_value)?;
+fn companion_status() -> CompanionStatus {
+    let sessions = PROP_SESSIONS.lock().unwrap().status();
+    let watchers = PROCESS_REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(package, group)| WatcherStatus {
+            package: package.clone(),
+            uid: group.uid,
+            pids: group.pids.iter().copied().collect(),
+            session: group.session,
+            restore_policy: group.policy.mode_name().to_string(),
+        })
+        .collect();
+    CompanionStatus {
+        sessions,
+        watchers,
+        mounts: crate::supervisor::overlay_status(),
     }
-
-    // Rebuild using the first backup's key to find the context.
-    rebuild_all_contexts(backups.iter().map(|b| &b.key));
-
-    Ok(())
 }
 
 const LOG_PATH: &str = "/data/adb/device_faker/logs/device_faker.log";
@@ -661,70 +648,47 @@ fn write_log_lines_to_path(path: &str, lines: &[String]) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn is_process_in_top_app(pid: u32) -> bool {
-    let cgroup_path = format!("/proc/{pid}/cgroup");
-    match fs::read_to_string(&cgroup_path) {
-        Ok(content) => content.lines().any(|line| line.contains("top-app")),
-        Err(_) => true,
-    }
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct ResetpropSessionRequest {
+    package: String,
     pid: u32,
+    /// 应用 uid，同包名同 uid 的进程归入同一进程组
+    #[serde(default)]
+    uid: u32,
     props: HashMap<String, String>,
     delete_props: Vec<String>,
+    #[serde(default)]
+    restore_policy: RestorePolicy,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct RestoreRequest {
-    props: HashMap<String, String>,
+    package: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "cmd", content = "payload")]
 pub enum CompanionRequest {
+    Hello(Hello),
     Apply(ResetpropSessionRequest),
     Restore(RestoreRequest),
     CpuSpoof(CpuSpoofRequest),
     WriteLog(WriteLogRequest),
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CompanionResponse {
-    pub status: i32,
-    pub message: Option<String>,
-    pub backups: Option<HashMap<String, String>>,
-}
-
-impl CompanionResponse {
-    pub fn ok() -> Self {
-        Self {
-            status: 0,
-            message: None,
-            backups: None,
+    /// 查询当前的属性会话、进程组与覆盖文件挂载
+    Status,
+    /// 关闭全部会话并写回所有属性原值
+    RestoreAll,
+}
+
+impl CompanionRequest {
+    /// 处理该请求所需的 companion 能力。
+    fn required_capabilities(&self) -> Vec<&'static str> {
+        match self {
+            Self::Hello(_) => Vec::new(),
+            Self::Apply(_) | Self::Restore(_) | Self::RestoreAll => vec![CAP_RESETPROP],
+            Self::CpuSpoof(request) => request.required_capabilities(),
+            Self::WriteLog(_) => vec![CAP_WRITE_LOG],
+            Self::Status => vec![CAP_STATUS],
         }
     }
-
-    pub fn err(msg: impl Into<String>) -> Self {
-        Self {
-            status: -1,
-            message: Some(msg.into()),
-            backups: None,
-        }
-    }
-
-    pub fn ok_with_backups(backups: HashMap<String, String>) -> Self {
-        Self {
-            status: 0,
-            message: None,
-            backups: Some(backups),
-        }
-    }
-}
-
-#[derive(Clone)]
-struct PropBackup {
-    key: String,
-    original_value: String,
 }
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::spoof_features::SpoofFeatures;
 
 /// 机型模板
 #[derive(Debug, Clone, Deserialize)]
@@ -51,6 +53,50 @@ pub struct DeviceTemplate {
     /// 自定义 CPU 伪装内容（优先级高于 cpu_spoof）
     #[serde(default)]
     pub cpu_spoof_custom: Option<String>,
+    /// GPU 标识伪装（仅 full 模式支持）
+    #[serde(default)]
+    pub gpu: Option<GpuConfig>,
+    /// 屏幕参数伪装（分辨率、密度）
+    #[serde(default)]
+    pub display: Option<DisplayConfig>,
+    /// 伪装的总内存（MB），用于 ActivityManager.MemoryInfo、Process.getTotalMemory()，
+    /// 以及开启 `meminfo_overlay` 时的 /proc/meminfo
+    #[serde(default)]
+    pub ram_total_mb: Option<u64>,
+    /// 按 `ram_total_mb` 覆盖 /proc/meminfo。覆盖内容是 app 启动时的快照，之后不再变化
+    #[serde(default)]
+    pub meminfo_overlay: Option<bool>,
+    /// 伪装的总存储容量（GB），用于 StatFs（仅 full 模式）
+    #[serde(default)]
+    pub storage_total_gb: Option<u64>,
+    /// 电池参数伪装（companion 模式，覆盖 power_supply sysfs）
+    #[serde(default)]
+    pub battery: Option<BatteryConfig>,
+    /// 温控区名称覆盖：真实 type → 伪装 type（companion 模式）
+    #[serde(default)]
+    pub thermal_zones: Option<HashMap<String, String>>,
+    /// app 首次读取 /proc/cpuinfo 后卸载其覆盖挂载，之后读到真实内容
+    #[serde(default)]
+    pub cpuinfo_unmount_after_read: Option<bool>,
+    /// 覆盖文件（cpuinfo/meminfo/battery/thermal_zones）源文件的 SELinux label，
+    /// `"target"` 表示沿用目标文件的 label（默认）
+    #[serde(default)]
+    pub overlay_labels: Option<HashMap<String, String>>,
+    /// 需要额外声明的系统特性（`name` 或 `name=version`，仅 full 模式）
+    #[serde(default)]
+    pub add_features: Option<Vec<String>>,
+    /// 需要隐藏的系统特性（仅 full 模式）
+    #[serde(default)]
+    pub remove_features: Option<Vec<String>>,
+    /// 伪装属性的还原策略（仅 companion 模式写入全局属性区的会话）
+    #[serde(default)]
+    pub restore_policy: Option<RestorePolicy>,
+    /// 逐项覆盖 `mode` 预设的伪装功能
+    #[serde(default)]
+    pub spoof_features: Option<SpoofFeatureOverrides>,
+    /// native 属性读取的伪装方式（`native_prop_hook` 启用时）
+    #[serde(default)]
+    pub native_prop_backend: Option<NativePropBackend>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -93,6 +139,9 @@ pub struct AppConfig {
     /// - "lite": 只修改 Build 类（轻量模式，可卸载模块）
     /// - "full": Build + SystemProperties Hook（完整模式，不可卸载）
     /// - "companion": Build + companion resetprop + 可选 CPU 伪装（可卸载模块）
+    /// - "isolated": Build + app 私有属性区副本 + 可选 CPU 伪装（可卸载模块，不修改全局属性）
+    ///
+    /// 各模式只是伪装功能的预设，可用 `spoof_features` 逐项调整
     #[serde(default)]
     pub mode: Option<String>,
     /// CPU 伪装预设名称（引用 [cpu_presets]）
@@ -101,11 +150,235 @@ pub struct AppConfig {
     /// 自定义 CPU 伪装内容（优先级高于 cpu_spoof）
     #[serde(default)]
     pub cpu_spoof_custom: Option<String>,
+    /// GPU 标识伪装（仅 full 模式支持）
+    #[serde(default)]
+    pub gpu: Option<GpuConfig>,
+    /// 屏幕参数伪装（分辨率、密度）
+    #[serde(default)]
+    pub display: Option<DisplayConfig>,
+    /// 伪装的总内存（MB），用于 ActivityManager.MemoryInfo、Process.getTotalMemory()，
+    /// 以及开启 `meminfo_overlay` 时的 /proc/meminfo
+    #[serde(default)]
+    pub ram_total_mb: Option<u64>,
+    /// 按 `ram_total_mb` 覆盖 /proc/meminfo。覆盖内容是 app 启动时的快照，之后不再变化
+    #[serde(default)]
+    pub meminfo_overlay: Option<bool>,
+    /// 伪装的总存储容量（GB），用于 StatFs（仅 full 模式）
+    #[serde(default)]
+    pub storage_total_gb: Option<u64>,
+    /// 电池参数伪装（companion 模式，覆盖 power_supply sysfs）
+    #[serde(default)]
+    pub battery: Option<BatteryConfig>,
+    /// 温控区名称覆盖：真实 type → 伪装 type（companion 模式）
+    #[serde(default)]
+    pub thermal_zones: Option<HashMap<String, String>>,
+    /// app 首次读取 /proc/cpuinfo 后卸载其覆盖挂载，之后读到真实内容
+    #[serde(default)]
+    pub cpuinfo_unmount_after_read: Option<bool>,
+    /// 覆盖文件（cpuinfo/meminfo/battery/thermal_zones）源文件的 SELinux label，
+    /// `"target"` 表示沿用目标文件的 label（默认）
+    #[serde(default)]
+    pub overlay_labels: Option<HashMap<String, String>>,
+    /// 需要额外声明的系统特性（`name` 或 `name=version`，仅 full 模式）
+    #[serde(default)]
+    pub add_features: Option<Vec<String>>,
+    /// 需要隐藏的系统特性（仅 full 模式）
+    #[serde(default)]
+    pub remove_features: Option<Vec<String>>,
+    /// 伪装属性的还原策略（仅 companion 模式写入全局属性区的会话）
+    #[serde(default)]
+    pub restore_policy: Option<RestorePolicy>,
+    /// 逐项覆盖 `mode` 预设的伪装功能
+    #[serde(default)]
+    pub spoof_features: Option<SpoofFeatureOverrides>,
+    /// native 属性读取的伪装方式（`native_prop_hook` 启用时）
+    #[serde(default)]
+    pub native_prop_backend: Option<NativePropBackend>,
+}
+
+/// GPU 标识伪装配置
+///
+/// OpenGL ES 字段对应 `glGetString` 的返回值，Vulkan 字段对应
+/// `VkPhysicalDeviceProperties` 中的同名成员。未设置的字段保持真实值。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GpuConfig {
+    /// `glGetString(GL_VENDOR)`（如 "Qualcomm"）
+    #[serde(default)]
+    pub vendor: Option<String>,
+    /// `glGetString(GL_RENDERER)`（如 "Adreno (TM) 750"）
+    #[serde(default)]
+    pub renderer: Option<String>,
+    /// `glGetString(GL_VERSION)`（如 "OpenGL ES 3.2 V@0762.0"）
+    #[serde(default)]
+    pub version: Option<String>,
+    /// `VkPhysicalDeviceProperties.deviceName`
+    #[serde(default)]
+    pub vulkan_device_name: Option<String>,
+    /// `VkPhysicalDeviceProperties.deviceID`（如 0x43050a01）
+    #[serde(default)]
+    pub vulkan_device_id: Option<u32>,
+    /// `VkPhysicalDeviceProperties.vendorID`（如 0x5143）
+    #[serde(default)]
+    pub vulkan_vendor_id: Option<u32>,
+}
+
+/// 屏幕参数伪装配置
+///
+/// `width`/`height` 按竖屏自然方向填写。full 模式改写 system_server 返回的 `DisplayInfo`，
+/// full/companion 模式同时伪装 `ro.sf.lcd_density`。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DisplayConfig {
+    /// 屏幕宽度（像素）
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// 屏幕高度（像素）
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// 屏幕密度（如 480、560）
+    #[serde(default)]
+    pub density_dpi: Option<u32>,
+    /// 当前刷新率（Hz，如 120）
+    #[serde(default)]
+    pub refresh_rate: Option<f32>,
+    /// `Display.getSupportedModes` 返回的刷新率列表
+    #[serde(default)]
+    pub supported_refresh_rates: Option<Vec<f32>>,
+}
+
+/// 电池参数伪装配置
+///
+/// 对应 `/sys/class/power_supply/battery/` 下的同名文件。容量单位为 mAh，
+/// 写入 sysfs 时换算为 µAh。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatteryConfig {
+    /// 设计容量（如 6000）
+    #[serde(default)]
+    pub charge_full_design_mah: Option<u64>,
+    /// 当前满充容量，未设置时按真实电池健康度从设计容量推算
+    #[serde(default)]
+    pub charge_full_mah: Option<u64>,
+    /// 电池技术（如 "Li-poly"、"Li-ion"）
+    #[serde(default)]
+    pub technology: Option<String>,
+}
+
+/// 伪装功能开关，未设置的项沿用 `mode` 对应的预设
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct SpoofFeatureOverrides {
+    /// Build 类静态字段
+    #[serde(default)]
+    pub build_fields: Option<bool>,
+    /// SystemProperties 等 Java hook
+    #[serde(default)]
+    pub java_prop_hook: Option<bool>,
+    /// native 属性读取与 GPU 标识 hook
+    #[serde(default)]
+    pub native_prop_hook: Option<bool>,
+    /// companion resetprop 修改全局属性
+    #[serde(default)]
+    pub resetprop: Option<bool>,
+    /// /proc/cpuinfo 覆盖
+    #[serde(default)]
+    pub cpuinfo: Option<bool>,
+    /// /proc/meminfo、电池与温控区覆盖
+    #[serde(default)]
+    pub overlays: Option<bool>,
+    /// app 私有属性区副本
+    #[serde(default)]
+    pub isolated_props: Option<bool>,
+}
+
+/// native 属性读取的伪装方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NativePropBackend {
+    /// PLT hook `__system_property_get`，需修改已加载库的 GOT 表
+    #[default]
+    PltHook,
+    /// companion 挂载的 app 私有属性区副本，不修改 GOT
+    PropertyArea,
+}
+
+impl NativePropBackend {
+    /// 配置中的名称。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PltHook => "plt_hook",
+            Self::PropertyArea => "property_area",
+        }
+    }
+
+    /// 该方式留下的可检测痕迹。
+    pub fn detectability(self) -> &'static str {
+        match self {
+            Self::PltHook => {
+                "GOT entries of loaded libraries are patched, visible to GOT/PLT integrity checks"
+            }
+            Self::PropertyArea => {
+                "no GOT changes, but property area copies show up as bind mounts in mountinfo \
+                 and are a snapshot taken at launch"
+            }
+        }
+    }
+}
+
+/// companion 模式下 restore watcher 何时把伪装属性恢复为原值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RestorePolicy {
+    /// 进程存活期间始终保持伪装，退出后还原
+    OnExitOnly,
+    /// 切到后台超过 `debounce_ms` 后还原，回到前台时重新应用（默认）
+    OnBackground {
+        /// oom_score_adj 不低于该值视为后台
+        #[serde(default = "default_background_threshold")]
+        threshold: i32,
+        #[serde(default = "default_background_debounce_ms")]
+        debounce_ms: u64,
+        /// 无法使用 inotify 时的轮询间隔
+        #[serde(default = "default_poll_interval_ms")]
+        poll_interval_ms: u64,
+    },
+    /// 不自动还原，进程退出后伪装值仍然保留，直到下次启动同一应用或重启
+    Never,
+}
+
+impl Default for RestorePolicy {
+    fn default() -> Self {
+        Self::OnBackground {
+            threshold: default_background_threshold(),
+            debounce_ms: default_background_debounce_ms(),
+            poll_interval_ms: default_poll_interval_ms(),
+        }
+    }
+}
+
+impl RestorePolicy {
+    /// 配置中的 `mode` 名称。
+    pub fn mode_name(&self) -> &'static str {
+        match self {
+            Self::OnExitOnly => "on_exit_only",
+            Self::OnBackground { .. } => "on_background",
+            Self::Never => "never",
+        }
+    }
+}
+
+fn default_background_threshold() -> i32 {
+    200
+}
+
+fn default_background_debounce_ms() -> u64 {
+    2000
+}
+
+fn default_poll_interval_ms() -> u64 {
+    200
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
-    /// 全局默认模式："lite", "full" 或 "companion"
+    /// 全局默认模式："lite", "full", "companion" 或 "isolated"
     #[serde(default = "default_mode")]
     pub default_mode: String,
     /// 是否默认启用 FORCE_DENYLIST_UNMOUNT（避免模块挂载痕迹）
@@ -134,7 +407,25 @@ fn default_mode() -> String {
 
 impl Config {
     pub fn from_toml(content: &str) -> Result<Self> {
-        Ok(toml::from_str(content)?)
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 检查各应用与模板的伪装功能组合。
+    fn validate(&self) -> Result<()> {
+        SpoofFeatures::preset(&self.default_mode).context("invalid default_mode")?;
+        for app in &self.apps {
+            let mode = app.mode.as_deref().unwrap_or(&self.default_mode);
+            SpoofFeatures::resolve(mode, &app.spoof_features.unwrap_or_default())
+                .with_context(|| format!("invalid config for app {}", app.package))?;
+        }
+        for (name, template) in &self.templates {
+            let mode = template.mode.as_deref().unwrap_or(&self.default_mode);
+            SpoofFeatures::resolve(mode, &template.spoof_features.unwrap_or_default())
+                .with_context(|| format!("invalid config for template {name}"))?;
+        }
+        Ok(())
     }
 
     /// 查找包名对应的应用配置（优先）或模板配置
@@ -177,6 +468,20 @@ impl Config {
                 cpu_spoof: app.cpu_spoof.clone(),
                 cpu_spoof_custom: app.cpu_spoof_custom.clone(),
                 cpuinfo_content: None,
+                gpu: app.gpu.clone(),
+                display: app.display.clone(),
+                ram_total_mb: app.ram_total_mb,
+                meminfo_overlay: app.meminfo_overlay.unwrap_or(false),
+                storage_total_gb: app.storage_total_gb,
+                battery: app.battery.clone(),
+                thermal_zones: app.thermal_zones.clone(),
+                cpuinfo_unmount_after_read: app.cpuinfo_unmount_after_read.unwrap_or(false),
+                overlay_labels: app.overlay_labels.clone().unwrap_or_default(),
+                add_features: app.add_features.clone(),
+                remove_features: app.remove_features.clone(),
+                restore_policy: app.restore_policy.unwrap_or_default(),
+                spoof_features: app.spoof_features.unwrap_or_default(),
+                native_prop_backend: app.native_prop_backend.unwrap_or_default(),
             };
             merged.cpuinfo_content = merged.resolve_cpuinfo(self);
             return Some(merged);
@@ -208,6 +513,20 @@ impl Config {
                 cpu_spoof: template.cpu_spoof.clone(),
                 cpu_spoof_custom: template.cpu_spoof_custom.clone(),
                 cpuinfo_content: None,
+                gpu: template.gpu.clone(),
+                display: template.display.clone(),
+                ram_total_mb: template.ram_total_mb,
+                meminfo_overlay: template.meminfo_overlay.unwrap_or(false),
+                storage_total_gb: template.storage_total_gb,
+                battery: template.battery.clone(),
+                thermal_zones: template.thermal_zones.clone(),
+                cpuinfo_unmount_after_read: template.cpuinfo_unmount_after_read.unwrap_or(false),
+                overlay_labels: template.overlay_labels.clone().unwrap_or_default(),
+                add_features: template.add_features.clone(),
+                remove_features: template.remove_features.clone(),
+                restore_policy: template.restore_policy.unwrap_or_default(),
+                spoof_features: template.spoof_features.unwrap_or_default(),
+                native_prop_backend: template.native_prop_backend.unwrap_or_default(),
             };
             merged.cpuinfo_content = merged.resolve_cpuinfo(self);
             return Some(merged);
@@ -313,6 +632,10 @@ impl Config {
             map.insert("ro.product.build.version.sdk".to_string(), sdk_str.clone());
         }
 
+        if let Some(density_dpi) = merged.display.as_ref().and_then(|d| d.density_dpi) {
+            map.insert("ro.sf.lcd_density".to_string(), density_dpi.to_string());
+        }
+
         // 自定义属性
         if let Some(custom_props) = &merged.custom_props {
             for (key, value) in custom_props {
@@ -425,6 +748,34 @@ pub struct MergedAppConfig {
     pub cpu_spoof_custom: Option<String>,
     /// 最终要挂载到 /proc/cpuinfo 的内容（已解析完成）
     pub cpuinfo_content: Option<String>,
+    /// GPU 标识伪装
+    pub gpu: Option<GpuConfig>,
+    /// 屏幕参数伪装
+    pub display: Option<DisplayConfig>,
+    /// 伪装的总内存（MB）
+    pub ram_total_mb: Option<u64>,
+    /// 按伪装总内存覆盖 /proc/meminfo
+    pub meminfo_overlay: bool,
+    /// 伪装的总存储容量（GB）
+    pub storage_total_gb: Option<u64>,
+    /// 电池参数伪装
+    pub battery: Option<BatteryConfig>,
+    /// 温控区名称覆盖
+    pub thermal_zones: Option<HashMap<String, String>>,
+    /// 读取 /proc/cpuinfo 后卸载覆盖挂载
+    pub cpuinfo_unmount_after_read: bool,
+    /// 覆盖文件源文件的 SELinux label
+    pub overlay_labels: HashMap<String, String>,
+    /// 额外声明的系统特性
+    pub add_features: Option<Vec<String>>,
+    /// 隐藏的系统特性
+    pub remove_features: Option<Vec<String>>,
+    /// 伪装属性的还原策略
+    pub restore_policy: RestorePolicy,
+    /// 覆盖模式预设的伪装功能
+    pub spoof_features: SpoofFeatureOverrides,
+    /// native 属性读取的伪装方式
+    pub native_prop_backend: NativePropBackend,
 }
 
 impl MergedAppConfig {
@@ -447,7 +798,7 @@ impl MergedAppConfig {
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Config, RestorePolicy};
 
     #[test]
     fn merged_config_includes_build_id_from_app_or_template() {
@@ -511,4 +862,88 @@ build_id = "__DELETE__"
             assert!(delete_props.iter().any(|prop| prop == key));
         }
     }
+
+    #[test]
+    fn display_density_maps_to_lcd_density_property() {
+        let config = Config::from_toml(
+            r#"
+[[apps]]
+package = "com.example.app"
+
+[apps.display]
+width = 1440
+height = 3200
+density_dpi = 560
+"#,
+        )
+        .unwrap();
+
+        let merged = config.get_merged_config("com.example.app").unwrap();
+        let display = merged.display.as_ref().unwrap();
+        assert_eq!((display.width, display.height), (Some(1440), Some(3200)));
+
+        let prop_map = Config::build_merged_property_map(&merged);
+        assert_eq!(
+            prop_map.get("ro.sf.lcd_density").map(String::as_str),
+            Some("560")
+        );
+    }
+
+    #[test]
+    fn restore_policy_defaults_and_overrides() {
+        let config = Config::from_toml(
+            r#"
+[[apps]]
+package = "com.example.default"
+
+[[apps]]
+package = "com.example.exit"
+restore_policy = { mode = "on_exit_only" }
+
+[[apps]]
+package = "com.example.slow"
+restore_policy = { mode = "on_background", debounce_ms = 10000 }
+"#,
+        )
+        .unwrap();
+
+        let merged = |package| config.get_merged_config(package).unwrap().restore_policy;
+        assert_eq!(merged("com.example.default"), RestorePolicy::default());
+        assert_eq!(merged("com.example.exit"), RestorePolicy::OnExitOnly);
+        assert_eq!(
+            merged("com.example.slow"),
+            RestorePolicy::OnBackground {
+                threshold: 200,
+                debounce_ms: 10000,
+                poll_interval_ms: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn resetprop_with_isolated_props_is_rejected() {
+        let err = Config::from_toml(
+            r#"
+[templates.pixel]
+packages = ["com.example.app"]
+mode = "isolated"
+spoof_features = { resetprop = true }
+"#,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("template pixel"));
+    }
+
+    #[test]
+    fn unknown_mode_is_rejected() {
+        let err = Config::from_toml(
+            r#"
+[[apps]]
+package = "com.example.app"
+mode = "stealth"
+"#,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("unknown mode 'stealth'"));
+    }
 }
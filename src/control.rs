@@ -0,0 +1,95 @@
+// companion 的本地控制接口，供 device_faker_cli 查询状态与强制还原。
+//
+// 模块经 Zygisk 提供的连接与 companion 通信，CLI 无法使用这条通道，因此 companion
+// 另外在 `CONTROL_SOCKET_PATH` 上监听一个 unix socket。连接使用与模块相同的帧格式
+// 与握手，只接受 `Status`、`Restore` 与 `RestoreAll` 请求，且仅限 root 发起。
+//
+// 模块在 system_server 启动时唤起 companion（见 `start_companion`），此前 socket 不存在。
+
+use std::{
+    fs,
+    os::{
+        fd::AsRawFd,
+        unix::{
+            fs::PermissionsExt,
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::Path,
+    sync::LazyLock,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+
+use crate::protocol::CONTROL_SOCKET_PATH;
+
+static CONTROL_LISTENER: LazyLock<()> = LazyLock::new(|| {
+    if let Err(e) = start_listener() {
+        error!("Failed to start control socket: {e:#}");
+    }
+});
+
+/// 启动控制 socket 监听线程，仅第一次调用生效。
+pub(crate) fn start() {
+    LazyLock::force(&CONTROL_LISTENER);
+}
+
+fn start_listener() -> Result<()> {
+    let path = Path::new(CONTROL_SOCKET_PATH);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    // 上一个 companion 进程留下的 socket 文件
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind {CONTROL_SOCKET_PATH}"))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    thread::Builder::new()
+        .name("df-control".to_string())
+        .spawn(move || serve(listener))?;
+    info!("control socket listening on {CONTROL_SOCKET_PATH}");
+    Ok(())
+}
+
+/// 逐个处理连接。请求只读写内存状态，耗时很短，不为每个连接开线程。
+fn serve(listener: UnixListener) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("control: accept failed: {e}");
+                continue;
+            }
+        };
+        // 连接逐个处理，不能让一个不发数据的客户端卡住监听线程
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        match peer_uid(&stream) {
+            Some(0) => crate::companion::handle_control_request(&mut stream),
+            uid => warn!("control: rejected connection from uid {uid:?}"),
+        }
+    }
+}
+
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(cred.uid)
+}
@@ -1,21 +1,34 @@
 use std::{
+    collections::HashMap,
     ffi::CString,
     fs,
-    io::{Read, Write},
     os::unix::io::AsRawFd,
     os::unix::net::UnixStream,
+    path::Path,
     sync::atomic::{AtomicBool, AtomicI32, Ordering},
 };
 
 use anyhow::{Context, Result};
-use libc::MS_BIND;
 use log::{error, info, warn};
 
-use crate::companion::{CompanionRequest, CompanionResponse, write_companion_response};
-use crate::config::MergedAppConfig;
+use crate::companion::{CompanionRequest, exchange, write_companion_response};
+use crate::config::{Config, MergedAppConfig};
+use crate::detached_mount::{self, AttachError};
+use crate::mount_child::{MountPlan, fork_mount_child, read_ns_ino};
+use crate::mount_hiding::{self, staged_name};
+use crate::mount_verify::verify_overlays;
+use crate::overlay::{render_battery_files, render_meminfo};
+use crate::overlay_label;
+use crate::overlay_store;
+use crate::prop_isolation::{
+    OverlayPropertyAreas, PROPERTIES_DIR, prepare_property_areas, remap_property_areas,
+};
+use crate::protocol::{CompanionResponse, ErrorCode};
+use crate::spoof_features::SpoofFeatures;
 use zygisk_api::api::{V4, ZygiskApi};
 
-// bind mount 的源文件放在 /data/adb/device_faker/cpu/ 下。
+// 暂存 namespace 可用时源文件放在 tmpfs 实例中（见 `mount_hiding`），否则放在
+// /data/adb/device_faker/cpu/ 下。
 // 之所以不放 /data/local/tmp/ 是为了规避检测：部分检测器（如 Duck-Detector 的
 // ShellTmpConcealmentProbe）会扫描 /proc/self/mountinfo，对挂载点落在
 // /data/local/tmp 及其子路径下的挂载报 "Shell tmp dedicated mount" 风险。
@@ -25,14 +38,18 @@ use zygisk_api::api::{V4, ZygiskApi};
 // 路径解析到源文件的 inode，SELinux 检查的是**源文件 inode 的 label**，
 // 而非 mount point 的 label。/data/adb/device_faker/ 目录的默认 label
 // （adb_data_file:s0 等）untrusted_app 无权读取，会导致 app open(/proc/cpuinfo)
-// 返回 EACCES。因此 companion 创建目录和源文件后必须把它们的 label 改成
-// app 可读的 system_file:s0（与 customize.sh 对 config 文件的处理一致）。
+// 返回 EACCES。因此 companion 创建源文件后必须把 label 改成 app 可读的
+// label，挂载前确定（见 `overlay_label`）；目录改成 system_file:s0（与
+// customize.sh 对 config 文件的处理一致）。
 // cpuwz 之所以不需要这一步，是因为它的源文件是模块安装时的静态文件，
 // 已被 Magisk/KSU 框架的 set_perm_recursive 赋予了可读 label。
-const CPU_SPOOF_STATE_DIR: &str = "/data/adb/device_faker/cpu";
+pub(crate) const CPU_SPOOF_STATE_DIR: &str = "/data/adb/device_faker/cpu";
 const PROC_CPUINFO: &str = "/proc/cpuinfo";
+const PROC_MEMINFO: &str = "/proc/meminfo";
+const BATTERY_SYSFS_DIR: &str = "/sys/class/power_supply/battery";
+const THERMAL_SYSFS_DIR: &str = "/sys/class/thermal";
 // app 可读的 SELinux label，与 customize.sh 对 config 文件设置的一致。
-const SELINUX_CONTEXT: &str = "u:object_r:system_file:s0";
+pub(crate) const SELINUX_CONTEXT: &str = "u:object_r:system_file:s0";
 
 /// 在 app specialize 时触发 CPU 伪装。
 /// 通过 companion 进程在目标应用的 mount namespace 中执行 bind mount。
@@ -50,33 +67,64 @@ const SELINUX_CONTEXT: &str = "u:object_r:system_file:s0";
 /// 与 socket fd 完全独立，无 fd 继承问题。
 static LEAKED_FD: AtomicI32 = AtomicI32::new(-1);
 
-/// 源文件路径是否已初始化。
-static UNSHARE_HOOK_INITIALIZED: AtomicBool = AtomicBool::new(false);
-
-/// 泄漏的 CString，持有 fake cpuinfo 源文件路径。
-/// 在 init_unshare_hook_state 中设置，永不 drop（hook 需要 'static 生命周期）。
-static mut UNSHARE_SOURCE_CSTRING: Option<CString> = None;
-
-/// fake cpuinfo 源文件的 C 字符串指针，由 UNSHARE_SOURCE_CSTRING 持有。
-/// 仅在 UNSHARE_HOOK_INITIALIZED 为 true 后访问。
-static mut UNSHARE_SOURCE_PTR: *const libc::c_char = std::ptr::null();
+/// 内核是否支持 open_tree/move_mount，首次遇到 ENOSYS 后置为 false。
+static DETACHED_MOUNT_SUPPORTED: AtomicBool = AtomicBool::new(true);
 
 /// **Socket 生命周期**：`with_companion` 内部的 `companion_sock` 是局部变量，
 /// 闭包返回后自动 drop 关闭 fd。因此我们在闭包内调用 `libc::dup()` 复制 fd，
 /// 将副本存入 `LEAKED_FD`。原始 fd 随闭包结束关闭，副本保持打开。
 /// 但注意：副本在 `apply_cpu_spoof` 中被**立即关闭**，不会泄漏到 app 进程。
-/// app 退出检测由 companion 侧的 pipe EOF 完成。
+/// app 退出检测由 companion 的 supervisor 通过 pidfd 完成。
+///
+/// 只请求 `features` 启用的覆盖文件。使用私有属性区副本时（isolated 模式或
+/// `property_area` 方式）属性也通过副本挂载，挂载完成后重新映射本进程从 zygote
+/// 继承的属性区。
 pub fn apply_cpu_spoof(
     api: &mut ZygiskApi<V4>,
     merged: &MergedAppConfig,
     package_name: &str,
+    features: &SpoofFeatures,
     debug: bool,
 ) -> anyhow::Result<()> {
-    let Some(content) = &merged.cpuinfo_content else {
-        return Ok(());
+    let isolate_props = features.property_area_copies();
+    let cpuinfo = merged
+        .cpuinfo_content
+        .as_deref()
+        .filter(|c| features.cpuinfo && !c.is_empty())
+        .filter(|content| {
+            let valid = !content.contains('\0');
+            if !valid {
+                warn!("Skipping cpuinfo overlay for {package_name}: content contains a NUL byte");
+            }
+            valid
+        });
+    let system_files = features.overlays;
+    let (props, delete_props) = if isolate_props {
+        (
+            Config::build_merged_property_map_for_resetprop(merged),
+            Config::build_delete_props_list(merged),
+        )
+    } else {
+        Default::default()
     };
-
-    if content.is_empty() {
+    let spoof_request = crate::companion::CpuSpoofRequest {
+        pid: std::process::id(),
+        content: cpuinfo.unwrap_or_default().to_string(),
+        ram_total_mb: merged
+            .ram_total_mb
+            .filter(|_| system_files && merged.meminfo_overlay),
+        battery: merged.battery.clone().filter(|_| system_files),
+        thermal_zones: merged
+            .thermal_zones
+            .clone()
+            .filter(|_| system_files)
+            .unwrap_or_default(),
+        props,
+        delete_props,
+        unmount_cpuinfo_after_read: merged.cpuinfo_unmount_after_read,
+        overlay_labels: merged.overlay_labels.clone(),
+    };
+    if !spoof_request.has_overlays() {
         return Ok(());
     }
 
@@ -84,26 +132,33 @@ pub fn apply_cpu_spoof(
         info!("Applying CPU spoof for {package_name}");
     }
 
-    init_unshare_hook_state(content);
-
-    let request = CompanionRequest::CpuSpoof(crate::companion::CpuSpoofRequest {
-        pid: std::process::id(),
-        content: content.clone(),
-    });
+    let request = CompanionRequest::CpuSpoof(spoof_request);
 
-    let response = send_companion_command_leak_fd(api, &request)?;
+    let response = send_companion_command_leak_fd(api, &request);
 
     let leaked = LEAKED_FD.swap(-1, Ordering::SeqCst);
     if leaked >= 0 {
         unsafe { libc::close(leaked) };
     }
 
-    if response.status != 0 {
-        anyhow::bail!(
-            response
-                .message
-                .unwrap_or_else(|| "companion cpu spoof failed".to_string())
-        );
+    let checks = response?.overlay_checks.unwrap_or_default();
+    let failed: Vec<_> = checks.iter().filter(|check| !check.passed()).collect();
+    for check in &failed {
+        warn!("Overlay check failed for {package_name}: {check}");
+    }
+
+    if isolate_props {
+        // 属性区副本不可用时重新映射会让 app 读不到任何属性，保留 zygote 继承的映射
+        if failed
+            .iter()
+            .any(|check| check.target.starts_with(PROPERTIES_DIR))
+        {
+            anyhow::bail!("property area overlays failed verification for {package_name}");
+        }
+        let remapped = remap_property_areas()?;
+        if debug {
+            info!("Remapped {remapped} property area(s) for {package_name}");
+        }
     }
 
     if debug {
@@ -123,20 +178,9 @@ fn send_companion_command_leak_fd(
     api: &mut ZygiskApi<V4>,
     request: &CompanionRequest,
 ) -> anyhow::Result<CompanionResponse> {
-    let payload = serde_json::to_vec(request)?;
     let response = api
         .with_companion(|stream| -> anyhow::Result<CompanionResponse> {
-            stream.write_all(&(payload.len() as u32).to_le_bytes())?;
-            stream.write_all(&payload)?;
-            stream.flush()?;
-
-            let mut len_buf = [0u8; 4];
-            stream.read_exact(&mut len_buf)?;
-            let resp_len = u32::from_le_bytes(len_buf) as usize;
-            let mut resp_buf = vec![0u8; resp_len];
-            stream.read_exact(&mut resp_buf)?;
-
-            let resp = serde_json::from_slice::<CompanionResponse>(&resp_buf)?;
+            let resp = exchange(stream, request)?;
 
             // dup'd fd 仅用于保持 socket 打开直到响应读取完成，之后立即关闭。
             // app 退出检测由 companion 侧的 pidfd + poll 完成。
@@ -154,37 +198,16 @@ fn send_companion_command_leak_fd(
     Ok(response)
 }
 
-// ---------------------------------------------------------------------------
-// unshare PLT hook 实现
-// ---------------------------------------------------------------------------
-
-/// 初始化 unshare hook 的全局状态（源文件路径）。
-/// 必须在 `register_unshare_hook` 之前调用，且在 app 进程中（非 companion）。
-fn init_unshare_hook_state(source_path: &str) {
-    // 安全：单线程环境（pre_app_specialize），写入后不再修改。
-    unsafe {
-        let cs = CString::new(source_path).expect("source_path contained NUL");
-        UNSHARE_SOURCE_PTR = cs.as_ptr();
-        UNSHARE_SOURCE_CSTRING = Some(cs);
-        UNSHARE_HOOK_INITIALIZED.store(true, Ordering::Release);
-    }
-    info!("unshare hook state initialized: {source_path}");
-}
-
-// ---------------------------------------------------------------------------
-// PLT hook 实现已移除：plt_hook_commit 修改 GOT 表会触发检测类 app 的 anti-tampering。
-// CPU spoof 当前仅依赖 companion 的 bind mount + mount child 的 timerfd namespace 检测。
 // ---------------------------------------------------------------------------
 // PLT hook 实现已移除：plt_hook_commit 修改 GOT 表会触发检测类 app 的 anti-tampering。
-// CPU spoof 当前仅依赖 companion 的 bind mount + mount child 的 timerfd namespace 检测。
+// CPU spoof 当前仅依赖 companion 的 bind mount 与 supervisor 的 namespace 跟踪。
+// native 属性伪装同样可改用私有属性区副本（`native_prop_backend = "property_area"`）。
 // ---------------------------------------------------------------------------
 
 /// Companion 进程入口：处理 CPU 伪装请求。
 ///
-/// **进程退出检测方案：pipe EOF 事件驱动**
-///
-/// Companion 从 exit pipe 读取 EOF 来检测 app 退出，mount child 负责 pidfd 监控。
-/// 每个 companion 连接是独立的，阻塞不影响其他 app 的 companion 请求。
+/// 挂载完成后自检并随应答返回结果，连接线程不再阻塞到 app 退出。
+/// 此后 app 的 namespace 切换、挂载被移除与退出后的源文件清理都交给 supervisor。
 pub fn handle_companion_cpu_spoof(
     stream: &mut UnixStream,
     request: crate::companion::CpuSpoofRequest,
@@ -199,41 +222,28 @@ pub fn handle_companion_cpu_spoof(
         std::process::id()
     );
 
-    let (setup_ok, mount_child_pid, exit_pipe_fd) = match do_cpu_spoof_setup(pid, &request.content)
-    {
-        Ok((child_pid, exit_fd)) => (true, child_pid, exit_fd),
+    let response = match do_cpu_spoof_setup(pid, &request) {
+        Ok((ns_ino, mounts)) => {
+            let checks = verify_overlays(pid, &mounts);
+            for check in checks.iter().filter(|check| !check.passed()) {
+                warn!("Overlay verification failed for pid {pid}: {check}");
+            }
+            let unmount_after_read = (request.unmount_cpuinfo_after_read
+                && mounts.iter().any(|mount| mount.target == PROC_CPUINFO))
+            .then(|| PROC_CPUINFO.to_string());
+            crate::supervisor::track_overlays(pid, mounts, ns_ino, unmount_after_read);
+            CompanionResponse {
+                overlay_checks: Some(checks),
+                ..CompanionResponse::ok()
+            }
+        }
         Err(e) => {
             error!("CPU spoof setup failed for pid {pid}: {e}");
-            let response = CompanionResponse::err(e.to_string());
-            if let Err(e) = write_companion_response(stream, &response) {
-                warn!("Failed to write CPU spoof response: {e}");
-            }
-            (false, -1, -1)
+            CompanionResponse::err(ErrorCode::OverlayFailed, e.to_string())
         }
     };
-
-    if setup_ok {
-        // 发送 OK 给 module，让 app 继续启动。
-        if let Err(e) = write_companion_response(stream, &CompanionResponse::ok()) {
-            warn!("Failed to write CPU spoof response: {e}");
-        }
-
-        // 阻塞等待 app 退出：从 exit pipe 读取 EOF（事件驱动，零轮询）。
-        // mount child 在 app 退出后关闭 pipe 写端，读端返回 0 字节。
-        let mut buf = [0u8; 1];
-        let _ = unsafe { libc::read(exit_pipe_fd, buf.as_mut_ptr() as *mut libc::c_void, 1) };
-        unsafe { libc::close(exit_pipe_fd) };
-        info!("Exit pipe signaled — app pid {pid} exited, cleaning up");
-
-        // 回收 mount 子进程
-        let mut status = 0i32;
-        unsafe { libc::waitpid(mount_child_pid, &mut status, 0) };
-
-        // 清理源文件
-        let internal_path = format!("{CPU_SPOOF_STATE_DIR}/cpu_{pid}");
-        if let Err(e) = fs::remove_file(&internal_path) {
-            warn!("Failed to remove cpuinfo source {internal_path}: {e}");
-        }
+    if let Err(e) = write_companion_response(stream, &response) {
+        warn!("Failed to write CPU spoof response: {e}");
     }
 }
 
@@ -245,529 +255,232 @@ pub fn handle_companion_cpu_spoof(
 // 在多线程进程中会返回 EINVAL（Linux 内核限制）。解决方案是 fork 子进程——
 // fork 后子进程是单线程的，可以安全调用 setns。
 //
-// 子进程进入 app 的 mount namespace 后**常驻等待**（不立即退出），以保持
-// namespace 引用并确保 bind mount 在 app 整个生命周期内有效。
-// Companion 线程等待 app 退出后通过 SIGTERM 通知子进程执行 umount 清理。
+// 子进程挂载后立即退出，bind mount 随 app 的 namespace 一起销毁，无需等待 app 退出
+// 再卸载。KernelSU 切换 app namespace 或卸载挂载时由 supervisor 重新挂载。
+// 子进程的实现见 `mount_child`。
+//
+// 内核支持新挂载 API 时优先走 `detached_mount`：companion 内的短命线程经 pidfd 进入
+// app namespace 后 move_mount，不再 fork。
 // ---------------------------------------------------------------------------
 
-/// 执行 CPU 伪装的 setup：写入源文件、fork 子进程进入 app namespace 并挂载。
-/// 返回 (子进程 pid, 退出通知 pipe 读端 fd)。
-/// 调用者从 pipe 读端阻塞读取——mount child 在 app 退出后关闭 pipe，读端返回 EOF。
-fn do_cpu_spoof_setup(pid: u32, content: &str) -> Result<(i32, i32)> {
+/// 一个待 bind mount 的伪装文件：companion 写好的源文件 → app namespace 中的目标路径。
+#[derive(Clone)]
+pub(crate) struct OverlayMount {
+    pub source: String,
+    pub target: String,
+}
+
+/// 执行 CPU 伪装的 setup：写入源文件并挂载到 app namespace。
+/// 除 /proc/cpuinfo 外，按请求同时覆盖 /proc/meminfo、电池与温控区 sysfs 文件。
+/// 返回 (挂载前 app 所在 namespace 的 inode, 已挂载的覆盖文件)。
+fn do_cpu_spoof_setup(
+    pid: u32,
+    request: &crate::companion::CpuSpoofRequest,
+) -> Result<(u64, Vec<OverlayMount>)> {
     ensure_dir(CPU_SPOOF_STATE_DIR)?;
     set_selinux_context(CPU_SPOOF_STATE_DIR);
 
-    let internal_path = format!("{CPU_SPOOF_STATE_DIR}/cpu_{pid}");
-    fs::write(&internal_path, content)
-        .with_context(|| format!("Failed to write internal cpuinfo file {internal_path}"))?;
-    set_selinux_context(&internal_path);
-
-    // 通过 fork+pipe 将 mount 操作委派给子进程（子进程是单线程，可安全 setns）。
-    let result = fork_mount_child(pid, &internal_path);
+    let mut mounts = Vec::new();
+    if let Err(e) = prepare_overlay_mounts(pid, request, &mut mounts) {
+        remove_overlay_sources(&mounts);
+        return Err(e);
+    }
+    if mounts.is_empty() {
+        anyhow::bail!("nothing to mount for pid {pid}");
+    }
+    mount_hiding::sort_for_mount(&mut mounts);
 
-    match &result {
-        Ok((child_pid, _)) => {
-            info!("Successfully mounted fake cpuinfo for pid {pid} (child_pid={child_pid})")
+    // 挂载前读取：挂载期间 namespace 若被切换，supervisor 会发现 inode 不同并重新挂载
+    let result = read_ns_ino(pid)
+        .context("cannot read app mount namespace")
+        .and_then(|ns_ino| mount_overlays(pid, &mounts).map(|()| ns_ino));
+    match result {
+        Ok(ns_ino) => {
+            info!(
+                "Successfully mounted {} overlay(s) for pid {pid} (ns_ino={ns_ino})",
+                mounts.len()
+            );
+            Ok((ns_ino, mounts))
         }
         Err(e) => {
             error!("Mount operation failed for pid {pid}: {e}");
-            let _ = fs::remove_file(&internal_path);
+            remove_overlay_sources(&mounts);
+            Err(e)
         }
     }
-
-    result
 }
 
-/// fork 子进程：setns 进入 app namespace → bind mount → 监控 app 退出。
-///
-/// 子进程通过 result pipe 报告挂载结果后**不退出**，保持 namespace 引用。
-/// 子进程监控 app 退出（pidfd 事件驱动或 procfs 轮询），退出时关闭 exit pipe。
-/// 父进程从 exit pipe 读取 EOF 即知 app 已退出，无需自己轮询。
+/// 把覆盖文件挂到 app 当前的 namespace，已有的同目标挂载会先被卸载。
 ///
-/// Result Pipe 协议：
-/// - 成功：写 4 字节 `0i32`
-/// - 失败：写 4 字节 `-1i32` + 4 字节 msg_len + UTF-8 错误消息
-///
-/// 返回 (子进程 pid, exit pipe 读端 fd)
-fn fork_mount_child(pid: u32, source_path: &str) -> Result<(i32, i32)> {
-    let mut pipe_fds = [0i32; 2];
-    // Result pipe: 子进程报告挂载结果
-    if unsafe { libc::pipe(pipe_fds.as_mut_ptr()) } != 0 {
-        anyhow::bail!("pipe failed: {}", std::io::Error::last_os_error());
-    }
-    let read_fd = pipe_fds[0];
-    let write_fd = pipe_fds[1];
-
-    // Exit notification pipe: 子进程 app 退出时关闭写端，父进程读端返回 EOF
-    let mut exit_pipe = [0i32; 2];
-    if unsafe { libc::pipe(exit_pipe.as_mut_ptr()) } != 0 {
-        unsafe {
-            libc::close(read_fd);
-            libc::close(write_fd);
-        }
-        anyhow::bail!("exit pipe failed: {}", std::io::Error::last_os_error());
-    }
-    let exit_read_fd = exit_pipe[0];
-    let exit_write_fd = exit_pipe[1];
-
-    match unsafe { libc::fork() } {
-        -1 => {
-            unsafe {
-                libc::close(read_fd);
-                libc::close(write_fd);
-                libc::close(exit_read_fd);
-                libc::close(exit_write_fd);
+/// 优先用 open_tree/move_mount 在 companion 内完成，不支持或失败时退回 fork 子进程。
+pub(crate) fn mount_overlays(pid: u32, mounts: &[OverlayMount]) -> Result<()> {
+    if DETACHED_MOUNT_SUPPORTED.load(Ordering::Relaxed) {
+        let pairs: Vec<_> = mounts
+            .iter()
+            .map(|mount| (mount.source.clone(), mount.target.clone()))
+            .collect();
+        match detached_mount::attach(pid, &pairs) {
+            Ok(()) => return Ok(()),
+            Err(AttachError::Unsupported) => {
+                info!("Kernel lacks open_tree/move_mount, using fork + setns");
+                DETACHED_MOUNT_SUPPORTED.store(false, Ordering::Relaxed);
             }
-            anyhow::bail!("fork failed: {}", std::io::Error::last_os_error());
-        }
-        0 => {
-            // === 子进程（单线程，可安全 setns）===
-            unsafe {
-                libc::close(read_fd);
-                libc::close(exit_read_fd); // 子进程不需要 exit pipe 读端
-            };
-            let status = do_mount_in_child(pid, source_path);
-            match status {
-                Ok(()) => {
-                    let code: i32 = 0;
-                    unsafe {
-                        libc::write(
-                            write_fd,
-                            &code as *const i32 as *const libc::c_void,
-                            std::mem::size_of::<i32>(),
-                        )
-                    };
-                }
-                Err(e) => {
-                    let msg = e.to_string();
-                    let code: i32 = -1;
-                    let msg_bytes = msg.as_bytes();
-                    let msg_len = msg_bytes.len() as i32;
-                    unsafe {
-                        libc::write(
-                            write_fd,
-                            &code as *const i32 as *const libc::c_void,
-                            std::mem::size_of::<i32>(),
-                        );
-                        libc::write(
-                            write_fd,
-                            &msg_len as *const i32 as *const libc::c_void,
-                            std::mem::size_of::<i32>(),
-                        );
-                        libc::write(
-                            write_fd,
-                            msg_bytes.as_ptr() as *const libc::c_void,
-                            msg_bytes.len(),
-                        );
-                        libc::close(write_fd);
-                        libc::_exit(1);
-                    }
-                }
+            // 子进程无法从暂存 namespace 克隆挂载
+            Err(AttachError::Failed(e))
+                if mounts
+                    .iter()
+                    .any(|mount| mount_hiding::is_staged(&mount.source)) =>
+            {
+                return Err(e);
             }
-            // 挂载成功：关闭 pipe 写端，然后等待 app 退出
-            unsafe { libc::close(write_fd) };
-
-            // 注册 SIGTERM handler（父进程在 app 退出后发 SIGTERM 作为 fallback 通知）
-            unsafe {
-                libc::signal(
-                    libc::SIGTERM,
-                    child_sigterm_handler as *const () as libc::sighandler_t,
-                );
+            Err(AttachError::Failed(e)) => {
+                warn!("move_mount failed for pid {pid}: {e:#}, falling back to fork + setns");
             }
-
-            // 等待 namespace 稳定后检查是否需要 remount，然后等待 app 退出。
-            // KernelSU 在 pre_app_specialize 后 ~100ms 调用 setns 切换 namespace。
-            // 使用 timerfd（内核定时器事件）等待 200ms 后检查 namespace 变化。
-            check_namespace_and_wait_exit(pid, source_path);
-
-            // 通知 companion：关闭 exit pipe 写端 → companion 的 read() 返回 EOF
-            unsafe { libc::close(exit_write_fd) };
-
-            // app 已退出（或收到 SIGTERM），执行 umount 清理
-            let _ = CString::new(PROC_CPUINFO).map(|target| {
-                unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
-            });
-            unsafe { libc::_exit(0) }
-        }
-        child_pid => {
-            // === 父进程（companion 线程）===
-            unsafe {
-                libc::close(write_fd);
-                libc::close(exit_write_fd); // 父进程不需要 exit pipe 写端
-            }
-
-            // 读取结果码
-            let mut code: i32 = -1;
-            let n = unsafe {
-                libc::read(
-                    read_fd,
-                    &mut code as *mut i32 as *mut libc::c_void,
-                    std::mem::size_of::<i32>(),
-                )
-            };
-            if n != std::mem::size_of::<i32>() as isize {
-                unsafe {
-                    libc::close(read_fd);
-                    libc::close(exit_read_fd);
-                }
-                let mut status = 0i32;
-                unsafe { libc::waitpid(child_pid, &mut status, 0) };
-                anyhow::bail!("Failed to read mount result from child (read {n} bytes)");
-            }
-
-            if code != 0 {
-                // 读取错误消息
-                let mut msg_len: i32 = 0;
-                let n = unsafe {
-                    libc::read(
-                        read_fd,
-                        &mut msg_len as *mut i32 as *mut libc::c_void,
-                        std::mem::size_of::<i32>(),
-                    )
-                };
-                let err_msg = if n == std::mem::size_of::<i32>() as isize && msg_len > 0 {
-                    let mut buf = vec![0u8; msg_len as usize];
-                    unsafe {
-                        libc::read(
-                            read_fd,
-                            buf.as_mut_ptr() as *mut libc::c_void,
-                            msg_len as usize,
-                        )
-                    };
-                    String::from_utf8_lossy(&buf).to_string()
-                } else {
-                    format!("error code {code}")
-                };
-                unsafe {
-                    libc::close(read_fd);
-                    libc::close(exit_read_fd);
-                }
-                let mut status = 0i32;
-                unsafe { libc::waitpid(child_pid, &mut status, 0) };
-                anyhow::bail!("Mount child failed: {err_msg}");
-            }
-
-            unsafe { libc::close(read_fd) };
-            // 子进程仍在运行，等待 app 退出后通过 exit pipe 通知
-            Ok((child_pid, exit_read_fd))
         }
     }
-}
 
-/// 子进程的 SIGTERM handler：收到信号后 umount 并退出。
-extern "C" fn child_sigterm_handler(_sig: libc::c_int) {
-    let _ = CString::new(PROC_CPUINFO).map(|target| {
-        unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
-    });
-    unsafe { libc::_exit(0) };
+    // 通过 fork+pipe 将 mount 操作委派给子进程（子进程是单线程，可安全 setns）。
+    // 路径在 fork 前准备好，子进程中不再分配内存。
+    let plan = MountPlan::new(
+        pid,
+        mounts
+            .iter()
+            .map(|mount| (mount.source.as_str(), mount.target.as_str())),
+    )?;
+    fork_mount_child(&plan)
 }
 
-/// 在 fork 子进程中执行 setns + bind mount。
-/// namespace 变化由 unshare PLT hook 事件驱动处理。
-fn do_mount_in_child(pid: u32, source_path: &str) -> Result<()> {
-    let ns_path = format!("/proc/{pid}/ns/mnt");
-    let ns_path_c = CString::new(ns_path.as_str())?;
-
-    let initial_ino = read_ns_ino(pid)?;
-
-    // setns 到 app 当前的 namespace
-    let ns_fd = unsafe { libc::open(ns_path_c.as_ptr(), libc::O_RDONLY) };
-    if ns_fd < 0 {
-        anyhow::bail!(
-            "Failed to open {}: {}",
-            ns_path,
-            std::io::Error::last_os_error()
-        );
-    }
-
-    let ret = unsafe {
-        libc::syscall(
-            libc::SYS_setns,
-            ns_fd as libc::c_long,
-            libc::CLONE_NEWNS as libc::c_long,
-        )
-    };
-    if ret != 0 {
-        let err = std::io::Error::last_os_error();
-        unsafe { libc::close(ns_fd) };
-        anyhow::bail!("setns failed for pid {pid}: {err}");
-    }
-    info!("[child] Entered NS of pid {pid} (ino={initial_ino})");
-
-    // 防御性卸载
-    let target = CString::new(PROC_CPUINFO)?;
-    unsafe {
-        libc::umount2(target.as_ptr(), libc::MNT_DETACH);
-    }
-
-    // bind mount
-    let source = CString::new(source_path)?;
-    let ret = unsafe {
-        libc::mount(
-            source.as_ptr(),
-            target.as_ptr(),
-            std::ptr::null(),
-            MS_BIND,
-            std::ptr::null(),
-        )
-    };
-    if ret != 0 {
-        let err = std::io::Error::last_os_error();
-        anyhow::bail!("bind mount failed: {err}");
+/// 按请求生成各覆盖文件的源文件，写入 `mounts`（出错时调用者负责清理已写入的部分）。
+fn prepare_overlay_mounts(
+    pid: u32,
+    request: &crate::companion::CpuSpoofRequest,
+    mounts: &mut Vec<OverlayMount>,
+) -> Result<()> {
+    if !request.content.is_empty() {
+        // 读取后卸载时需要按 inode 判断是哪个 app 读取，源文件不能共用
+        let label = request.overlay_label("cpuinfo");
+        let source = if request.unmount_cpuinfo_after_read {
+            overlay_store::acquire_private(
+                pid,
+                staged_name(PROC_CPUINFO),
+                request.content.as_bytes(),
+                &overlay_label::candidates(PROC_CPUINFO, label)?,
+            )?
+        } else {
+            write_overlay_source("cpu", PROC_CPUINFO, &request.content, label)?
+        };
+        mounts.push(OverlayMount {
+            source,
+            target: PROC_CPUINFO.to_string(),
+        });
     }
 
-    info!("[child] Mounted fake cpuinfo for pid {pid} (ns_ino={initial_ino})");
-
-    // 验证
-    match fs::read_to_string(PROC_CPUINFO) {
-        Ok(actual) if !actual.is_empty() => {
-            info!("[child] Verified for pid {pid} ({} bytes)", actual.len());
+    if let Some(ram_total_mb) = request.ram_total_mb {
+        let real = fs::read_to_string(PROC_MEMINFO)
+            .with_context(|| format!("Failed to read {PROC_MEMINFO}"))?;
+        match render_meminfo(&real, ram_total_mb) {
+            Some(content) => mounts.push(OverlayMount {
+                source: write_overlay_source(
+                    "meminfo",
+                    PROC_MEMINFO,
+                    &content,
+                    request.overlay_label("meminfo"),
+                )?,
+                target: PROC_MEMINFO.to_string(),
+            }),
+            None => warn!("{PROC_MEMINFO} has no MemTotal, skipping meminfo overlay"),
         }
-        Ok(_) => warn!("[child] /proc/cpuinfo empty for pid {pid}"),
-        Err(e) => warn!("[child] Read failed for pid {pid}: {e}"),
     }
 
-    Ok(())
-}
-
-/// 检查 namespace 变化（重复 timerfd 事件驱动）+ 等待 app 退出（pidfd 事件驱动）。
-///
-/// 流程：
-/// 1. 读取初始 namespace inode
-/// 2. timerfd 重复定时器（50ms 间隔）→ epoll_wait 阻塞等待内核 hrtimer 唤醒
-/// 3. 每次唤醒：检查 namespace 是否变化 → 如变化则 setns + remount → 关闭 timer
-/// 4. pidfd_open + poll 等待 app 退出（事件驱动）
-///
-/// 使用重复定时器而非固定延迟，自适应不同设备的 KernelSU namespace 切换速度。
-/// epoll_wait 由内核 hrtimer 唤醒，非 sleep 轮询。
-fn check_namespace_and_wait_exit(pid: u32, source_path: &str) {
-    const NS_CHECK_INTERVAL_NS: i64 = 25_000_000; // 25ms
-    const NS_CHECK_MAX_MS: i32 = 500; // 最多检查 500ms
-
-    let initial_ino = match read_ns_ino(pid) {
-        Ok(ino) => ino,
-        Err(_) => {
-            wait_for_app_exit_event(pid);
-            return;
-        }
-    };
-
-    let tfd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK) };
-    if tfd < 0 {
-        wait_for_app_exit_event(pid);
-        return;
-    }
-
-    // 重复定时器：每 50ms 触发一次
-    let spec = libc::itimerspec {
-        it_interval: libc::timespec {
-            tv_sec: 0,
-            tv_nsec: NS_CHECK_INTERVAL_NS,
-        },
-        it_value: libc::timespec {
-            tv_sec: 0,
-            tv_nsec: NS_CHECK_INTERVAL_NS,
-        },
-    };
-    unsafe { libc::timerfd_settime(tfd, 0, &spec, std::ptr::null_mut()) };
-
-    let efd = unsafe { libc::epoll_create1(0) };
-    if efd < 0 {
-        unsafe { libc::close(tfd) };
-        wait_for_app_exit_event(pid);
-        return;
-    }
-    let mut ev = libc::epoll_event {
-        events: libc::EPOLLIN as u32,
-        u64: tfd as u64,
-    };
-    unsafe { libc::epoll_ctl(efd, libc::EPOLL_CTL_ADD, tfd, &mut ev) };
-
-    let mut events = [libc::epoll_event { events: 0, u64: 0 }; 1];
-    let mut ns_changed = false;
-    let start = std::time::Instant::now();
-
-    loop {
-        let elapsed_ms = start.elapsed().as_millis() as i32;
-        let remaining = NS_CHECK_MAX_MS - elapsed_ms;
-        if remaining <= 0 {
-            break;
-        }
-
-        let nfds = unsafe { libc::epoll_wait(efd, events.as_mut_ptr(), 1, remaining) };
-        if nfds <= 0 {
-            break;
-        }
-
-        // 读取 timerfd 以清除可读状态
-        let mut buf = [0u8; 8];
-        unsafe {
-            libc::read(tfd, buf.as_mut_ptr() as *mut libc::c_void, 8);
-        }
-
-        if let Ok(new_ino) = read_ns_ino(pid) {
-            if new_ino != initial_ino {
-                info!("[child] NS changed for pid {pid}: {initial_ino} -> {new_ino}");
-                remount_in_namespace(pid, source_path, new_ino);
-                ns_changed = true;
-                break;
+    if let Some(battery) = &request.battery {
+        let read_real = |file: &str| fs::read_to_string(format!("{BATTERY_SYSFS_DIR}/{file}")).ok();
+        for (file, content) in render_battery_files(battery, read_real) {
+            let target = format!("{BATTERY_SYSFS_DIR}/{file}");
+            if !Path::new(&target).exists() {
+                warn!("{target} does not exist, skipping battery overlay");
+                continue;
             }
-        } else {
-            break; // 无法读取 namespace，app 可能已退出
+            mounts.push(OverlayMount {
+                source: write_overlay_source(
+                    &format!("battery_{file}"),
+                    &target,
+                    &content,
+                    request.overlay_label("battery"),
+                )?,
+                target,
+            });
         }
     }
 
-    unsafe {
-        libc::epoll_ctl(efd, libc::EPOLL_CTL_DEL, tfd, std::ptr::null_mut());
-        libc::close(efd);
-        libc::close(tfd);
-    }
-
-    if !ns_changed {
-        info!("[child] NS stable for pid {pid} (ino={initial_ino}), no remount needed");
-    }
-
-    wait_for_app_exit_event(pid);
-}
-
-/// 在新 namespace 中执行 setns + umount + bind mount。
-fn remount_in_namespace(pid: u32, source_path: &str, new_ino: u64) {
-    let ns_path = format!("/proc/{pid}/ns/mnt");
-    let Ok(ns_path_c) = CString::new(ns_path.as_str()) else {
-        return;
-    };
-    let ns_fd = unsafe { libc::open(ns_path_c.as_ptr(), libc::O_RDONLY) };
-    if ns_fd < 0 {
-        warn!("[child] Cannot open new NS for pid {pid}");
-        return;
+    if !request.thermal_zones.is_empty() {
+        prepare_thermal_zone_mounts(
+            &request.thermal_zones,
+            request.overlay_label("thermal_zones"),
+            mounts,
+        )?;
     }
 
-    let ret = unsafe {
-        libc::syscall(
-            libc::SYS_setns,
-            ns_fd as libc::c_long,
-            libc::CLONE_NEWNS as libc::c_long,
-        )
-    };
-    if ret != 0 {
-        warn!(
-            "[child] setns to new NS failed for pid {pid}: {}",
-            std::io::Error::last_os_error()
-        );
-        unsafe { libc::close(ns_fd) };
-        return;
+    if !request.props.is_empty() || !request.delete_props.is_empty() {
+        let copies = prepare_property_areas(
+            &mut OverlayPropertyAreas { pid },
+            PROPERTIES_DIR,
+            &request.props,
+            &request.delete_props,
+        )?;
+        mounts.extend(copies.into_iter().map(|copy| OverlayMount {
+            source: copy.source,
+            target: copy.target,
+        }));
     }
 
-    let target = match CString::new(PROC_CPUINFO) {
-        Ok(t) => t,
-        Err(_) => return,
-    };
-    unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
-
-    let source = match CString::new(source_path) {
-        Ok(s) => s,
-        Err(_) => return,
-    };
-    let ret = unsafe {
-        libc::mount(
-            source.as_ptr(),
-            target.as_ptr(),
-            std::ptr::null(),
-            MS_BIND,
-            std::ptr::null(),
-        )
-    };
-    if ret == 0 {
-        info!("[child] Re-mounted in new NS for pid {pid} (ino={new_ino})");
-    } else {
-        warn!(
-            "[child] Re-mount failed for pid {pid}: {}",
-            std::io::Error::last_os_error()
-        );
-    }
+    Ok(())
 }
 
-/// Mount 子进程中等待 app 退出：pidfd_open 事件驱动，旧内核回退到 pause + SIGTERM。
-///
-/// 子进程在此阻塞，直到 app 退出或收到 SIGTERM。
-/// namespace 变化不再由此函数处理——unshare PLT hook 在 app specialize 期间
-/// 事件驱动地完成 remount，无需子进程监控。
-fn wait_for_app_exit_event(pid: u32) {
-    // 尝试 pidfd_open（与父进程 wait_for_app_exit 中相同的 syscall）
-    let pidfd =
-        unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0 as libc::c_uint) };
-
-    if pidfd >= 0 {
-        let pidfd = pidfd as i32;
-        info!("[child] Monitoring app exit via pidfd {pidfd} (pid={pid})");
-
-        let mut pfd = libc::pollfd {
-            fd: pidfd,
-            events: libc::POLLIN,
-            revents: 0,
+/// 扫描 thermal_zone*/type，对真实名称命中覆盖表的温控区生成覆盖文件。
+/// 按名称而非编号匹配，同一份模板在不同真机上的编号可能不同。
+fn prepare_thermal_zone_mounts(
+    overrides: &HashMap<String, String>,
+    label: Option<&str>,
+    mounts: &mut Vec<OverlayMount>,
+) -> Result<()> {
+    let entries = fs::read_dir(THERMAL_SYSFS_DIR)
+        .with_context(|| format!("Failed to read {THERMAL_SYSFS_DIR}"))?;
+    for entry in entries.flatten() {
+        let zone = entry.file_name().to_string_lossy().into_owned();
+        if !zone.starts_with("thermal_zone") {
+            continue;
+        }
+        let target = format!("{THERMAL_SYSFS_DIR}/{zone}/type");
+        let Ok(real_type) = fs::read_to_string(&target) else {
+            continue;
         };
-        loop {
-            let ret = unsafe { libc::poll(&mut pfd, 1, -1) };
-            if ret > 0 {
-                info!("[child] pidfd signaled — app (pid {pid}) exited");
-                break;
-            }
-            if ret < 0 {
-                let err = std::io::Error::last_os_error();
-                if err.kind() == std::io::ErrorKind::Interrupted {
-                    continue; // EINTR: re-enter poll (e.g. SIGTERM handled)
-                }
-                warn!("[child] poll(pidfd) error for pid {pid}: {err}");
-                break;
-            }
+        if let Some(fake_type) = overrides.get(real_type.trim()) {
+            mounts.push(OverlayMount {
+                source: write_overlay_source(&zone, &target, &format!("{fake_type}\n"), label)?,
+                target,
+            });
         }
-
-        unsafe { libc::close(pidfd) };
-        return;
     }
+    Ok(())
+}
 
-    // pidfd_open 不可用（旧内核）：已注册 SIGTERM handler，pause 等待父进程通知。
-    let err = std::io::Error::last_os_error();
-    info!("[child] pidfd_open unavailable for pid {pid}: {err}, waiting for SIGTERM from parent");
-    loop {
-        // pause() 阻塞直到收到任意信号。
-        // SIGTERM handler 调用 _exit(0)，不会从 pause 返回。
-        // 其他信号会唤醒 pause，然后继续循环。
-        unsafe { libc::pause() };
-    }
+/// 写入（或复用内容相同的）源文件，路径由 `overlay_store` 按内容决定。
+///
+/// `label` 为配置中该覆盖文件的 SELinux label，挂载前确认 app 可读。
+fn write_overlay_source(
+    kind: &str,
+    target: &str,
+    content: &str,
+    label: Option<&str>,
+) -> Result<String> {
+    let labels = overlay_label::candidates(target, label)?;
+    overlay_store::acquire(kind, staged_name(target), content.as_bytes(), &labels)
 }
 
-/// 读取 /proc/{pid}/ns/mnt 的 namespace 标识（通过 readlink 获取 mnt:[inode]）。
-/// stat() 返回的是 procfs 条目的 inode（固定不变），必须用 readlink 获取真正的 namespace ID。
-fn read_ns_ino(pid: u32) -> Result<u64> {
-    let ns_path = format!("/proc/{pid}/ns/mnt");
-    let ns_path_c = CString::new(ns_path.as_str())?;
-    let mut buf = [0u8; 64];
-    let len = unsafe {
-        libc::readlink(
-            ns_path_c.as_ptr(),
-            buf.as_mut_ptr() as *mut libc::c_char,
-            buf.len() - 1,
-        )
-    };
-    if len < 0 {
-        anyhow::bail!(
-            "readlink({}) failed: {}",
-            ns_path,
-            std::io::Error::last_os_error()
-        );
+fn remove_overlay_sources(mounts: &[OverlayMount]) {
+    for mount in mounts {
+        overlay_store::release(&mount.source);
     }
-    buf[len as usize] = 0;
-    let link = std::str::from_utf8(&buf[..len as usize])
-        .map_err(|_| anyhow::anyhow!("invalid utf8 in ns link"))?;
-    // 格式: "mnt:[4026535831]"
-    let ino_str = link
-        .strip_prefix("mnt:[")
-        .and_then(|s| s.strip_suffix(']'))
-        .ok_or_else(|| anyhow::anyhow!("unexpected ns link format: {link}"))?;
-    ino_str
-        .parse::<u64>()
-        .map_err(|_| anyhow::anyhow!("cannot parse ns ino: {ino_str}"))
 }
 
 fn ensure_dir(path: &str) -> Result<()> {
@@ -775,48 +488,50 @@ fn ensure_dir(path: &str) -> Result<()> {
     Ok(())
 }
 
-/// 把给定路径的 SELinux label 设为 app 可读的 system_file:s0。
+/// 把给定路径（状态目录）的 SELinux label 设为 app 可读的 system_file:s0。
 ///
-/// bind mount 后 app 读 /proc/cpuinfo 时，内核在 VFS 层把路径解析到源文件 inode，
-/// SELinux 检查的是**源文件 inode 的 label**。/data/adb/device_faker/ 下的文件默认
-/// label（adb_data_file:s0 等）untrusted_app 无权读取，会返回 EACCES，导致 app 读
-/// 不到伪装后的 cpuinfo。必须把目录和源文件都改成 system_file:s0（与 customize.sh
-/// 对 config 文件的处理一致）。
+/// /data/adb/device_faker/ 下的默认 label（adb_data_file:s0 等）untrusted_app 无权
+/// 访问（与 customize.sh 对 config 文件的处理一致）。源文件的 label 由
+/// `overlay_label` 在挂载前选择并确认。
 ///
-/// 失败时仅记录警告而非中断：在某些 root 实现下 lsetxattr 可能被策略限制，此时退回
-/// 默认 label；最坏情况是 app 读不到 cpuinfo（与不修复无异），但不影响 mount 本身。
-fn set_selinux_context(path: &str) {
-    let result = (|| -> std::io::Result<()> {
-        let p = CString::new(path).map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contained nul")
-        })?;
-        let ctx = CString::new(SELINUX_CONTEXT).map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::InvalidInput, "context contained nul")
-        })?;
-        // flags = 0：若属性已存在则覆盖，不存在则创建（create-or-replace）。
-        let ret = unsafe {
-            libc::lsetxattr(
-                p.as_ptr(),
-                c"security.selinux".as_ptr() as *const _,
-                ctx.as_ptr() as *const libc::c_void,
-                SELINUX_CONTEXT.len(), // 不含末尾 nul
-                0,
-            )
-        };
-        if ret != 0 {
-            return Err(std::io::Error::last_os_error());
-        }
-        Ok(())
-    })();
+/// 失败时仅记录警告而非中断：在某些 root 实现下 lsetxattr 可能被策略限制。
+pub(crate) fn set_selinux_context(path: &str) {
+    set_selinux_label(path, SELINUX_CONTEXT);
+}
 
-    match result {
+/// 把给定路径的 SELinux label 设为 `label`，失败时仅记录警告。
+pub(crate) fn set_selinux_label(path: &str, label: &str) {
+    match write_selinux_label(path, label) {
         Ok(()) => {
             #[cfg(target_os = "android")]
-            info!("Set SELinux context {SELINUX_CONTEXT} on {path}");
+            info!("Set SELinux context {label} on {path}");
         }
         Err(e) => {
-            // 不致命：记录后继续，mount 仍可完成；最坏 app 读不到 cpuinfo。
-            warn!("Failed to set SELinux context on {path}: {e} (app may not read cpuinfo)");
+            // 不致命：记录后继续，mount 仍可完成；最坏 app 读不到覆盖文件。
+            warn!("Failed to set SELinux context on {path}: {e} (app may not read the overlay)");
         }
     }
 }
+
+/// 把给定路径的 SELinux label 设为 `label`。
+pub(crate) fn write_selinux_label(path: &str, label: &str) -> std::io::Result<()> {
+    let p = CString::new(path)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contained nul"))?;
+    let ctx = CString::new(label).map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "context contained nul")
+    })?;
+    // flags = 0：若属性已存在则覆盖，不存在则创建（create-or-replace）。
+    let ret = unsafe {
+        libc::lsetxattr(
+            p.as_ptr(),
+            c"security.selinux".as_ptr() as *const _,
+            ctx.as_ptr() as *const libc::c_void,
+            label.len(), // 不含末尾 nul
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// 基于新挂载 API 的覆盖文件挂载，不需要 fork 子进程。
+//
+// 一个短命线程 unshare(CLONE_FS) 后进入源文件所在的暂存 namespace（见
+// `mount_hiding`），用 open_tree(OPEN_TREE_CLONE) 为每个源文件创建游离（detached）
+// 挂载，再经 pidfd setns 进入 app 的 mount namespace，用 move_mount 把游离挂载接到
+// 目标路径上。setns(CLONE_NEWNS) 只要求调用者的 fs_struct 不与其他线程共享，因此
+// unshare(CLONE_FS) 后多线程进程中的单个线程也能切换 namespace，不影响 companion
+// 的其他线程。线程挂载后即退出。
+//
+// 内核不支持新挂载 API（5.2 以下）时返回 `AttachError::Unsupported`，调用方退回
+// fork + setns。
+
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    thread,
+};
+
+use anyhow::{Context, Result};
+
+const OPEN_TREE_CLONE: libc::c_uint = 0x1;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+
+pub enum AttachError {
+    /// 内核不支持 open_tree/move_mount
+    Unsupported,
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for AttachError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// 一组 open_tree 克隆出的游离挂载，与 `mounts` 一一对应。
+struct DetachedTrees(Vec<OwnedFd>);
+
+impl DetachedTrees {
+    fn clone_sources(mounts: &[(String, String)]) -> Result<Self, AttachError> {
+        let mut trees = Vec::with_capacity(mounts.len());
+        for (source, _) in mounts {
+            let path = CString::new(source.as_str()).map_err(anyhow::Error::from)?;
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_open_tree,
+                    libc::AT_FDCWD,
+                    path.as_ptr(),
+                    OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint,
+                )
+            };
+            if fd < 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() == Some(libc::ENOSYS) {
+                    return Err(AttachError::Unsupported);
+                }
+                return Err(anyhow::anyhow!("open_tree({source}) failed: {err}").into());
+            }
+            trees.push(unsafe { OwnedFd::from_raw_fd(fd as i32) });
+        }
+        Ok(Self(trees))
+    }
+}
+
+/// 把 `mounts`（源文件, 目标路径）挂到 pid 所在的 mount namespace。
+pub fn attach(pid: u32, mounts: &[(String, String)]) -> Result<(), AttachError> {
+    let pidfd = pidfd_open(pid)?;
+    in_mount_thread(|| {
+        // 游离挂载须在源文件所在的 namespace 中创建，进入 app 的 namespace 后再移动
+        unshare_fs()?;
+        crate::mount_hiding::enter_staging_ns()?;
+        let trees = DetachedTrees::clone_sources(mounts)?;
+        enter_namespace(pid, &pidfd)?;
+        move_all(&trees, mounts)?;
+        Ok(())
+    })
+}
+
+/// 卸载 pid 所在 mount namespace 中的 `targets`。
+pub fn unmount(pid: u32, targets: &[String]) -> Result<()> {
+    let pidfd = pidfd_open(pid)?;
+    in_mount_thread(|| {
+        unshare_fs()?;
+        enter_namespace(pid, &pidfd)?;
+        for target in targets {
+            let target_c = CString::new(target.as_str())?;
+            if unsafe { libc::umount2(target_c.as_ptr(), libc::MNT_DETACH) } != 0 {
+                anyhow::bail!("umount {target} failed: {}", io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    })
+}
+
+/// setns 后线程的 fs_struct 留在 app 的 namespace 中，用完即弃。
+fn in_mount_thread<E: From<anyhow::Error> + Send>(
+    f: impl FnOnce() -> Result<(), E> + Send,
+) -> Result<(), E> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .name("df-mount".to_string())
+            .spawn_scoped(scope, f)
+            .context("Failed to spawn mount thread")?
+            .join()
+            .map_err(|_| anyhow::anyhow!("mount thread panicked"))?
+    })
+}
+
+fn unshare_fs() -> Result<()> {
+    if unsafe { libc::unshare(libc::CLONE_FS) } != 0 {
+        anyhow::bail!("unshare(CLONE_FS) failed: {}", io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn pidfd_open(pid: u32) -> Result<Option<OwnedFd>> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0u32) };
+    if fd >= 0 {
+        return Ok(Some(unsafe { OwnedFd::from_raw_fd(fd as i32) }));
+    }
+    match io::Error::last_os_error() {
+        // 5.3 以下没有 pidfd，改用 /proc/<pid>/ns/mnt
+        err if err.raw_os_error() == Some(libc::ENOSYS) => Ok(None),
+        err => anyhow::bail!("pidfd_open({pid}) failed: {err}"),
+    }
+}
+
+/// 让当前线程进入 app 的 mount namespace，调用前须已 unshare(CLONE_FS)。
+///
+/// 优先 setns(pidfd)（5.8+），不支持时打开 /proc/<pid>/ns/mnt。
+fn enter_namespace(pid: u32, pidfd: &Option<OwnedFd>) -> Result<()> {
+    if let Some(pidfd) = pidfd
+        && unsafe { libc::setns(pidfd.as_raw_fd(), libc::CLONE_NEWNS) } == 0
+    {
+        return Ok(());
+    }
+    let ns = File::open(format!("/proc/{pid}/ns/mnt"))
+        .with_context(|| format!("Failed to open mount namespace of pid {pid}"))?;
+    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNS) } != 0 {
+        anyhow::bail!("setns failed for pid {pid}: {}", io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 防御性卸载后把游离挂载接到目标路径。
+fn move_all(trees: &DetachedTrees, mounts: &[(String, String)]) -> Result<()> {
+    for (tree, (_, target)) in trees.0.iter().zip(mounts) {
+        let target_c = CString::new(target.as_str())?;
+        unsafe { libc::umount2(target_c.as_ptr(), libc::MNT_DETACH) };
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_move_mount,
+                tree.as_raw_fd(),
+                c"".as_ptr(),
+                libc::AT_FDCWD,
+                target_c.as_ptr(),
+                MOVE_MOUNT_F_EMPTY_PATH,
+            )
+        };
+        if ret != 0 {
+            anyhow::bail!(
+                "move_mount onto {target} failed: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
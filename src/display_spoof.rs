@@ -0,0 +1,432 @@
+use std::sync::OnceLock;
+
+use anyhow::Context;
+
+use jni::{
+    Env, EnvUnowned, Outcome, jni_sig, jni_str,
+    objects::{JObject, JObjectArray, JValue},
+};
+use zygisk_api::api::{V4, ZygiskApi};
+
+use crate::{
+    binder_hook::{self, JniParcel, Parcel},
+    config::DisplayConfig,
+};
+
+// Display.getMetrics/getRealMetrics/getRefreshRate/getSupportedModes 都读取 DisplayInfo，
+// 后者由 DisplayManagerGlobal 经 IDisplayManager.getDisplayInfo 从 system_server 取得并缓存，
+// 屏幕变化时重新获取。在 BinderProxy.transactNative 中改写这个回复，所有读取路径都会看到伪装值。
+// 仍然保持真实的：Activity Configuration（screenWidthDp 等）与 WindowManager.getCurrentWindowMetrics
+// 的窗口边界，它们随 Activity 启动事务下发，不经过 getDisplayInfo。
+
+const DISPLAY_MANAGER_DESCRIPTOR: &str = "android.hardware.display.IDisplayManager";
+/// `Display.DEFAULT_DISPLAY`
+const DEFAULT_DISPLAY: i32 = 0;
+
+/// specialize 前确定、之后只读的屏幕伪装配置。
+static DISPLAY_SPOOF: OnceLock<DisplaySpoof> = OnceLock::new();
+
+struct DisplaySpoof {
+    /// `IDisplayManager.Stub.TRANSACTION_getDisplayInfo`，各 Android 版本不同，运行时读取。
+    code: i32,
+    display: DisplayConfig,
+}
+
+/// Display.Mode 的 (id, 宽, 高, 刷新率)。
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mode {
+    id: i32,
+    width: i32,
+    height: i32,
+    refresh_rate: f32,
+}
+
+/// Hook BinderProxy.transactNative 以改写默认屏幕的 DisplayInfo（仅 full 模式）。
+pub fn hook_display_info(
+    api: &mut ZygiskApi<V4>,
+    env: &mut EnvUnowned,
+    display: &DisplayConfig,
+) -> anyhow::Result<()> {
+    // 在 specialize 之前读取隐藏字段，此时 hidden API 限制尚未生效。
+    let outcome = env
+        .with_env(|jenv| -> Result<i32, jni::errors::Error> {
+            let stub_class =
+                jenv.find_class(jni_str!("android/hardware/display/IDisplayManager$Stub"))?;
+            let code = jenv
+                .get_static_field(
+                    &stub_class,
+                    jni_str!("TRANSACTION_getDisplayInfo"),
+                    jni_sig!("I"),
+                )
+                .and_then(|v| v.i());
+            if code.is_err() {
+                jenv.exception_clear();
+            }
+            code
+        })
+        .into_outcome();
+    let code = match outcome {
+        Outcome::Ok(code) => code,
+        Outcome::Err(err) => {
+            return Err(err)
+                .context("Failed to read IDisplayManager.Stub.TRANSACTION_getDisplayInfo");
+        }
+        Outcome::Panic(_) => {
+            anyhow::bail!("Panicked while reading IDisplayManager transaction code")
+        }
+    };
+    // AIDL 事务号从 FIRST_CALL_TRANSACTION (1) 开始
+    if code <= 0 {
+        anyhow::bail!("Invalid IDisplayManager.Stub.TRANSACTION_getDisplayInfo: {code}");
+    }
+    if DISPLAY_SPOOF
+        .set(DisplaySpoof {
+            code,
+            display: display.clone(),
+        })
+        .is_err()
+    {
+        anyhow::bail!("Display spoof already installed");
+    }
+
+    binder_hook::install(api, env)
+}
+
+/// 事务号是否可能是 getDisplayInfo，供 hook 在接触 Parcel 之前快速过滤。
+pub(crate) fn wants_transaction(code: i32) -> bool {
+    DISPLAY_SPOOF.get().is_some_and(|spoof| spoof.code == code)
+}
+
+/// 确认请求是默认屏幕的 IDisplayManager.getDisplayInfo 后改写回复中的 DisplayInfo。
+pub(crate) fn patch_transaction(
+    env: &mut Env,
+    code: i32,
+    data: &JObject,
+    reply: &JObject,
+    header_ints: i32,
+) -> Result<(), jni::errors::Error> {
+    let Some(spoof) = DISPLAY_SPOOF.get().filter(|spoof| spoof.code == code) else {
+        return Ok(());
+    };
+    if !is_default_display_query(&mut JniParcel { env, parcel: data }, header_ints)? {
+        return Ok(());
+    }
+
+    binder_hook::rewrite_reply_parcelable(
+        env,
+        reply,
+        |env| {
+            let info_class = env.find_class(jni_str!("android/view/DisplayInfo"))?;
+            let creator = env
+                .get_static_field(
+                    &info_class,
+                    jni_str!("CREATOR"),
+                    jni_sig!("Landroid/os/Parcelable$Creator;"),
+                )?
+                .l()?;
+            env.call_method(
+                &creator,
+                jni_str!("createFromParcel"),
+                jni_sig!("(Landroid/os/Parcel;)Ljava/lang/Object;"),
+                &[JValue::Object(reply)],
+            )?
+            .l()
+        },
+        |env, info| patch_display_info(env, info, &spoof.display),
+    )
+}
+
+/// 请求是否查询默认屏幕。请求 Parcel 的位置读取后恢复原样。
+fn is_default_display_query(
+    data: &mut dyn Parcel,
+    header_ints: i32,
+) -> Result<bool, jni::errors::Error> {
+    let data_position = data.position()?;
+    let matched = match binder_hook::read_interface(data, header_ints)? {
+        Some(descriptor) if descriptor == DISPLAY_MANAGER_DESCRIPTOR => {
+            data.read_int()? == DEFAULT_DISPLAY
+        }
+        _ => false,
+    };
+    data.set_position(data_position)?;
+    Ok(matched)
+}
+
+fn patch_display_info(
+    env: &mut Env,
+    info: &JObject,
+    display: &DisplayConfig,
+) -> Result<(), jni::errors::Error> {
+    if display.width.is_some() || display.height.is_some() {
+        let logical = (
+            get_int(env, info, jni_str!("logicalWidth"))?,
+            get_int(env, info, jni_str!("logicalHeight"))?,
+        );
+        let app = (
+            get_int(env, info, jni_str!("appWidth"))?,
+            get_int(env, info, jni_str!("appHeight"))?,
+        );
+        let new_logical = oriented_size(logical, display);
+        // 应用区域扣除了导航栏等装饰，按比例缩放
+        let new_app = (
+            scale(app.0, logical.0, new_logical.0),
+            scale(app.1, logical.1, new_logical.1),
+        );
+        set_int(env, info, jni_str!("logicalWidth"), new_logical.0)?;
+        set_int(env, info, jni_str!("logicalHeight"), new_logical.1)?;
+        set_int(env, info, jni_str!("appWidth"), new_app.0)?;
+        set_int(env, info, jni_str!("appHeight"), new_app.1)?;
+    }
+
+    if let Some(density_dpi) = display.density_dpi {
+        set_int(env, info, jni_str!("logicalDensityDpi"), density_dpi as i32)?;
+    }
+
+    if display.width.is_none()
+        && display.height.is_none()
+        && display.refresh_rate.is_none()
+        && display.supported_refresh_rates.is_none()
+    {
+        return Ok(());
+    }
+
+    let modes = env
+        .get_field(
+            info,
+            jni_str!("supportedModes"),
+            jni_sig!("[Landroid/view/Display$Mode;"),
+        )?
+        .l()?;
+    if modes.is_null() {
+        return Ok(());
+    }
+    let modes = unsafe { JObjectArray::<JObject>::from_raw(env, modes.into_raw()) };
+    let mut real_modes = Vec::new();
+    for index in 0..modes.len(env)? {
+        let mode = modes.get_element(env, index)?;
+        real_modes.push(Mode {
+            id: call_int(env, &mode, jni_str!("getModeId"))?,
+            width: call_int(env, &mode, jni_str!("getPhysicalWidth"))?,
+            height: call_int(env, &mode, jni_str!("getPhysicalHeight"))?,
+            refresh_rate: env
+                .call_method(&mode, jni_str!("getRefreshRate"), jni_sig!("()F"), &[])?
+                .f()?,
+        });
+    }
+    let active_id = get_int(env, info, jni_str!("modeId"))?;
+    let Some((fake_modes, fake_active)) = plan_modes(&real_modes, active_id, display) else {
+        return Ok(());
+    };
+
+    let mode_class = env.find_class(jni_str!("android/view/Display$Mode"))?;
+    let new_modes = env.new_object_array(fake_modes.len() as i32, &mode_class, JObject::null())?;
+    for (index, mode) in fake_modes.iter().enumerate() {
+        let object = env.new_object(
+            &mode_class,
+            jni_sig!("(IIIF)V"),
+            &[
+                JValue::Int(mode.id),
+                JValue::Int(mode.width),
+                JValue::Int(mode.height),
+                JValue::Float(mode.refresh_rate),
+            ],
+        )?;
+        new_modes.set_element(env, index, &object)?;
+    }
+    env.set_field(
+        info,
+        jni_str!("supportedModes"),
+        jni_sig!("[Landroid/view/Display$Mode;"),
+        JValue::Object(&new_modes),
+    )?;
+    set_int(env, info, jni_str!("modeId"), fake_active.id)?;
+    set_int(env, info, jni_str!("defaultModeId"), fake_active.id)?;
+
+    if display.refresh_rate.is_some() || display.supported_refresh_rates.is_some() {
+        // 以下字段只在较新的版本存在：refreshRateOverride (12+) 会让 getRefreshRate
+        // 绕过当前模式，renderFrameRate (14+) 是实际渲染帧率
+        if set_int(env, info, jni_str!("refreshRateOverride"), 0).is_err() {
+            env.exception_clear();
+        }
+        if env
+            .set_field(
+                info,
+                jni_str!("renderFrameRate"),
+                jni_sig!("F"),
+                JValue::Float(fake_active.refresh_rate),
+            )
+            .is_err()
+        {
+            env.exception_clear();
+        }
+    }
+
+    Ok(())
+}
+
+/// 按当前方向排列配置的宽高，未配置的一边沿用当前值。
+fn oriented_size(current: (i32, i32), display: &DisplayConfig) -> (i32, i32) {
+    // 配置按竖屏方向填写，当前处于横屏时交换宽高。
+    let (width, height) = if current.0 > current.1 {
+        (display.height, display.width)
+    } else {
+        (display.width, display.height)
+    };
+    (
+        width.map_or(current.0, |w| w as i32),
+        height.map_or(current.1, |h| h as i32),
+    )
+}
+
+fn scale(value: i32, from: i32, to: i32) -> i32 {
+    if from <= 0 {
+        return to;
+    }
+    ((value as i64 * to as i64) / from as i64) as i32
+}
+
+/// 计算伪装后的模式列表和当前模式。没有可改写的模式时返回 `None`。
+///
+/// 只改分辨率时保留真实模式，逐个替换尺寸；配置了刷新率时按刷新率列表重建模式，
+/// 当前模式沿用真实的模式 id。
+fn plan_modes(real: &[Mode], active_id: i32, display: &DisplayConfig) -> Option<(Vec<Mode>, Mode)> {
+    let active = *real
+        .iter()
+        .find(|mode| mode.id == active_id)
+        .or(real.first())?;
+    let resize = |mode: Mode| {
+        let (width, height) = oriented_size((mode.width, mode.height), display);
+        Mode {
+            width,
+            height,
+            ..mode
+        }
+    };
+
+    if display.refresh_rate.is_none() && display.supported_refresh_rates.is_none() {
+        let modes: Vec<Mode> = real.iter().copied().map(resize).collect();
+        return Some((modes, resize(active)));
+    }
+
+    let mut rates = display.supported_refresh_rates.clone().unwrap_or_default();
+    rates.retain(|rate| rate.is_finite() && *rate > 0.0);
+    let active_rate = display
+        .refresh_rate
+        .filter(|rate| rate.is_finite() && *rate > 0.0)
+        .or_else(|| {
+            rates
+                .iter()
+                .copied()
+                .find(|rate| (rate - active.refresh_rate).abs() < 0.5)
+        })
+        .or_else(|| rates.iter().copied().reduce(f32::max))
+        .unwrap_or(active.refresh_rate);
+    if !rates.iter().any(|rate| (rate - active_rate).abs() < 0.5) {
+        rates.push(active_rate);
+    }
+
+    let base = resize(active);
+    let mut next_id = real.iter().map(|mode| mode.id).max().unwrap_or(active.id) + 1;
+    let modes = rates
+        .iter()
+        .map(|&refresh_rate| {
+            let id = if (refresh_rate - active_rate).abs() < 0.5 {
+                active.id
+            } else {
+                next_id += 1;
+                next_id - 1
+            };
+            Mode {
+                id,
+                refresh_rate,
+                ..base
+            }
+        })
+        .collect();
+    Some((
+        modes,
+        Mode {
+            refresh_rate: active_rate,
+            ..base
+        },
+    ))
+}
+
+fn get_int(
+    env: &mut Env,
+    object: &JObject,
+    name: &jni::strings::JNIStr,
+) -> Result<i32, jni::errors::Error> {
+    env.get_field(object, name, jni_sig!("I"))?.i()
+}
+
+fn set_int(
+    env: &mut Env,
+    object: &JObject,
+    name: &jni::strings::JNIStr,
+    value: i32,
+) -> Result<(), jni::errors::Error> {
+    env.set_field(object, name, jni_sig!("I"), JValue::Int(value))
+}
+
+fn call_int(
+    env: &mut Env,
+    object: &JObject,
+    name: &jni::strings::JNIStr,
+) -> Result<i32, jni::errors::Error> {
+    env.call_method(object, name, jni_sig!("()I"), &[])?.i()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(id: i32, refresh_rate: f32) -> Mode {
+        Mode {
+            id,
+            width: 1080,
+            height: 2400,
+            refresh_rate,
+        }
+    }
+
+    #[test]
+    fn refresh_rates_rebuild_modes_around_the_active_one() {
+        let display = DisplayConfig {
+            width: Some(1440),
+            height: Some(3200),
+            refresh_rate: Some(144.0),
+            supported_refresh_rates: Some(vec![60.0, 90.0, 120.0]),
+            ..DisplayConfig::default()
+        };
+        let (modes, active) = plan_modes(&[mode(1, 60.0), mode(2, 120.0)], 2, &display).unwrap();
+
+        assert_eq!(active.id, 2);
+        assert_eq!(active.refresh_rate, 144.0);
+        assert_eq!((active.width, active.height), (1440, 3200));
+        let rates: Vec<(i32, f32)> = modes.iter().map(|m| (m.id, m.refresh_rate)).collect();
+        assert_eq!(rates, vec![(3, 60.0), (4, 90.0), (5, 120.0), (2, 144.0)]);
+    }
+
+    #[test]
+    fn resolution_only_keeps_real_modes_in_current_orientation() {
+        let display = DisplayConfig {
+            width: Some(1440),
+            height: Some(3200),
+            ..DisplayConfig::default()
+        };
+        let landscape = Mode {
+            width: 2400,
+            height: 1080,
+            ..mode(1, 120.0)
+        };
+        let (modes, active) = plan_modes(&[landscape], 1, &display).unwrap();
+
+        assert_eq!(modes, vec![active]);
+        assert_eq!(
+            (active.width, active.height, active.refresh_rate),
+            (3200, 1440, 120.0)
+        );
+        assert_eq!(scale(2296, 2400, 3200), 3061);
+    }
+}
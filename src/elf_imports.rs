@@ -0,0 +1,242 @@
+// 已加载库的导入符号检查。
+//
+// Zygisk 的 PLT hook 按 (dev, inode) 注册到具体的库。只读取磁盘上 ELF 的 .dynsym，
+// 挑出真正导入了目标符号的库，其余库的 GOT 保持不变。不修改任何内存。
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+const SHT_DYNSYM: u32 = 11;
+const SHN_UNDEF: u16 = 0;
+
+// 单个节的读取上限，防止损坏的节头导致巨量分配
+const MAX_SECTION_SIZE: u64 = 64 << 20;
+
+/// 当前进程中映射的一个共享库。
+pub(crate) struct MappedLibrary {
+    pub dev: u64,
+    pub inode: u64,
+    pub path: String,
+}
+
+/// 从 /proc/self/maps 收集已映射的共享库，按 (dev, inode) 去重。
+pub(crate) fn mapped_libraries() -> Vec<MappedLibrary> {
+    std::fs::read_to_string("/proc/self/maps")
+        .map(|maps| parse_mapped_libraries(&maps))
+        .unwrap_or_default()
+}
+
+fn parse_mapped_libraries(maps: &str) -> Vec<MappedLibrary> {
+    let mut libraries: Vec<MappedLibrary> = Vec::new();
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(_range), Some(_perms), Some(offset), Some(dev), Some(inode), Some(path)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            continue;
+        };
+        if !path.starts_with('/')
+            || !path.contains(".so")
+            || !offset.trim_start_matches('0').is_empty()
+        {
+            continue;
+        }
+        let Some((major, minor)) = dev.split_once(':') else {
+            continue;
+        };
+        let (Ok(major), Ok(minor), Ok(inode)) = (
+            u32::from_str_radix(major, 16),
+            u32::from_str_radix(minor, 16),
+            inode.parse::<u64>(),
+        ) else {
+            continue;
+        };
+        let dev = libc::makedev(major, minor) as u64;
+        if inode != 0
+            && !libraries
+                .iter()
+                .any(|library| library.dev == dev && library.inode == inode)
+        {
+            libraries.push(MappedLibrary {
+                dev,
+                inode,
+                path: path.to_string(),
+            });
+        }
+    }
+    libraries
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn field(buf: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    buf.get(offset..offset + len)
+        .ok_or_else(|| invalid("truncated ELF structure"))
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(
+        field(buf, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(
+        field(buf, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// 按 ELF 类别读取地址宽度的字段。
+fn read_addr(buf: &[u8], offset: usize, is64: bool) -> io::Result<u64> {
+    if is64 {
+        Ok(u64::from_le_bytes(
+            field(buf, offset, 8)?.try_into().unwrap(),
+        ))
+    } else {
+        read_u32(buf, offset).map(u64::from)
+    }
+}
+
+fn read_at(file: &File, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+    if size > MAX_SECTION_SIZE {
+        return Err(invalid("section too large"));
+    }
+    let mut buf = vec![0; size as usize];
+    file.read_exact_at(&mut buf, offset)?;
+    Ok(buf)
+}
+
+/// 返回 `wanted` 中被 `path` 从其他库导入（.dynsym 中未定义）的符号。
+pub(crate) fn imported_symbols<'a>(path: &str, wanted: &[&'a CStr]) -> io::Result<Vec<&'a CStr>> {
+    let file = File::open(path)?;
+    let header = read_at(&file, 0, 64)?;
+    if header[..4] != *b"\x7fELF" {
+        return Err(invalid("not an ELF file"));
+    }
+    let is64 = match header[4] {
+        1 => false,
+        2 => true,
+        _ => return Err(invalid("unknown ELF class")),
+    };
+    if header[5] != 1 {
+        return Err(invalid("big-endian ELF"));
+    }
+
+    // e_shoff / e_shentsize / e_shnum 与各结构的布局见 elf.h
+    let (shoff, shentsize, shnum) = if is64 {
+        (
+            read_addr(&header, 0x28, true)?,
+            read_u16(&header, 0x3A)?,
+            read_u16(&header, 0x3C)?,
+        )
+    } else {
+        (
+            read_addr(&header, 0x20, false)?,
+            read_u16(&header, 0x2E)?,
+            read_u16(&header, 0x30)?,
+        )
+    };
+    let sections = read_at(&file, shoff, u64::from(shentsize) * u64::from(shnum))?;
+    let section = |index: usize| -> io::Result<(u32, u64, u64, u32)> {
+        let base = index * usize::from(shentsize);
+        let sh_type = read_u32(&sections, base + 4)?;
+        Ok(if is64 {
+            (
+                sh_type,
+                read_addr(&sections, base + 24, true)?,
+                read_addr(&sections, base + 32, true)?,
+                read_u32(&sections, base + 40)?,
+            )
+        } else {
+            (
+                sh_type,
+                read_addr(&sections, base + 16, false)?,
+                read_addr(&sections, base + 20, false)?,
+                read_u32(&sections, base + 24)?,
+            )
+        })
+    };
+
+    let mut dynsym = None;
+    for index in 0..usize::from(shnum) {
+        let (sh_type, offset, size, link) = section(index)?;
+        if sh_type == SHT_DYNSYM {
+            dynsym = Some((offset, size, link));
+            break;
+        }
+    }
+    let Some((sym_offset, sym_size, strtab_index)) = dynsym else {
+        return Ok(Vec::new());
+    };
+    let (_, str_offset, str_size, _) = section(strtab_index as usize)?;
+    let symbols = read_at(&file, sym_offset, sym_size)?;
+    let strings = read_at(&file, str_offset, str_size)?;
+
+    let (entry_size, shndx_offset) = if is64 { (24, 6) } else { (16, 14) };
+    let mut imported = Vec::new();
+    for entry in symbols.chunks_exact(entry_size) {
+        if read_u16(entry, shndx_offset)? != SHN_UNDEF {
+            continue;
+        }
+        let name_offset = read_u32(entry, 0)? as usize;
+        let Some(name) = strings
+            .get(name_offset..)
+            .and_then(|rest| CStr::from_bytes_until_nul(rest).ok())
+        else {
+            continue;
+        };
+        if let Some(symbol) = wanted.iter().find(|symbol| **symbol == name)
+            && !imported.contains(symbol)
+        {
+            imported.push(*symbol);
+        }
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_libraries_are_deduplicated_by_dev_and_inode() {
+        let maps = "\
+70a3d00000-70a3d10000 r--p 00000000 fd:05 777 /system/lib64/libc.so
+70a3d10000-70a3d20000 r-xp 00010000 fd:05 777 /system/lib64/libc.so
+70a3e00000-70a3e10000 r--p 00000000 fd:05 901 /system/lib64/libGLESv2.so
+70a3c00000-70a3c20000 r--s 00000000 00:13 1234 /dev/__properties__/u:object_r:vendor_prop:s0
+70a3f00000-70a3f10000 rw-p 00000000 00:00 0
+";
+        let dev = libc::makedev(0xfd, 0x05) as u64;
+        let libraries: Vec<_> = parse_mapped_libraries(maps)
+            .into_iter()
+            .map(|library| (library.dev, library.inode, library.path))
+            .collect();
+        assert_eq!(
+            libraries,
+            [
+                (dev, 777, "/system/lib64/libc.so".to_string()),
+                (dev, 901, "/system/lib64/libGLESv2.so".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_undefined_dynamic_symbols_count_as_imports() {
+        // 测试程序本身动态链接 libc：malloc 是导入符号，glGetString 不存在，
+        // 测试函数名只在 .symtab 中出现
+        let exe = std::env::current_exe().unwrap();
+        let wanted = [c"malloc", c"glGetString", c"main"];
+        let imported = imported_symbols(exe.to_str().unwrap(), &wanted).unwrap();
+        assert_eq!(imported, [c"malloc"]);
+    }
+}
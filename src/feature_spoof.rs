@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use jni::{Env, EnvUnowned, jni_sig, jni_str, objects::JObject};
+use zygisk_api::api::{V4, ZygiskApi};
+
+use crate::binder_hook::{self, JniParcel, Parcel};
+
+// PackageManager.hasSystemFeature 没有 native 实现：ApplicationPackageManager 经 Binder
+// 向 system_server 查询，结果在进程内缓存。能在 app 进程内截获的最底层 native 方法是
+// BinderProxy.transactNative，因此在那里按事务号和接口描述符识别 hasSystemFeature 调用并改写回复。
+// system_server 只在启动时解析 /system/etc/permissions/*.xml，覆盖这些文件对 app 无效。
+
+const PACKAGE_MANAGER_DESCRIPTOR: &str = "android.content.pm.IPackageManager";
+
+/// specialize 前确定、之后只读的特性伪装配置。
+static FEATURE_SPOOF: OnceLock<FeatureSpoof> = OnceLock::new();
+
+struct FeatureSpoof {
+    /// `IPackageManager.Stub.TRANSACTION_hasSystemFeature`，各 Android 版本不同，运行时读取。
+    code: i32,
+    overrides: FeatureOverrides,
+}
+
+/// 系统特性覆盖表。
+#[derive(Debug, Default)]
+struct FeatureOverrides {
+    /// 新增的特性 → 声明的版本（`name=version` 写法，未写版本时对任意版本返回 true）
+    added: HashMap<String, Option<i32>>,
+    removed: HashSet<String>,
+}
+
+impl FeatureOverrides {
+    fn from_config(add: &[String], remove: &[String]) -> Self {
+        let added = add
+            .iter()
+            .filter_map(|entry| {
+                let (name, version) = match entry.split_once('=') {
+                    Some((name, version)) => (name.trim(), version.trim().parse().ok()),
+                    None => (entry.trim(), None),
+                };
+                (!name.is_empty()).then(|| (name.to_string(), version))
+            })
+            .collect();
+        let removed = remove
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { added, removed }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// 返回伪装后的查询结果，`None` 表示使用真实结果。移除优先于新增。
+    fn lookup(&self, name: &str, version: i32) -> Option<bool> {
+        if self.removed.contains(name) {
+            return Some(false);
+        }
+        self.added
+            .get(name)
+            .map(|declared| declared.is_none_or(|declared| version <= declared))
+    }
+}
+
+/// Hook BinderProxy.transactNative 以伪装 `PackageManager.hasSystemFeature` 的结果（仅 full 模式）。
+pub fn hook_system_features(
+    api: &mut ZygiskApi<V4>,
+    env: &mut EnvUnowned,
+    add_features: &[String],
+    remove_features: &[String],
+) -> anyhow::Result<()> {
+    let overrides = FeatureOverrides::from_config(add_features, remove_features);
+    if overrides.is_empty() {
+        return Ok(());
+    }
+
+    // 在 specialize 之前读取隐藏字段，此时 hidden API 限制尚未生效。
+    let code = env
+        .with_env(|jenv| -> Result<i32, jni::errors::Error> {
+            let stub_class =
+                jenv.find_class(jni_str!("android/content/pm/IPackageManager$Stub"))?;
+            jenv.get_static_field(
+                &stub_class,
+                jni_str!("TRANSACTION_hasSystemFeature"),
+                jni_sig!("I"),
+            )?
+            .i()
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+    // AIDL 事务号从 FIRST_CALL_TRANSACTION (1) 开始
+    if code <= 0 {
+        anyhow::bail!("Failed to read IPackageManager.Stub.TRANSACTION_hasSystemFeature");
+    }
+    if FEATURE_SPOOF.set(FeatureSpoof { code, overrides }).is_err() {
+        anyhow::bail!("System feature overrides already installed");
+    }
+
+    binder_hook::install(api, env)
+}
+
+/// 事务号是否可能是 hasSystemFeature，供 hook 在接触 Parcel 之前快速过滤。
+pub(crate) fn wants_transaction(code: i32) -> bool {
+    FEATURE_SPOOF.get().is_some_and(|spoof| spoof.code == code)
+}
+
+/// 确认请求确实是 IPackageManager.hasSystemFeature 后，按覆盖表改写回复中的布尔值。
+pub(crate) fn patch_transaction(
+    env: &mut Env,
+    code: i32,
+    data: &JObject,
+    reply: &JObject,
+    header_ints: i32,
+) -> Result<(), jni::errors::Error> {
+    let Some(spoof) = FEATURE_SPOOF.get().filter(|spoof| spoof.code == code) else {
+        return Ok(());
+    };
+    let available = spoof_answer(
+        &spoof.overrides,
+        &mut JniParcel { env, parcel: data },
+        header_ints,
+    )?;
+    match available {
+        Some(available) => rewrite_reply(&mut JniParcel { env, parcel: reply }, available),
+        None => Ok(()),
+    }
+}
+
+/// 读取请求并查覆盖表，返回伪装结果。接口描述符不是 IPackageManager 时不读取参数。
+/// 请求 Parcel 的位置读取后恢复原样。
+fn spoof_answer(
+    overrides: &FeatureOverrides,
+    data: &mut dyn Parcel,
+    header_ints: i32,
+) -> Result<Option<bool>, jni::errors::Error> {
+    let data_position = data.position()?;
+    let answer = match binder_hook::read_interface(data, header_ints)? {
+        Some(descriptor) if descriptor == PACKAGE_MANAGER_DESCRIPTOR => {
+            let name = data.read_string()?;
+            let version = data.read_int()?;
+            name.and_then(|name| overrides.lookup(&name, version))
+        }
+        _ => None,
+    };
+    data.set_position(data_position)?;
+    Ok(answer)
+}
+
+/// 改写回复中的布尔值。回复带有异常时保持原样，由调用方照常抛出。
+fn rewrite_reply(reply: &mut dyn Parcel, available: bool) -> Result<(), jni::errors::Error> {
+    let reply_position = reply.position()?;
+    if reply.read_reply_header()? {
+        reply.write_int(available as i32)?;
+    }
+    reply.set_position(reply_position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 Parcel 读写顺序记录 int 和字符串的内存实现。
+    #[derive(Debug, Default, PartialEq)]
+    struct FakeParcel {
+        values: Vec<FakeValue>,
+        position: usize,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum FakeValue {
+        Int(i32),
+        Str(Option<String>),
+    }
+
+    impl FakeParcel {
+        fn new(values: Vec<FakeValue>) -> Self {
+            Self {
+                values,
+                position: 0,
+            }
+        }
+
+        fn next(&mut self) -> Result<FakeValue, jni::errors::Error> {
+            let value = self
+                .values
+                .get(self.position)
+                .cloned()
+                .ok_or(jni::errors::Error::IndexOutOfBounds)?;
+            self.position += 1;
+            Ok(value)
+        }
+    }
+
+    impl Parcel for FakeParcel {
+        fn position(&mut self) -> Result<i32, jni::errors::Error> {
+            Ok(self.position as i32)
+        }
+
+        fn set_position(&mut self, position: i32) -> Result<(), jni::errors::Error> {
+            self.position = position as usize;
+            Ok(())
+        }
+
+        fn read_int(&mut self) -> Result<i32, jni::errors::Error> {
+            match self.next()? {
+                FakeValue::Int(value) => Ok(value),
+                FakeValue::Str(_) => Err(jni::errors::Error::WrongObjectType),
+            }
+        }
+
+        fn read_string(&mut self) -> Result<Option<String>, jni::errors::Error> {
+            match self.next()? {
+                FakeValue::Str(value) => Ok(value),
+                FakeValue::Int(_) => Err(jni::errors::Error::WrongObjectType),
+            }
+        }
+
+        fn write_int(&mut self, value: i32) -> Result<(), jni::errors::Error> {
+            self.values.truncate(self.position);
+            self.values.push(FakeValue::Int(value));
+            self.position += 1;
+            Ok(())
+        }
+
+        fn read_reply_header(&mut self) -> Result<bool, jni::errors::Error> {
+            self.position = 0;
+            Ok(self.read_int()? == 0)
+        }
+    }
+
+    /// Android 11+ 的请求：strict mode 策略、work source、`SYST` 头、描述符，然后是参数。
+    fn request(descriptor: &str, args: &[FakeValue]) -> FakeParcel {
+        let mut values = vec![
+            FakeValue::Int(0x4200_0004),
+            FakeValue::Int(-1),
+            FakeValue::Int(0x5359_5354),
+            FakeValue::Str(Some(descriptor.to_string())),
+        ];
+        values.extend_from_slice(args);
+        FakeParcel::new(values)
+    }
+
+    fn feature_query(name: &str, version: i32) -> Vec<FakeValue> {
+        vec![
+            FakeValue::Str(Some(name.to_string())),
+            FakeValue::Int(version),
+        ]
+    }
+
+    #[test]
+    fn has_system_feature_reply_is_rewritten() {
+        let overrides = overrides(
+            &["android.hardware.vulkan.level=1"],
+            &["android.hardware.nfc"],
+        );
+
+        let mut data = request(
+            PACKAGE_MANAGER_DESCRIPTOR,
+            &feature_query("android.hardware.nfc", 0),
+        );
+        data.position = 7;
+        let answer = spoof_answer(&overrides, &mut data, 3).unwrap();
+        assert_eq!(answer, Some(false));
+        assert_eq!(data.position, 7);
+
+        let mut reply = FakeParcel::new(vec![FakeValue::Int(0), FakeValue::Int(1)]);
+        reply.position = 2;
+        rewrite_reply(&mut reply, false).unwrap();
+        assert_eq!(reply.values, vec![FakeValue::Int(0), FakeValue::Int(0)]);
+        assert_eq!(reply.position, 2);
+
+        // 回复带有异常时不改写
+        let mut failed = FakeParcel::new(vec![
+            FakeValue::Int(-3),
+            FakeValue::Str(Some("SecurityException".to_string())),
+        ]);
+        rewrite_reply(&mut failed, true).unwrap();
+        assert_eq!(failed.values[0], FakeValue::Int(-3));
+        assert_eq!(failed.values.len(), 2);
+    }
+
+    #[test]
+    fn other_interfaces_with_same_code_are_ignored() {
+        let overrides = overrides(&[], &["android.hardware.nfc"]);
+        // 事务号相同但属于其他接口，参数布局也不同
+        let mut data = request(
+            "android.app.IActivityManager",
+            &[FakeValue::Int(42), FakeValue::Int(7)],
+        );
+
+        assert_eq!(spoof_answer(&overrides, &mut data, 3).unwrap(), None);
+        assert_eq!(data.position, 0);
+    }
+
+    fn overrides(add: &[&str], remove: &[&str]) -> FeatureOverrides {
+        let add: Vec<String> = add.iter().map(|s| s.to_string()).collect();
+        let remove: Vec<String> = remove.iter().map(|s| s.to_string()).collect();
+        FeatureOverrides::from_config(&add, &remove)
+    }
+
+    #[test]
+    fn added_feature_without_version_matches_any_version() {
+        let overrides = overrides(&["com.google.android.feature.PIXEL_EXPERIENCE"], &[]);
+
+        assert_eq!(
+            overrides.lookup("com.google.android.feature.PIXEL_EXPERIENCE", 0),
+            Some(true)
+        );
+        assert_eq!(overrides.lookup("android.hardware.nfc", 0), None);
+    }
+
+    #[test]
+    fn added_feature_version_is_honoured() {
+        let overrides = overrides(&["android.hardware.vulkan.level = 1"], &[]);
+
+        assert_eq!(
+            overrides.lookup("android.hardware.vulkan.level", 0),
+            Some(true)
+        );
+        assert_eq!(
+            overrides.lookup("android.hardware.vulkan.level", 1),
+            Some(true)
+        );
+        assert_eq!(
+            overrides.lookup("android.hardware.vulkan.level", 2),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn removal_wins_over_addition() {
+        let overrides = overrides(&["android.hardware.nfc"], &["android.hardware.nfc", " "]);
+
+        assert_eq!(overrides.lookup("android.hardware.nfc", 0), Some(false));
+        assert_eq!(overrides.removed.len(), 1);
+    }
+}
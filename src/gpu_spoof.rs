@@ -0,0 +1,480 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    sync::{
+        OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use anyhow::Context;
+use log::warn;
+use zygisk_api::api::{V4, ZygiskApi};
+
+use crate::config::GpuConfig;
+use crate::elf_imports::{imported_symbols, mapped_libraries};
+
+// GLES 常量（GLES2/gl2.h）
+const GL_VENDOR: u32 = 0x1F00;
+const GL_RENDERER: u32 = 0x1F01;
+const GL_VERSION: u32 = 0x1F02;
+
+// vulkan_core.h: VK_MAX_PHYSICAL_DEVICE_NAME_SIZE
+const VK_MAX_PHYSICAL_DEVICE_NAME_SIZE: usize = 256;
+
+/// `VkPhysicalDeviceProperties` 的前缀部分。
+///
+/// 只声明需要改写的成员，后面的 `pipelineCacheUUID`/`limits`/`sparseProperties`
+/// 由调用方分配，我们不触碰。
+#[repr(C)]
+struct VkPhysicalDevicePropertiesHead {
+    api_version: u32,
+    driver_version: u32,
+    vendor_id: u32,
+    device_id: u32,
+    device_type: i32,
+    device_name: [c_char; VK_MAX_PHYSICAL_DEVICE_NAME_SIZE],
+}
+
+/// `VkPhysicalDeviceProperties2` 的前缀部分。
+#[repr(C)]
+struct VkPhysicalDeviceProperties2Head {
+    s_type: i32,
+    p_next: *mut c_void,
+    properties: VkPhysicalDevicePropertiesHead,
+}
+
+type GlGetStringFn = unsafe extern "C" fn(name: u32) -> *const u8;
+type VkGetPhysicalDevicePropertiesFn =
+    unsafe extern "C" fn(device: *mut c_void, props: *mut VkPhysicalDevicePropertiesHead);
+type VkGetPhysicalDeviceProperties2Fn =
+    unsafe extern "C" fn(device: *mut c_void, props: *mut VkPhysicalDeviceProperties2Head);
+type VkGetInstanceProcAddrFn =
+    unsafe extern "C" fn(instance: *mut c_void, name: *const c_char) -> *const c_void;
+
+// 原函数地址，0 表示尚未记录。specialize 时由 Zygisk 写入，Vulkan 的属性查询
+// 也可能由 vkGetInstanceProcAddr hook 在渲染线程补记。
+static ORIGINAL_GL_GET_STRING: AtomicUsize = AtomicUsize::new(0);
+static ORIGINAL_VK_GET_PROPERTIES: AtomicUsize = AtomicUsize::new(0);
+static ORIGINAL_VK_GET_PROPERTIES2: AtomicUsize = AtomicUsize::new(0);
+static ORIGINAL_VK_GET_INSTANCE_PROC_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// 读取原函数地址。
+///
+/// # Safety
+/// `F` 必须是与 `slot` 中记录的函数签名一致的函数指针类型。
+unsafe fn original<F: Copy>(slot: &AtomicUsize) -> Option<F> {
+    const { assert!(size_of::<F>() == size_of::<usize>()) };
+    match slot.load(Ordering::Acquire) {
+        0 => None,
+        address => Some(unsafe { std::mem::transmute_copy(&address) }),
+    }
+}
+
+/// 伪装值。`hook_gpu_identity` 在提交 hook 之前写入一次，之后只读，
+/// `glGetString` 直接返回其中 CString 的指针。
+struct FakeGpu {
+    vendor: Option<CString>,
+    renderer: Option<CString>,
+    version: Option<CString>,
+    vulkan_device_name: Option<CString>,
+    vulkan_device_id: Option<u32>,
+    vulkan_vendor_id: Option<u32>,
+}
+
+static FAKE_GPU: OnceLock<FakeGpu> = OnceLock::new();
+
+impl FakeGpu {
+    fn from_config(gpu: &GpuConfig) -> anyhow::Result<Self> {
+        fn to_cstring(value: &Option<String>, field: &str) -> anyhow::Result<Option<CString>> {
+            value
+                .as_deref()
+                .filter(|v| !v.is_empty())
+                .map(|v| CString::new(v).with_context(|| format!("gpu.{field} contains NUL")))
+                .transpose()
+        }
+
+        Ok(Self {
+            vendor: to_cstring(&gpu.vendor, "vendor")?,
+            renderer: to_cstring(&gpu.renderer, "renderer")?,
+            version: to_cstring(&gpu.version, "version")?,
+            vulkan_device_name: to_cstring(&gpu.vulkan_device_name, "vulkan_device_name")?,
+            vulkan_device_id: gpu.vulkan_device_id,
+            vulkan_vendor_id: gpu.vulkan_vendor_id,
+        })
+    }
+
+    fn has_gl(&self) -> bool {
+        self.vendor.is_some() || self.renderer.is_some() || self.version.is_some()
+    }
+
+    fn gl_string(&self, name: u32) -> Option<&CString> {
+        match name {
+            GL_VENDOR => self.vendor.as_ref(),
+            GL_RENDERER => self.renderer.as_ref(),
+            GL_VERSION => self.version.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn has_vulkan(&self) -> bool {
+        self.vulkan_device_name.is_some()
+            || self.vulkan_device_id.is_some()
+            || self.vulkan_vendor_id.is_some()
+    }
+
+    fn patch_properties(&self, props: &mut VkPhysicalDevicePropertiesHead) {
+        if let Some(vendor_id) = self.vulkan_vendor_id {
+            props.vendor_id = vendor_id;
+        }
+        if let Some(device_id) = self.vulkan_device_id {
+            props.device_id = device_id;
+        }
+        if let Some(name) = &self.vulkan_device_name {
+            write_device_name(&mut props.device_name, name);
+        }
+    }
+}
+
+/// 按 Vulkan 规范写入以 NUL 结尾的 deviceName，超长时截断。
+fn write_device_name(dst: &mut [c_char; VK_MAX_PHYSICAL_DEVICE_NAME_SIZE], name: &CStr) {
+    let bytes = name.to_bytes();
+    let len = bytes.len().min(VK_MAX_PHYSICAL_DEVICE_NAME_SIZE - 1);
+    for (slot, byte) in dst.iter_mut().zip(&bytes[..len]) {
+        *slot = *byte as c_char;
+    }
+    dst[len..].fill(0);
+}
+
+unsafe extern "C" fn my_gl_get_string(name: u32) -> *const u8 {
+    if let Some(value) = FAKE_GPU.get().and_then(|gpu| gpu.gl_string(name)) {
+        return value.as_ptr().cast();
+    }
+
+    match unsafe { original::<GlGetStringFn>(&ORIGINAL_GL_GET_STRING) } {
+        Some(orig_fn) => unsafe { orig_fn(name) },
+        None => std::ptr::null(),
+    }
+}
+
+unsafe extern "C" fn my_vk_get_physical_device_properties(
+    device: *mut c_void,
+    props: *mut VkPhysicalDevicePropertiesHead,
+) {
+    unsafe {
+        let Some(orig_fn) =
+            original::<VkGetPhysicalDevicePropertiesFn>(&ORIGINAL_VK_GET_PROPERTIES)
+        else {
+            return;
+        };
+        orig_fn(device, props);
+
+        if let Some(props) = props.as_mut()
+            && let Some(gpu) = FAKE_GPU.get()
+        {
+            gpu.patch_properties(props);
+        }
+    }
+}
+
+unsafe extern "C" fn my_vk_get_physical_device_properties2(
+    device: *mut c_void,
+    props: *mut VkPhysicalDeviceProperties2Head,
+) {
+    unsafe {
+        let Some(orig_fn) =
+            original::<VkGetPhysicalDeviceProperties2Fn>(&ORIGINAL_VK_GET_PROPERTIES2)
+        else {
+            return;
+        };
+        orig_fn(device, props);
+
+        if let Some(props) = props.as_mut()
+            && let Some(gpu) = FAKE_GPU.get()
+        {
+            gpu.patch_properties(&mut props.properties);
+        }
+    }
+}
+
+/// 游戏引擎通常通过 `vkGetInstanceProcAddr` 动态获取函数指针，绕过 PLT。
+/// 这里把属性查询相关的入口替换为我们的实现，原始指针记录下来供转发。
+unsafe extern "C" fn my_vk_get_instance_proc_addr(
+    instance: *mut c_void,
+    name: *const c_char,
+) -> *const c_void {
+    unsafe {
+        let Some(orig_fn) =
+            original::<VkGetInstanceProcAddrFn>(&ORIGINAL_VK_GET_INSTANCE_PROC_ADDR)
+        else {
+            return std::ptr::null();
+        };
+        let resolved = orig_fn(instance, name);
+        if resolved.is_null() || name.is_null() {
+            return resolved;
+        }
+
+        let (slot, replacement) = match CStr::from_ptr(name).to_bytes() {
+            b"vkGetPhysicalDeviceProperties" => (
+                &ORIGINAL_VK_GET_PROPERTIES,
+                my_vk_get_physical_device_properties as *const c_void,
+            ),
+            b"vkGetPhysicalDeviceProperties2" | b"vkGetPhysicalDeviceProperties2KHR" => (
+                &ORIGINAL_VK_GET_PROPERTIES2,
+                my_vk_get_physical_device_properties2 as *const c_void,
+            ),
+            _ => return resolved,
+        };
+        let _ = slot.compare_exchange(0, resolved as usize, Ordering::AcqRel, Ordering::Acquire);
+        replacement
+    }
+}
+
+/// 一个需要 hook 的导入符号。
+struct GpuHook {
+    symbol: &'static CStr,
+    replacement: usize,
+    original: &'static AtomicUsize,
+}
+
+impl GpuHook {
+    /// 记录 Zygisk 写回的原函数地址，已有记录时保持不变。
+    fn record_original(&self, address: usize) {
+        if address != 0 && address != self.replacement {
+            let _ = self
+                .original
+                .compare_exchange(0, address, Ordering::AcqRel, Ordering::Acquire);
+        }
+    }
+}
+
+/// 需要 hook 的 GPU 查询入口。
+fn gpu_hooks(has_gl: bool, has_vulkan: bool) -> Vec<GpuHook> {
+    let mut hooks = Vec::new();
+    if has_gl {
+        hooks.push(GpuHook {
+            symbol: c"glGetString",
+            replacement: my_gl_get_string as *const () as usize,
+            original: &ORIGINAL_GL_GET_STRING,
+        });
+    }
+    if has_vulkan {
+        hooks.extend([
+            GpuHook {
+                symbol: c"vkGetPhysicalDeviceProperties",
+                replacement: my_vk_get_physical_device_properties as *const () as usize,
+                original: &ORIGINAL_VK_GET_PROPERTIES,
+            },
+            GpuHook {
+                symbol: c"vkGetPhysicalDeviceProperties2",
+                replacement: my_vk_get_physical_device_properties2 as *const () as usize,
+                original: &ORIGINAL_VK_GET_PROPERTIES2,
+            },
+            GpuHook {
+                symbol: c"vkGetInstanceProcAddr",
+                replacement: my_vk_get_instance_proc_addr as *const () as usize,
+                original: &ORIGINAL_VK_GET_INSTANCE_PROC_ADDR,
+            },
+        ]);
+    }
+    hooks
+}
+
+/// 通过 PLT Hook 伪装 GPU 标识（libGLESv2 的 `glGetString`，libvulkan 的属性查询）。
+///
+/// 只为 specialize 时已加载、且确实导入了这些符号的库（如 Java 层
+/// `GLES20.glGetString` 所在的 libandroid_runtime）注册 hook，其他库的 GOT 不动。
+/// 之后才加载的库（游戏引擎等）不在覆盖范围内。
+pub fn hook_gpu_identity(api: &mut ZygiskApi<V4>, gpu: &GpuConfig) -> anyhow::Result<()> {
+    let fake = FakeGpu::from_config(gpu)?;
+    let (has_gl, has_vulkan) = (fake.has_gl(), fake.has_vulkan());
+    if !has_gl && !has_vulkan {
+        return Ok(());
+    }
+    if FAKE_GPU.set(fake).is_err() {
+        anyhow::bail!("GPU identity hooks are already installed");
+    }
+
+    let hooks = gpu_hooks(has_gl, has_vulkan);
+    let symbols: Vec<&CStr> = hooks.iter().map(|hook| hook.symbol).collect();
+    let mut targets = Vec::new();
+    for library in mapped_libraries() {
+        let imported = match imported_symbols(&library.path, &symbols) {
+            Ok(imported) => imported,
+            Err(e) => {
+                warn!("Skipping {} for GPU hooks: {e}", library.path);
+                continue;
+            }
+        };
+        for hook in hooks.iter().filter(|hook| imported.contains(&hook.symbol)) {
+            targets.push((library.dev, library.inode, hook));
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    // Zygisk 在提交时才写回原函数地址，缓冲区需保持到提交之后
+    let mut backups = vec![std::ptr::null::<()>(); targets.len()];
+    for (&(dev, inode, hook), backup) in targets.iter().zip(backups.iter_mut()) {
+        unsafe {
+            api.plt_hook_register(
+                dev as _,
+                inode as _,
+                hook.symbol.to_owned(),
+                hook.replacement as *const (),
+                backup,
+            );
+        }
+    }
+    if !api.plt_hook_commit() {
+        anyhow::bail!("plt_hook_commit failed for GPU identity hooks");
+    }
+    for ((_, _, hook), backup) in targets.iter().zip(&backups) {
+        hook.record_original(*backup as usize);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // 钩子状态是进程级全局变量，测试之间需要串行执行。
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    // 模拟 libGLESv2/libvulkan 的桩实现，返回一块“真实”的 Adreno GPU。
+    unsafe extern "C" fn stub_gl_get_string(name: u32) -> *const u8 {
+        match name {
+            GL_VENDOR => c"Qualcomm".as_ptr().cast(),
+            GL_RENDERER => c"Adreno (TM) 740".as_ptr().cast(),
+            GL_VERSION => c"OpenGL ES 3.2 V@0676.0".as_ptr().cast(),
+            _ => std::ptr::null(),
+        }
+    }
+
+    unsafe extern "C" fn stub_vk_get_properties(
+        _device: *mut c_void,
+        props: *mut VkPhysicalDevicePropertiesHead,
+    ) {
+        let props = unsafe { &mut *props };
+        props.vendor_id = 0x5143;
+        props.device_id = 0x43050a01;
+        write_device_name(&mut props.device_name, c"Adreno (TM) 740");
+    }
+
+    unsafe extern "C" fn stub_vk_get_properties2(
+        device: *mut c_void,
+        props: *mut VkPhysicalDeviceProperties2Head,
+    ) {
+        unsafe { stub_vk_get_properties(device, &mut (*props).properties) };
+    }
+
+    unsafe extern "C" fn stub_vk_get_instance_proc_addr(
+        _instance: *mut c_void,
+        name: *const c_char,
+    ) -> *const c_void {
+        match unsafe { CStr::from_ptr(name) }.to_bytes() {
+            b"vkGetPhysicalDeviceProperties" => stub_vk_get_properties as *const c_void,
+            b"vkGetPhysicalDeviceProperties2" => stub_vk_get_properties2 as *const c_void,
+            _ => std::ptr::null(),
+        }
+    }
+
+    /// 所有测试共用一份伪装配置：只改 renderer 与 Vulkan 标识。
+    fn install() {
+        FAKE_GPU.get_or_init(|| {
+            FakeGpu::from_config(&GpuConfig {
+                renderer: Some("Mali-G720 Immortalis MC12".to_string()),
+                vulkan_device_name: Some("Mali-G720 Immortalis MC12".to_string()),
+                vulkan_device_id: Some(0x7212_0000),
+                vulkan_vendor_id: Some(0x13B5),
+                ..Default::default()
+            })
+            .unwrap()
+        });
+        ORIGINAL_GL_GET_STRING.store(stub_gl_get_string as *const () as usize, Ordering::Release);
+        ORIGINAL_VK_GET_PROPERTIES.store(0, Ordering::Release);
+        ORIGINAL_VK_GET_PROPERTIES2.store(0, Ordering::Release);
+        ORIGINAL_VK_GET_INSTANCE_PROC_ADDR.store(
+            stub_vk_get_instance_proc_addr as *const () as usize,
+            Ordering::Release,
+        );
+    }
+
+    fn empty_properties() -> VkPhysicalDevicePropertiesHead {
+        VkPhysicalDevicePropertiesHead {
+            api_version: 0,
+            driver_version: 0,
+            vendor_id: 0,
+            device_id: 0,
+            device_type: 0,
+            device_name: [0; VK_MAX_PHYSICAL_DEVICE_NAME_SIZE],
+        }
+    }
+
+    fn device_name(props: &VkPhysicalDevicePropertiesHead) -> String {
+        unsafe { CStr::from_ptr(props.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn gl_get_string_overrides_configured_names_only() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        install();
+
+        let read = |name| unsafe {
+            CStr::from_ptr(my_gl_get_string(name).cast())
+                .to_string_lossy()
+                .into_owned()
+        };
+        assert_eq!(read(GL_RENDERER), "Mali-G720 Immortalis MC12");
+        assert_eq!(read(GL_VENDOR), "Qualcomm");
+        assert_eq!(read(GL_VERSION), "OpenGL ES 3.2 V@0676.0");
+    }
+
+    #[test]
+    fn vulkan_properties_are_patched_through_proc_addr() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        install();
+
+        let get_properties: VkGetPhysicalDevicePropertiesFn = unsafe {
+            std::mem::transmute(my_vk_get_instance_proc_addr(
+                std::ptr::null_mut(),
+                c"vkGetPhysicalDeviceProperties".as_ptr(),
+            ))
+        };
+        let mut props = empty_properties();
+        unsafe { get_properties(std::ptr::null_mut(), &mut props) };
+        assert_eq!(props.vendor_id, 0x13B5);
+        assert_eq!(props.device_id, 0x7212_0000);
+        assert_eq!(device_name(&props), "Mali-G720 Immortalis MC12");
+
+        let get_properties2: VkGetPhysicalDeviceProperties2Fn = unsafe {
+            std::mem::transmute(my_vk_get_instance_proc_addr(
+                std::ptr::null_mut(),
+                c"vkGetPhysicalDeviceProperties2".as_ptr(),
+            ))
+        };
+        let mut props2 = VkPhysicalDeviceProperties2Head {
+            s_type: 0,
+            p_next: std::ptr::null_mut(),
+            properties: empty_properties(),
+        };
+        unsafe { get_properties2(std::ptr::null_mut(), &mut props2) };
+        assert_eq!(props2.properties.vendor_id, 0x13B5);
+        assert_eq!(device_name(&props2.properties), "Mali-G720 Immortalis MC12");
+    }
+
+    #[test]
+    fn device_name_is_truncated_and_nul_terminated() {
+        let mut dst = [1 as c_char; VK_MAX_PHYSICAL_DEVICE_NAME_SIZE];
+        let long = CString::new("x".repeat(400)).unwrap();
+        write_device_name(&mut dst, &long);
+        assert_eq!(dst[VK_MAX_PHYSICAL_DEVICE_NAME_SIZE - 1], 0);
+        assert_eq!(dst[VK_MAX_PHYSICAL_DEVICE_NAME_SIZE - 2], b'x' as c_char);
+    }
+}
@@ -1,17 +1,23 @@
-use std::ffi::{CStr, CString};
+use std::{
+    ffi::{CStr, CString},
+    sync::atomic::Ordering,
+};
 
 use anyhow::Context;
 use jni::{
-    Env, EnvUnowned, jni_sig, jni_str,
-    objects::{JClass, JString, JValue},
+    Env, EnvUnowned, Outcome, jni_sig, jni_str,
+    objects::{JClass, JObject, JString, JValue},
     strings::JNIStr,
     sys::JNINativeMethod,
 };
 use zygisk_api::api::{V4, ZygiskApi};
 
 use crate::{
-    config::MergedAppConfig,
-    state::{FAKE_PROPS, ORIGINAL_NATIVE_GET, OriginalNativeGet},
+    config::{DisplayConfig, MergedAppConfig},
+    state::{
+        FAKE_PROPS, FAKE_STORAGE_TOTAL_BYTES, FAKE_TOTAL_MEMORY_BYTES, ORIGINAL_NATIVE_GET,
+        ORIGINAL_STATVFS, OriginalNativeGet, OriginalStatvfs,
+    },
 };
 
 static mut ORIGINAL_SYSTEM_PROPERTY_GET: Option<
@@ -147,6 +153,147 @@ fn set_build_int_field(
     Ok(())
 }
 
+/// 根据配置改写 zygote 中已经初始化的 DisplayMetrics（仅 full 模式）。
+///
+/// `DENSITY_DEVICE`/`DENSITY_DEVICE_STABLE` 在 zygote 预加载时已从 `ro.sf.lcd_density`
+/// 初始化，属性 Hook 对它们无效，需要直接写静态字段。系统 Resources 的
+/// DisplayMetrics 同样在 zygote 中创建，一并改写分辨率和密度。之后从 Display
+/// 重新读取的值由 `display_spoof` 改写 DisplayInfo 保证一致。
+pub fn hook_display_metrics(env: &mut EnvUnowned, display: &DisplayConfig) -> anyhow::Result<()> {
+    let outcome = env
+        .with_env(|jenv| -> anyhow::Result<()> {
+            let result = set_display_metrics(jenv, display);
+            if result.is_err() {
+                jenv.exception_clear();
+            }
+            result
+        })
+        .into_outcome();
+    match outcome {
+        Outcome::Ok(()) => Ok(()),
+        Outcome::Err(err) => Err(err),
+        Outcome::Panic(_) => anyhow::bail!("Panicked while faking DisplayMetrics"),
+    }
+}
+
+fn set_display_metrics(env: &mut Env, display: &DisplayConfig) -> anyhow::Result<()> {
+    let metrics_class = env
+        .find_class(jni_str!("android/util/DisplayMetrics"))
+        .context("Failed to find DisplayMetrics class")?;
+
+    if let Some(density_dpi) = display.density_dpi {
+        set_build_int_field(
+            env,
+            &metrics_class,
+            jni_str!("DENSITY_DEVICE"),
+            density_dpi as i32,
+        )?;
+        set_build_int_field(
+            env,
+            &metrics_class,
+            jni_str!("DENSITY_DEVICE_STABLE"),
+            density_dpi as i32,
+        )?;
+    }
+
+    let resources_class = env
+        .find_class(jni_str!("android/content/res/Resources"))
+        .context("Failed to find Resources class")?;
+    let system_resources = env
+        .call_static_method(
+            &resources_class,
+            jni_str!("getSystem"),
+            jni_sig!("()Landroid/content/res/Resources;"),
+            &[],
+        )
+        .and_then(|v| v.l())
+        .context("Failed to get system Resources")?;
+    let metrics = env
+        .call_method(
+            &system_resources,
+            jni_str!("getDisplayMetrics"),
+            jni_sig!("()Landroid/util/DisplayMetrics;"),
+            &[],
+        )
+        .and_then(|v| v.l())
+        .context("Failed to get system DisplayMetrics")?;
+
+    if display.width.is_some() || display.height.is_some() {
+        let current_width = env
+            .get_field(&metrics, jni_str!("widthPixels"), jni_sig!("I"))
+            .and_then(|v| v.i())
+            .context("Failed to read widthPixels")?;
+        let current_height = env
+            .get_field(&metrics, jni_str!("heightPixels"), jni_sig!("I"))
+            .and_then(|v| v.i())
+            .context("Failed to read heightPixels")?;
+
+        // 配置按竖屏方向填写，当前处于横屏时交换宽高。
+        let mut width = display.width.map_or(current_width, |w| w as i32);
+        let mut height = display.height.map_or(current_height, |h| h as i32);
+        if current_width > current_height && width < height {
+            std::mem::swap(&mut width, &mut height);
+        }
+
+        env.set_field(
+            &metrics,
+            jni_str!("widthPixels"),
+            jni_sig!("I"),
+            JValue::Int(width),
+        )
+        .context("Failed to set widthPixels")?;
+        env.set_field(
+            &metrics,
+            jni_str!("heightPixels"),
+            jni_sig!("I"),
+            JValue::Int(height),
+        )
+        .context("Failed to set heightPixels")?;
+    }
+
+    if let Some(density_dpi) = display.density_dpi {
+        let density = env
+            .get_field(&metrics, jni_str!("density"), jni_sig!("F"))
+            .and_then(|v| v.f())
+            .context("Failed to read density")?;
+        let scaled_density = env
+            .get_field(&metrics, jni_str!("scaledDensity"), jni_sig!("F"))
+            .and_then(|v| v.f())
+            .context("Failed to read scaledDensity")?;
+        // 保持字体缩放比例不变
+        let font_scale = if density > 0.0 {
+            scaled_density / density
+        } else {
+            1.0
+        };
+        let new_density = density_dpi as f32 / 160.0;
+
+        env.set_field(
+            &metrics,
+            jni_str!("densityDpi"),
+            jni_sig!("I"),
+            JValue::Int(density_dpi as i32),
+        )
+        .context("Failed to set densityDpi")?;
+        env.set_field(
+            &metrics,
+            jni_str!("density"),
+            jni_sig!("F"),
+            JValue::Float(new_density),
+        )
+        .context("Failed to set density")?;
+        env.set_field(
+            &metrics,
+            jni_str!("scaledDensity"),
+            jni_sig!("F"),
+            JValue::Float(new_density * font_scale),
+        )
+        .context("Failed to set scaledDensity")?;
+    }
+
+    Ok(())
+}
+
 /// Hook SystemProperties.native_get 以截获属性查询。
 pub fn hook_system_properties(api: &mut ZygiskApi<V4>, env: &mut EnvUnowned) -> anyhow::Result<()> {
     let mut methods = [JNINativeMethod {
@@ -211,6 +358,177 @@ pub unsafe extern "C" fn native_get_hook(
     result.resolve::<jni::errors::ThrowRuntimeExAndDefault>()
 }
 
+/// Hook Process.getTotalMemory 以伪装总内存。
+///
+/// `ActivityManager.MemoryInfo` 由 `memory_spoof` 改写 Binder 回复处理，读取 /proc/meminfo
+/// 的代码由 companion 的 meminfo 覆盖处理（需开启 `meminfo_overlay`）。
+pub fn hook_total_memory(
+    api: &mut ZygiskApi<V4>,
+    env: &mut EnvUnowned,
+    ram_total_mb: u64,
+) -> anyhow::Result<()> {
+    FAKE_TOTAL_MEMORY_BYTES.store(ram_total_mb.saturating_mul(1024 * 1024), Ordering::Relaxed);
+
+    let mut methods = [JNINativeMethod {
+        name: c"getTotalMemory".as_ptr().cast_mut(),
+        signature: c"()J".as_ptr().cast_mut(),
+        fnPtr: get_total_memory_hook as *mut std::ffi::c_void,
+    }];
+
+    let class_name = unsafe { JNIStr::from_ptr(c"android/os/Process".as_ptr()) };
+
+    env.with_env(|jenv| -> Result<(), jni::errors::Error> {
+        let env_unowned = unsafe { EnvUnowned::from_raw(jenv.get_raw()) };
+        unsafe {
+            api.hook_jni_native_methods(env_unowned, class_name, &mut methods);
+        }
+        Ok(())
+    })
+    .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+
+    if methods[0].fnPtr.is_null() {
+        anyhow::bail!("Failed to hook android.os.Process.getTotalMemory");
+    }
+
+    Ok(())
+}
+
+unsafe extern "C" fn get_total_memory_hook(
+    _env: *mut jni::sys::JNIEnv,
+    _class: jni::sys::jclass,
+) -> jni::sys::jlong {
+    FAKE_TOTAL_MEMORY_BYTES.load(Ordering::Relaxed) as jni::sys::jlong
+}
+
+/// Hook libcore.io.Linux.statvfs（`StatFs`/`Os.statvfs` 的底层实现）以伪装存储总容量。
+pub fn hook_storage_stats(
+    api: &mut ZygiskApi<V4>,
+    env: &mut EnvUnowned,
+    storage_total_gb: u64,
+) -> anyhow::Result<()> {
+    // 存储厂商按十进制 GB 标称容量
+    FAKE_STORAGE_TOTAL_BYTES.store(
+        storage_total_gb.saturating_mul(1_000_000_000),
+        Ordering::Relaxed,
+    );
+
+    let mut methods = [JNINativeMethod {
+        name: c"statvfs".as_ptr().cast_mut(),
+        signature: c"(Ljava/lang/String;)Landroid/system/StructStatVfs;"
+            .as_ptr()
+            .cast_mut(),
+        fnPtr: statvfs_hook as *mut std::ffi::c_void,
+    }];
+
+    let class_name = unsafe { JNIStr::from_ptr(c"libcore/io/Linux".as_ptr()) };
+
+    env.with_env(|jenv| -> Result<(), jni::errors::Error> {
+        let env_unowned = unsafe { EnvUnowned::from_raw(jenv.get_raw()) };
+        unsafe {
+            api.hook_jni_native_methods(env_unowned, class_name, &mut methods);
+        }
+        Ok(())
+    })
+    .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+
+    if methods[0].fnPtr.is_null() {
+        anyhow::bail!("Failed to hook libcore.io.Linux.statvfs");
+    }
+    let original_fn_ptr =
+        unsafe { std::mem::transmute::<*mut std::ffi::c_void, OriginalStatvfs>(methods[0].fnPtr) };
+    *ORIGINAL_STATVFS.lock().unwrap() = Some(original_fn_ptr);
+
+    Ok(())
+}
+
+/// 为 Hook 提供的 libcore.io.Linux.statvfs 替身实现。
+///
+/// 先调用原实现，再对用户存储分区（/data、/storage、/sdcard）的结果按伪装容量
+/// 改写 `f_blocks`，`f_bfree`/`f_bavail` 等比缩放以保持使用率不变。
+pub unsafe extern "C" fn statvfs_hook(
+    env: *mut jni::sys::JNIEnv,
+    this: jni::sys::jobject,
+    path: jni::sys::jstring,
+) -> jni::sys::jobject {
+    let Some(orig_fn) = *ORIGINAL_STATVFS.lock().unwrap() else {
+        return std::ptr::null_mut();
+    };
+    let result = unsafe { orig_fn(env, this, path) };
+    let total_bytes = FAKE_STORAGE_TOTAL_BYTES.load(Ordering::Relaxed);
+    if result.is_null() || path.is_null() || total_bytes == 0 {
+        return result;
+    }
+
+    let mut env_wrapper = unsafe { EnvUnowned::from_raw(env) };
+    env_wrapper
+        .with_env(|jenv| -> Result<(), jni::errors::Error> {
+            let path_jstring = unsafe { JString::from_raw(jenv, path) };
+            let path_string = path_jstring.mutf8_chars(jenv)?.to_string();
+            if !["/data", "/storage", "/sdcard"]
+                .iter()
+                .any(|prefix| path_string.starts_with(prefix))
+            {
+                return Ok(());
+            }
+
+            let stat = unsafe { JObject::from_raw(jenv, result) };
+            patch_statvfs(jenv, &stat, total_bytes)
+        })
+        .resolve::<jni::errors::ThrowRuntimeExAndDefault>();
+
+    result
+}
+
+fn patch_statvfs(
+    env: &mut Env,
+    stat: &JObject,
+    total_bytes: u64,
+) -> Result<(), jni::errors::Error> {
+    let frsize = env
+        .get_field(stat, jni_str!("f_frsize"), jni_sig!("J"))?
+        .j()?;
+    let blocks = env
+        .get_field(stat, jni_str!("f_blocks"), jni_sig!("J"))?
+        .j()?;
+    if frsize <= 0 || blocks <= 0 {
+        return Ok(());
+    }
+
+    let new_blocks = (total_bytes / frsize as u64) as i64;
+    let scale = |value: i64| -> i64 {
+        ((value.max(0) as i128 * new_blocks as i128) / blocks as i128).min(new_blocks as i128)
+            as i64
+    };
+
+    let bfree = env
+        .get_field(stat, jni_str!("f_bfree"), jni_sig!("J"))?
+        .j()?;
+    let bavail = env
+        .get_field(stat, jni_str!("f_bavail"), jni_sig!("J"))?
+        .j()?;
+
+    env.set_field(
+        stat,
+        jni_str!("f_blocks"),
+        jni_sig!("J"),
+        JValue::Long(new_blocks),
+    )?;
+    env.set_field(
+        stat,
+        jni_str!("f_bfree"),
+        jni_sig!("J"),
+        JValue::Long(scale(bfree)),
+    )?;
+    env.set_field(
+        stat,
+        jni_str!("f_bavail"),
+        jni_sig!("J"),
+        JValue::Long(scale(bavail)),
+    )?;
+
+    Ok(())
+}
+
 unsafe extern "C" fn my_system_property_get(
     name: *const libc::c_char,
     value: *mut libc::c_char,
@@ -230,7 +548,7 @@ unsafe extern "C" fn my_system_property_get(
             let len = std::cmp::min(fake_value.len(), 91);
             unsafe {
                 std::ptr::copy(fake_value.as_ptr() as *const libc::c_char, value, len);
-                value.add(len).write(b'\0');
+                value.add(len).write(0);
             }
             len as libc::c_int
         })
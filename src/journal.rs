@@ -0,0 +1,180 @@
+// 属性会话日志：在修改属性之前记录其真实原值。
+//
+// companion 或 restore watcher 异常退出时，内存中的原值随进程丢失，伪装值会一直
+// 保留到重启。日志落盘后，companion 下次启动或 CLI `restore-all` 可据此恢复。
+// 属性区在重启后由 init 重新加载，因此其他开机周期留下的日志视为失效。
+//
+// 本文件只依赖 std、anyhow 与 serde，device_faker_cli 通过 `#[path]` 复用。
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+pub const JOURNAL_PATH: &str = "/data/adb/device_faker/prop_journal.json";
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// 属性被修改前的状态。“不存在”与“空值”需要区分，前者恢复时应重新删除。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PropOriginal {
+    Absent,
+    Empty,
+    Value(String),
+}
+
+impl PropOriginal {
+    /// 由属性读取结果构造，`None` 表示属性不存在。
+    pub fn from_read(value: Option<String>) -> Self {
+        match value {
+            None => Self::Absent,
+            Some(value) if value.is_empty() => Self::Empty,
+            Some(value) => Self::Value(value),
+        }
+    }
+
+    /// 恢复时应写入的值，`None` 表示应删除该属性。
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Self::Absent => None,
+            Self::Empty => Some(""),
+            Self::Value(value) => Some(value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+struct JournalFile {
+    boot_id: String,
+    /// 属性名 → 真实原值
+    originals: BTreeMap<String, PropOriginal>,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    boot_id: String,
+}
+
+impl Journal {
+    pub fn new(path: impl Into<PathBuf>, boot_id: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            boot_id: boot_id.into(),
+        }
+    }
+
+    /// 使用默认路径与当前开机周期。
+    pub fn system() -> Self {
+        Self::new(JOURNAL_PATH, current_boot_id())
+    }
+
+    /// 以原子替换的方式写入当前原值表；表为空时删除日志。
+    pub fn record(&self, originals: &HashMap<String, PropOriginal>) -> Result<()> {
+        if originals.is_empty() {
+            return self.clear();
+        }
+
+        let file = JournalFile {
+            boot_id: self.boot_id.clone(),
+            originals: originals
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        };
+        let bytes = serde_json::to_vec_pretty(&file)?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut tmp = fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        tmp.write_all(&bytes)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// 读取本开机周期内尚未恢复的原值。失效或损坏的日志会被删除并视为空。
+    pub fn pending(&self) -> Result<HashMap<String, PropOriginal>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()));
+            }
+        };
+
+        match serde_json::from_slice::<JournalFile>(&content) {
+            Ok(file) if file.boot_id == self.boot_id => Ok(file.originals.into_iter().collect()),
+            _ => {
+                self.clear()?;
+                Ok(HashMap::new())
+            }
+        }
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to remove {}", self.path.display())),
+        }
+    }
+}
+
+pub fn current_boot_id() -> String {
+    fs::read_to_string(BOOT_ID_PATH)
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "device_faker_journal_{name}_{}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn journal_roundtrip_and_clear_on_empty() {
+        let path = temp_path("roundtrip");
+        let journal = Journal::new(&path, "boot-a");
+        let originals = HashMap::from([
+            (
+                "ro.product.model".to_string(),
+                PropOriginal::Value("Real".to_string()),
+            ),
+            ("ro.boot.flash.locked".to_string(), PropOriginal::Empty),
+            ("ro.build.selinux".to_string(), PropOriginal::Absent),
+        ]);
+
+        journal.record(&originals).unwrap();
+        assert_eq!(journal.pending().unwrap(), originals);
+
+        journal.record(&HashMap::new()).unwrap();
+        assert!(!path.exists());
+        assert!(journal.pending().unwrap().is_empty());
+    }
+
+    #[test]
+    fn journal_from_previous_boot_is_discarded() {
+        let path = temp_path("stale");
+        let originals = HashMap::from([("ro.product.model".to_string(), PropOriginal::Absent)]);
+        Journal::new(&path, "boot-a").record(&originals).unwrap();
+
+        assert!(Journal::new(&path, "boot-b").pending().unwrap().is_empty());
+        assert!(!path.exists());
+    }
+}
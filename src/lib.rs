@@ -1,25 +1,54 @@
 #[cfg(target_os = "android")]
 mod atexit;
+mod binder_hook;
 mod companion;
 mod config;
+mod control;
 mod cpu_spoof;
+mod detached_mount;
+mod display_spoof;
+mod elf_imports;
+mod feature_spoof;
 #[cfg(target_os = "android")]
 mod file_logger;
+mod gpu_spoof;
 mod hooks;
+mod journal;
+mod memory_spoof;
+mod mount_child;
+mod mount_hiding;
+mod mount_verify;
+mod overlay;
+mod overlay_label;
+mod overlay_store;
+mod process_registry;
+mod prop_isolation;
+mod prop_session;
+mod protocol;
+mod spoof_features;
 mod state;
+mod supervisor;
 
 use std::{fs, path::Path};
 
 use anyhow::Context;
 use companion::{
     handle_companion_request, restore_previous_resetprop_if_needed,
-    spoof_system_props_via_companion,
+    spoof_system_props_via_companion, start_companion,
 };
-use config::{Config, MergedAppConfig};
+use config::{Config, MergedAppConfig, NativePropBackend};
 use cpu_spoof::apply_cpu_spoof;
-use hooks::{hook_build_fields, hook_native_property_get, hook_system_properties};
+use display_spoof::hook_display_info;
+use feature_spoof::hook_system_features;
+use gpu_spoof::hook_gpu_identity;
+use hooks::{
+    hook_build_fields, hook_display_metrics, hook_native_property_get, hook_storage_stats,
+    hook_system_properties, hook_total_memory,
+};
 use jni::{EnvUnowned, errors::ThrowRuntimeExAndDefault};
-use log::{LevelFilter, error, info};
+use log::{LevelFilter, error, info, warn};
+use memory_spoof::hook_memory_info;
+use spoof_features::SpoofFeatures;
 use state::{FAKE_PROPS, IS_FULL_MODE};
 use zygisk_api::{
     ZygiskModule,
@@ -68,6 +97,10 @@ impl ZygiskModule for MyModule {
         _env: EnvUnowned,
         _args: &mut <V4 as ZygiskRaw>::ServerSpecializeArgs,
     ) {
+        // 开机时尽早让 companion 恢复上次遗留的属性，不必等到第一个 app 启动
+        if let Err(e) = start_companion(&mut api) {
+            warn!("Failed to start companion: {e}");
+        }
         api.set_option(ZygiskOption::DlCloseModuleLibrary);
     }
 }
@@ -101,6 +134,7 @@ impl MyModule {
         let package_name = Self::extract_package_name(env, args)?;
         let user_id = Self::extract_android_user_id(args);
         let package_with_user = format!("{package_name}@{user_id}");
+        let uid = u32::try_from(*args.uid).unwrap_or(0);
         restore_previous_resetprop_if_needed(api, &package_with_user)?;
 
         let config = match load_config() {
@@ -145,25 +179,23 @@ impl MyModule {
             }
         }
 
+        let features = SpoofFeatures::for_app(&merged)?;
         if config.debug {
             info!(
-                "Using mode: {} for app: {package_name} (user {user_id})",
+                "Using mode: {} for app: {package_name} (user {user_id}), features: {features:?}",
                 merged.mode
             );
         }
 
-        hook_build_fields(env, &merged)?;
-        if config.debug {
-            info!("Build fields faked successfully");
-        }
-
-        match SpoofMode::from_mode_str(&merged.mode) {
-            SpoofMode::Lite => Self::apply_lite_mode(api, config.debug),
-            SpoofMode::Full => Self::apply_full_mode(api, env, &merged, config.debug),
-            SpoofMode::Companion => {
-                Self::apply_companion_mode(api, &package_with_user, &merged, config.debug)
-            }
-        }
+        Self::apply_features(
+            api,
+            env,
+            &package_with_user,
+            uid,
+            &merged,
+            features,
+            config.debug,
+        )
     }
 
     fn extract_android_user_id(args: &<V4 as ZygiskRaw>::AppSpecializeArgs) -> u32 {
@@ -204,100 +236,186 @@ impl MyModule {
         Ok(result)
     }
 
-    fn apply_lite_mode(api: &mut ZygiskApi<V4>, debug: bool) -> anyhow::Result<()> {
-        FAKE_PROPS.lock().unwrap().clear();
-        IS_FULL_MODE.store(false, std::sync::atomic::Ordering::Relaxed);
-        if debug {
-            info!("Lite mode: only Build fields faked, unloading module");
+    /// 依次执行启用的伪装功能；没有常驻 hook 时最后卸载模块。
+    fn apply_features(
+        api: &mut ZygiskApi<V4>,
+        env: &mut EnvUnowned,
+        package_name: &str,
+        uid: u32,
+        merged: &MergedAppConfig,
+        features: SpoofFeatures,
+        debug: bool,
+    ) -> anyhow::Result<()> {
+        if features.build_fields {
+            hook_build_fields(env, merged)?;
+            if debug {
+                info!("Build fields faked successfully");
+            }
+        }
+
+        if features.resident() {
+            let prop_map = Config::build_merged_property_map(merged);
+            if debug {
+                info!("Property map created with {} entries", prop_map.len());
+            }
+            *FAKE_PROPS.lock().unwrap() = prop_map;
+            IS_FULL_MODE.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        if features.java_prop_hook {
+            Self::apply_java_hooks(api, env, merged, debug)?;
+        }
+
+        if features.native_prop_hook {
+            let backend = features.native_prop_backend;
+            if debug {
+                info!(
+                    "Native property backend {}: {}",
+                    backend.name(),
+                    backend.detectability()
+                );
+            }
+            match backend {
+                NativePropBackend::PltHook => Self::apply_native_hooks(api, merged, debug)?,
+                // 属性由下面挂载的私有属性区副本提供
+                NativePropBackend::PropertyArea => {
+                    if merged.gpu.is_some() {
+                        warn!("GPU identity spoofing needs PLT hooks, skipped with property_area");
+                    }
+                }
+            }
+        }
+
+        if features.resetprop {
+            Self::apply_resetprop(api, package_name, uid, merged, debug)?;
+        }
+
+        if features.property_area_copies() {
+            // 属性只写入本 app 的私有副本，失败即整体失败
+            apply_cpu_spoof(api, merged, package_name, &features, debug)?;
+            if debug {
+                info!("Isolated property spoofing completed");
+            }
+        } else if let Err(err) = apply_cpu_spoof(api, merged, package_name, &features, debug) {
+            error!("Failed to apply CPU spoof: {err:?}");
+        }
+
+        if features.resident() {
+            if debug {
+                info!("Hooks registered, module will stay loaded");
+            }
+        } else {
+            FAKE_PROPS.lock().unwrap().clear();
+            IS_FULL_MODE.store(false, std::sync::atomic::Ordering::Relaxed);
+            if debug {
+                info!("No resident hooks for {package_name}, unloading module");
+            }
+            api.set_option(ZygiskOption::DlCloseModuleLibrary);
         }
-        api.set_option(ZygiskOption::DlCloseModuleLibrary);
         Ok(())
     }
 
-    fn apply_full_mode(
+    /// SystemProperties 以及内存、存储、屏幕、系统特性的 Java hook。
+    fn apply_java_hooks(
         api: &mut ZygiskApi<V4>,
         env: &mut EnvUnowned,
         merged: &MergedAppConfig,
         debug: bool,
     ) -> anyhow::Result<()> {
+        hook_system_properties(api, env)?;
         if debug {
-            info!("Full mode: faking SystemProperties");
+            info!("SystemProperties hooks registered");
         }
 
-        let prop_map = Config::build_merged_property_map(merged);
-        if debug {
-            info!("Property map created with {} entries", prop_map.len());
+        if let Some(display) = &merged.display {
+            hook_display_metrics(env, display)?;
+            hook_display_info(api, env, display)?;
+            if debug {
+                info!("Display info faked");
+            }
         }
 
-        *FAKE_PROPS.lock().unwrap() = prop_map;
-        IS_FULL_MODE.store(true, std::sync::atomic::Ordering::Relaxed);
-        hook_system_properties(api, env)?;
-        hook_native_property_get(api)?;
+        if let Some(ram_total_mb) = merged.ram_total_mb {
+            hook_total_memory(api, env, ram_total_mb)?;
+            hook_memory_info(api, env, ram_total_mb)?;
+            if debug {
+                info!("Total memory faked as {ram_total_mb} MB");
+            }
+        }
+
+        if let Some(storage_total_gb) = merged.storage_total_gb {
+            hook_storage_stats(api, env, storage_total_gb)?;
+            if debug {
+                info!("Storage capacity faked as {storage_total_gb} GB");
+            }
+        }
 
+        if merged.add_features.is_some() || merged.remove_features.is_some() {
+            hook_system_features(
+                api,
+                env,
+                merged.add_features.as_deref().unwrap_or_default(),
+                merged.remove_features.as_deref().unwrap_or_default(),
+            )?;
+            if debug {
+                info!("System feature hooks registered");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// native 属性读取与 GPU 标识的 PLT hook。
+    fn apply_native_hooks(
+        api: &mut ZygiskApi<V4>,
+        merged: &MergedAppConfig,
+        debug: bool,
+    ) -> anyhow::Result<()> {
+        hook_native_property_get(api)?;
         if debug {
-            info!("SystemProperties faked successfully, module will stay loaded");
+            info!("Native property hooks registered");
+        }
+
+        if let Some(gpu) = &merged.gpu {
+            hook_gpu_identity(api, gpu)?;
+            if debug {
+                info!("GPU identity hooks registered");
+            }
         }
 
         Ok(())
     }
 
-    fn apply_companion_mode(
+    /// 通过 companion 的 resetprop 修改全局属性。
+    fn apply_resetprop(
         api: &mut ZygiskApi<V4>,
         package_name: &str,
+        uid: u32,
         merged: &MergedAppConfig,
         debug: bool,
     ) -> anyhow::Result<()> {
         if debug {
-            info!("Companion mode: using companion process");
+            info!("Spoofing properties via companion resetprop");
         }
 
         let prop_map = Config::build_merged_property_map_for_resetprop(merged);
         let delete_props = Config::build_delete_props_list(merged);
-        spoof_system_props_via_companion(api, &prop_map, &delete_props, package_name)?;
+        spoof_system_props_via_companion(
+            api,
+            &prop_map,
+            &delete_props,
+            package_name,
+            uid,
+            merged.restore_policy,
+        )?;
 
         if debug {
             info!("Companion property spoofing completed");
         }
-
-        if let Err(err) = apply_cpu_spoof(api, merged, package_name, debug) {
-            error!("Failed to apply CPU spoof: {err:?}");
-        } else if debug
-            && merged
-                .cpuinfo_content
-                .as_ref()
-                .is_some_and(|c| !c.is_empty())
-        {
-            info!("CPU spoof applied for {package_name}");
-        }
-
-        FAKE_PROPS.lock().unwrap().clear();
-        IS_FULL_MODE.store(false, std::sync::atomic::Ordering::Relaxed);
-        api.set_option(ZygiskOption::DlCloseModuleLibrary);
         Ok(())
     }
 }
 
-#[derive(Clone, Copy)]
-enum SpoofMode {
-    Lite,
-    Full,
-    Companion,
-}
-
-impl SpoofMode {
-    fn from_mode_str(value: &str) -> Self {
-        match value {
-            "lite" => Self::Lite,
-            "full" => Self::Full,
-            "companion" => Self::Companion,
-            other => {
-                error!("Mode '{other}' not fully supported, falling back to 'lite' mode");
-                Self::Lite
-            }
-        }
-    }
-}
-
 fn load_config() -> anyhow::Result<Option<Config>> {
     if !Path::new(CONFIG_PATH).exists() {
         return Ok(None);
@@ -325,14 +443,7 @@ fn flush_log_buffer_to_companion(api: &mut ZygiskApi<V4>) -> anyhow::Result<()>
     }
 
     let request = companion::CompanionRequest::WriteLog(companion::WriteLogRequest { lines });
-    let response = companion::send_companion_command(api, &request)?;
-    if response.status != 0 {
-        anyhow::bail!(
-            response
-                .message
-                .unwrap_or_else(|| "companion write log failed".to_string())
-        );
-    }
+    companion::send_companion_command(api, &request)?;
     Ok(())
 }
 
@@ -0,0 +1,175 @@
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use jni::{
+    Env, EnvUnowned, Outcome, jni_sig, jni_str,
+    objects::{JObject, JValue},
+};
+use zygisk_api::api::{V4, ZygiskApi};
+
+use crate::binder_hook::{self, JniParcel, Parcel};
+
+// ActivityManager.getMemoryInfo 经 IActivityManager.getMemoryInfo 由 system_server 填充
+// out 参数 MemoryInfo，在 BinderProxy.transactNative 中改写这个回复。
+
+const ACTIVITY_MANAGER_DESCRIPTOR: &str = "android.app.IActivityManager";
+
+/// specialize 前确定、之后只读的内存伪装配置。
+static MEMORY_SPOOF: OnceLock<MemorySpoof> = OnceLock::new();
+
+struct MemorySpoof {
+    /// `IActivityManager.Stub.TRANSACTION_getMemoryInfo`，各 Android 版本不同，运行时读取。
+    code: i32,
+    total_bytes: i64,
+}
+
+/// Hook BinderProxy.transactNative 以伪装 `ActivityManager.MemoryInfo`（仅 full 模式）。
+pub fn hook_memory_info(
+    api: &mut ZygiskApi<V4>,
+    env: &mut EnvUnowned,
+    ram_total_mb: u64,
+) -> anyhow::Result<()> {
+    // 在 specialize 之前读取隐藏字段，此时 hidden API 限制尚未生效。
+    let outcome = env
+        .with_env(|jenv| -> Result<i32, jni::errors::Error> {
+            let code = jenv
+                .find_class(jni_str!("android/app/IActivityManager$Stub"))
+                .and_then(|stub_class| {
+                    jenv.get_static_field(
+                        &stub_class,
+                        jni_str!("TRANSACTION_getMemoryInfo"),
+                        jni_sig!("I"),
+                    )
+                })
+                .and_then(|v| v.i());
+            if code.is_err() {
+                jenv.exception_clear();
+            }
+            code
+        })
+        .into_outcome();
+    let code = match outcome {
+        Outcome::Ok(code) => code,
+        Outcome::Err(err) => {
+            return Err(err)
+                .context("Failed to read IActivityManager.Stub.TRANSACTION_getMemoryInfo");
+        }
+        Outcome::Panic(_) => {
+            anyhow::bail!("Panicked while reading IActivityManager transaction code")
+        }
+    };
+    // AIDL 事务号从 FIRST_CALL_TRANSACTION (1) 开始
+    if code <= 0 {
+        anyhow::bail!("Invalid IActivityManager.Stub.TRANSACTION_getMemoryInfo: {code}");
+    }
+    let total_bytes = i64::try_from(ram_total_mb.saturating_mul(1024 * 1024)).unwrap_or(i64::MAX);
+    if MEMORY_SPOOF.set(MemorySpoof { code, total_bytes }).is_err() {
+        anyhow::bail!("Memory info spoof already installed");
+    }
+
+    binder_hook::install(api, env)
+}
+
+/// 事务号是否可能是 getMemoryInfo，供 hook 在接触 Parcel 之前快速过滤。
+pub(crate) fn wants_transaction(code: i32) -> bool {
+    MEMORY_SPOOF.get().is_some_and(|spoof| spoof.code == code)
+}
+
+/// 确认请求是 IActivityManager.getMemoryInfo 后改写回复中的 MemoryInfo。
+pub(crate) fn patch_transaction(
+    env: &mut Env,
+    code: i32,
+    data: &JObject,
+    reply: &JObject,
+    header_ints: i32,
+) -> Result<(), jni::errors::Error> {
+    let Some(spoof) = MEMORY_SPOOF.get().filter(|spoof| spoof.code == code) else {
+        return Ok(());
+    };
+    let mut data_parcel = JniParcel { env, parcel: data };
+    let data_position = data_parcel.position()?;
+    let descriptor = binder_hook::read_interface(&mut data_parcel, header_ints)?;
+    data_parcel.set_position(data_position)?;
+    if descriptor.as_deref() != Some(ACTIVITY_MANAGER_DESCRIPTOR) {
+        return Ok(());
+    }
+
+    binder_hook::rewrite_reply_parcelable(
+        env,
+        reply,
+        |env| {
+            let info_class = env.find_class(jni_str!("android/app/ActivityManager$MemoryInfo"))?;
+            let info = env.new_object(&info_class, jni_sig!("()V"), &[])?;
+            env.call_method(
+                &info,
+                jni_str!("readFromParcel"),
+                jni_sig!("(Landroid/os/Parcel;)V"),
+                &[JValue::Object(reply)],
+            )?;
+            Ok(info)
+        },
+        |env, info| patch_memory_info(env, info, spoof.total_bytes),
+    )
+}
+
+fn patch_memory_info(
+    env: &mut Env,
+    info: &JObject,
+    total_bytes: i64,
+) -> Result<(), jni::errors::Error> {
+    let real_total = env
+        .get_field(info, jni_str!("totalMem"), jni_sig!("J"))?
+        .j()?;
+    let avail = env
+        .get_field(info, jni_str!("availMem"), jni_sig!("J"))?
+        .j()?;
+    let threshold = env
+        .get_field(info, jni_str!("threshold"), jni_sig!("J"))?
+        .j()?;
+
+    for (name, value) in [
+        (jni_str!("totalMem"), total_bytes),
+        (jni_str!("availMem"), scale(avail, real_total, total_bytes)),
+        (
+            jni_str!("threshold"),
+            scale(threshold, real_total, total_bytes),
+        ),
+    ] {
+        env.set_field(info, name, jni_sig!("J"), JValue::Long(value))?;
+    }
+    // advertisedMem 只在 Android 14+ 存在，表示机型标称内存
+    if env
+        .set_field(
+            info,
+            jni_str!("advertisedMem"),
+            jni_sig!("J"),
+            JValue::Long(total_bytes),
+        )
+        .is_err()
+    {
+        env.exception_clear();
+    }
+    Ok(())
+}
+
+/// 按总内存的比例缩放 `value`，保持可用内存、低内存阈值与总内存的比例不变。
+fn scale(value: i64, real_total: i64, fake_total: i64) -> i64 {
+    if real_total <= 0 {
+        return value;
+    }
+    (value.max(0) as i128 * fake_total as i128 / real_total as i128).min(fake_total as i128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_memory_keeps_real_ratio() {
+        let gib = 1024 * 1024 * 1024;
+
+        assert_eq!(scale(3 * gib, 8 * gib, 16 * gib), 6 * gib);
+        assert_eq!(scale(9 * gib, 8 * gib, 16 * gib), 16 * gib);
+        assert_eq!(scale(gib, 0, 16 * gib), gib);
+    }
+}
@@ -0,0 +1,365 @@
+// mount 子进程：fork 后 setns 进入 app 的 mount namespace 完成 bind mount。
+//
+// companion 是多线程进程，fork 时其他线程可能正持有日志锁或分配器的锁，这些锁在
+// 子进程中永远不会被释放。因此所有路径在 fork 前准备成 `CString`，子进程只调用原始
+// 系统调用，不分配内存、不写日志、不格式化 `io::Error`。
+//
+// 子进程的日志以定长记录写回 result pipe，由父进程输出。子进程挂载后立即退出，
+// 之后 app 的 namespace 切换由 supervisor 跟踪。
+//
+// 记录格式：1 字节类型 + 1 字节消息长度 + 4 字节 errno（本机字节序）+ UTF-8 消息。
+// 单条记录不超过 PIPE_BUF，write 是原子的。
+
+use std::{
+    ffi::{CStr, CString},
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, Read},
+    os::fd::{FromRawFd, RawFd},
+};
+
+use anyhow::Result;
+use log::{info, warn};
+
+const RECORD_INFO: u8 = 0;
+const RECORD_WARN: u8 = 1;
+/// 全部挂载成功，父进程可以返回
+const RECORD_MOUNTED: u8 = 2;
+/// 挂载失败，子进程随后退出
+const RECORD_FAILED: u8 = 3;
+
+const HEADER_LEN: usize = 6;
+const MAX_MESSAGE_LEN: usize = 240;
+
+/// fork 前准备好的挂载计划，子进程只读取其中的字段。
+pub struct MountPlan {
+    pid: u32,
+    ns_path: CString,
+    mounts: Vec<PreparedMount>,
+}
+
+struct PreparedMount {
+    source: CString,
+    target: CString,
+}
+
+impl MountPlan {
+    /// `mounts` 为 (源文件, 目标路径)。
+    pub fn new<'a>(pid: u32, mounts: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let mounts = mounts
+            .into_iter()
+            .map(|(source, target)| {
+                Ok(PreparedMount {
+                    source: CString::new(source)?,
+                    target: CString::new(target)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            pid,
+            ns_path: CString::new(format!("/proc/{pid}/ns/mnt"))?,
+            mounts,
+        })
+    }
+}
+
+/// fork 子进程执行 `plan`，等待挂载结果并回收子进程。
+pub fn fork_mount_child(plan: &MountPlan) -> Result<()> {
+    let mut pipe_fds = [0i32; 2];
+    if unsafe { libc::pipe2(pipe_fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        anyhow::bail!("pipe failed: {}", io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = pipe_fds;
+
+    match unsafe { libc::fork() } {
+        -1 => {
+            let err = io::Error::last_os_error();
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+            }
+            anyhow::bail!("fork failed: {err}");
+        }
+        0 => {
+            // === 子进程：此后只允许原始系统调用 ===
+            unsafe { libc::close(read_fd) };
+            run_child(plan, &ChildLog { fd: write_fd })
+        }
+        child_pid => {
+            unsafe { libc::close(write_fd) };
+            let log = unsafe { File::from_raw_fd(read_fd) };
+            // 子进程写完结果即退出。不能读到 EOF 为止：并发 fork 的其他子进程
+            // 可能继承了写端
+            let result = wait_for_result(child_pid as u32, &log);
+            let mut status = 0i32;
+            unsafe { libc::waitpid(child_pid, &mut status, 0) };
+            result
+        }
+    }
+}
+
+/// 读取 /proc/{pid}/ns/mnt 指向的 namespace inode。
+pub fn read_ns_ino(pid: u32) -> Option<u64> {
+    let path = CString::new(format!("/proc/{pid}/ns/mnt")).ok()?;
+    ns_ino_at(&path)
+}
+
+// ---------------------------------------------------------------------------
+// 父进程侧
+// ---------------------------------------------------------------------------
+
+struct Record {
+    kind: u8,
+    errno: i32,
+    message: String,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if self.errno != 0 {
+            write!(f, ": {}", io::Error::from_raw_os_error(self.errno))?;
+        }
+        Ok(())
+    }
+}
+
+fn read_record(reader: &mut impl Read) -> io::Result<Option<Record>> {
+    let mut header = [0u8; HEADER_LEN];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut message = vec![0u8; header[1] as usize];
+    reader.read_exact(&mut message)?;
+    Ok(Some(Record {
+        kind: header[0],
+        errno: i32::from_ne_bytes([header[2], header[3], header[4], header[5]]),
+        message: String::from_utf8_lossy(&message).into_owned(),
+    }))
+}
+
+fn log_record(child: u32, record: &Record) {
+    match record.kind {
+        RECORD_INFO => info!("[child {child}] {record}"),
+        _ => warn!("[child {child}] {record}"),
+    }
+}
+
+/// 读取日志直到挂载结果。
+fn wait_for_result(child: u32, mut log: &File) -> Result<()> {
+    loop {
+        match read_record(&mut log)? {
+            None => anyhow::bail!("mount child exited without reporting a result"),
+            Some(record) if record.kind == RECORD_MOUNTED => return Ok(()),
+            Some(record) if record.kind == RECORD_FAILED => {
+                anyhow::bail!("Mount child failed: {record}")
+            }
+            Some(record) => log_record(child, &record),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 子进程侧：以下函数不分配内存
+// ---------------------------------------------------------------------------
+
+/// 写往 result pipe 的日志，消息格式化到栈上缓冲区，超长部分截断。
+struct ChildLog {
+    fd: RawFd,
+}
+
+struct RecordBuf {
+    buf: [u8; HEADER_LEN + MAX_MESSAGE_LEN],
+    len: usize,
+}
+
+impl fmt::Write for RecordBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+impl ChildLog {
+    fn send(&self, kind: u8, errno: i32, args: fmt::Arguments<'_>) {
+        let mut record = RecordBuf {
+            buf: [0u8; HEADER_LEN + MAX_MESSAGE_LEN],
+            len: HEADER_LEN,
+        };
+        let _ = record.write_fmt(args);
+        record.buf[0] = kind;
+        record.buf[1] = (record.len - HEADER_LEN) as u8;
+        record.buf[2..HEADER_LEN].copy_from_slice(&errno.to_ne_bytes());
+        unsafe { libc::write(self.fd, record.buf.as_ptr().cast(), record.len) };
+    }
+
+    fn info(&self, args: fmt::Arguments<'_>) {
+        self.send(RECORD_INFO, 0, args);
+    }
+
+    fn warn(&self, errno: i32, args: fmt::Arguments<'_>) {
+        self.send(RECORD_WARN, errno, args);
+    }
+}
+
+fn run_child(plan: &MountPlan, log: &ChildLog) -> ! {
+    let code = if mount_all(plan, log) {
+        log.send(RECORD_MOUNTED, 0, format_args!("mounted"));
+        0
+    } else {
+        1
+    };
+    unsafe {
+        libc::close(log.fd);
+        libc::_exit(code)
+    }
+}
+
+fn errno() -> i32 {
+    io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// setns 到 app 当前的 namespace 并挂载全部文件，失败时已写入 `RECORD_FAILED`。
+fn mount_all(plan: &MountPlan, log: &ChildLog) -> bool {
+    let pid = plan.pid;
+    let Some(ino) = ns_ino_at(&plan.ns_path) else {
+        let args = format_args!("cannot read mount namespace of pid {pid}");
+        log.send(RECORD_FAILED, errno(), args);
+        return false;
+    };
+    if let Err(err) = enter_namespace(&plan.ns_path) {
+        log.send(
+            RECORD_FAILED,
+            err,
+            format_args!("setns failed for pid {pid}"),
+        );
+        return false;
+    }
+    log.info(format_args!("entered NS of pid {pid} (ino={ino})"));
+
+    for mount in &plan.mounts {
+        let target = display(&mount.target);
+        if let Err(err) = bind_mount(mount) {
+            log.send(
+                RECORD_FAILED,
+                err,
+                format_args!("bind mount onto {target} failed"),
+            );
+            return false;
+        }
+        match read_len(&mount.target) {
+            Ok(0) => log.warn(0, format_args!("{target} empty after mount")),
+            Ok(len) => log.info(format_args!("mounted {target} ({len} bytes, ns_ino={ino})")),
+            Err(err) => log.warn(err, format_args!("read of {target} failed after mount")),
+        }
+    }
+    true
+}
+
+fn enter_namespace(ns_path: &CStr) -> Result<(), i32> {
+    let ns_fd = unsafe { libc::open(ns_path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) };
+    if ns_fd < 0 {
+        return Err(errno());
+    }
+    let ret = unsafe { libc::setns(ns_fd, libc::CLONE_NEWNS) };
+    let err = errno();
+    unsafe { libc::close(ns_fd) };
+    if ret != 0 { Err(err) } else { Ok(()) }
+}
+
+/// 防御性卸载后 bind mount。
+fn bind_mount(mount: &PreparedMount) -> Result<(), i32> {
+    unsafe { libc::umount2(mount.target.as_ptr(), libc::MNT_DETACH) };
+    let ret = unsafe {
+        libc::mount(
+            mount.source.as_ptr(),
+            mount.target.as_ptr(),
+            std::ptr::null(),
+            libc::MS_BIND,
+            std::ptr::null(),
+        )
+    };
+    if ret != 0 { Err(errno()) } else { Ok(()) }
+}
+
+/// 读取整个文件，返回字节数。
+fn read_len(path: &CStr) -> Result<usize, i32> {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(errno());
+    }
+    let mut buf = [0u8; 512];
+    let mut total = 0;
+    let result = loop {
+        match unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) } {
+            0 => break Ok(total),
+            n if n > 0 => total += n as usize,
+            _ => break Err(errno()),
+        }
+    };
+    unsafe { libc::close(fd) };
+    result
+}
+
+fn display(path: &CStr) -> &str {
+    path.to_str().unwrap_or("<non-utf8 path>")
+}
+
+/// readlink /proc/{pid}/ns/mnt 获取 namespace inode。
+/// stat() 返回的是 procfs 条目的 inode（固定不变），必须用 readlink 获取真正的 namespace ID。
+fn ns_ino_at(ns_path: &CStr) -> Option<u64> {
+    let mut buf = [0u8; 64];
+    let len = unsafe { libc::readlink(ns_path.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+    if len < 0 {
+        return None;
+    }
+    parse_ns_link(&buf[..len as usize])
+}
+
+/// 解析 "mnt:[4026535831]"。
+fn parse_ns_link(link: &[u8]) -> Option<u64> {
+    std::str::from_utf8(link)
+        .ok()?
+        .strip_prefix("mnt:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_namespace_link() {
+        assert_eq!(parse_ns_link(b"mnt:[4026531841]"), Some(4026531841));
+        assert_eq!(parse_ns_link(b"net:[4026531841]"), None);
+        assert_eq!(parse_ns_link(b"mnt:[abc]"), None);
+    }
+
+    #[test]
+    fn child_records_round_trip_through_pipe() {
+        let mut fds = [0i32; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let log = ChildLog { fd: fds[1] };
+        log.info(format_args!("entered NS of pid {} (ino={})", 42, 7u64));
+        log.warn(libc::EACCES, format_args!("{}", "x".repeat(400)));
+        unsafe { libc::close(fds[1]) };
+
+        let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+        let first = read_record(&mut reader).unwrap().unwrap();
+        assert_eq!(first.kind, RECORD_INFO);
+        assert_eq!(first.to_string(), "entered NS of pid 42 (ino=7)");
+
+        let second = read_record(&mut reader).unwrap().unwrap();
+        assert_eq!(second.kind, RECORD_WARN);
+        assert_eq!(second.errno, libc::EACCES);
+        assert_eq!(second.message.len(), MAX_MESSAGE_LEN);
+
+        assert!(read_record(&mut reader).unwrap().is_none());
+    }
+}
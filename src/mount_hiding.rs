@@ -0,0 +1,516 @@
+// 隐藏覆盖文件挂载的特征。
+//
+// 检测器扫描 /proc/self/mountinfo 时，挂在 /proc/cpuinfo 上、源路径位于 /data/adb
+// 的 bind mount 本身就是很强的信号（第 4 列会原样显示 `/adb/device_faker/...`）。
+// 因此源文件改为放在独立的 tmpfs 实例中，每个实例只放一个以目标文件名命名的文件，
+// 挂载后 mountinfo 中的源路径为 `/cpuinfo` 这类与目标一致的名字，每挂载选项
+// （nosuid,nodev,noexec）与 procfs/sysfs 相同。
+//
+// 仍然可见的特征：文件系统类型是 tmpfs（statfs 返回 TMPFS_MAGIC），超级块与
+// procfs/sysfs 不同（st_dev 不同），目标路径上多出一个挂载项，mount id 可能倒序
+// （由内核按最小可用值分配）。这些无法靠源文件的位置消除，由 `telltales` 报告。
+//
+// tmpfs 实例挂在 companion 私有的暂存 mount namespace 中：该 namespace 整体设为
+// private，挂载不会传播到 companion 或 app 的 namespace。open_tree 只能克隆调用者
+// 所在 namespace 中的挂载，因此 `detached_mount` 的挂载线程先进入暂存 namespace
+// 克隆源文件，再进入 app 的 namespace。其他线程经 `/proc/self/fd/<fd>/<name>`
+// 读写实例中的文件。
+//
+// fork 出的 mount 子进程无法从暂存 namespace 克隆挂载，内核不支持 open_tree 时
+// 退回状态目录下的普通文件。属性区副本同样放在 tmpfs 实例中，由继承了实例 fd 的
+// 子进程改写。
+
+use std::{
+    ffi::{CStr, CString},
+    fs::{self, File},
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::LazyLock,
+    thread,
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::cpu_spoof::{CPU_SPOOF_STATE_DIR, OverlayMount};
+
+const OPEN_TREE_CLONE: libc::c_uint = 0x1;
+/// 与 procfs/sysfs 的每挂载选项一致：rw,nosuid,nodev,noexec,relatime。
+/// 文件系统类型与超级块选项仍是 tmpfs 的
+const MOUNT_FLAGS: libc::c_ulong = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+/// 经 fd 访问暂存文件的路径前缀
+const STAGED_PREFIX: &str = "/proc/self/fd/";
+
+/// 暂存 namespace，创建失败或内核不支持 open_tree 时为 `None`。
+static STAGING_NS: LazyLock<Option<OwnedFd>> = LazyLock::new(|| match create_staging_ns() {
+    Ok(ns) => ns,
+    Err(e) => {
+        warn!("staging namespace unavailable ({e:#}), using plain overlay sources");
+        None
+    }
+});
+
+/// 暂存 namespace 中 tmpfs 实例的挂载点所在目录。
+/// 在 companion 的 namespace 中只是一个空目录。
+fn staging_dir() -> String {
+    format!("{CPU_SPOOF_STATE_DIR}/.staging")
+}
+
+fn create_staging_ns() -> Result<Option<OwnedFd>> {
+    let dir = staging_dir();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {dir}"))?;
+    let dir = CString::new(dir)?;
+
+    spawn_scoped("df-staging", || {
+        if unsafe { libc::unshare(libc::CLONE_FS | libc::CLONE_NEWNS) } != 0 {
+            anyhow::bail!(
+                "unshare(CLONE_NEWNS) failed: {}",
+                io::Error::last_os_error()
+            );
+        }
+        // 先把整个树设为私有，之后的挂载不会传播回 companion 所在的 namespace
+        sys_mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+        sys_mount(Some(c"tmpfs"), &dir, Some(c"tmpfs"), MOUNT_FLAGS)?;
+
+        let probe = unsafe {
+            libc::syscall(
+                libc::SYS_open_tree,
+                libc::AT_FDCWD,
+                dir.as_ptr(),
+                OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint,
+            )
+        };
+        if probe < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOSYS) {
+                info!("Kernel lacks open_tree, using plain overlay sources");
+                return Ok(None);
+            }
+            anyhow::bail!("open_tree probe failed: {err}");
+        }
+        drop(unsafe { OwnedFd::from_raw_fd(probe as i32) });
+
+        let ns =
+            File::open("/proc/thread-self/ns/mnt").context("Failed to open staging namespace")?;
+        Ok(Some(OwnedFd::from(ns)))
+    })
+}
+
+/// 让当前线程进入暂存 namespace，调用前须已 unshare(CLONE_FS)。没有暂存 namespace 时不做任何事。
+pub(crate) fn enter_staging_ns() -> Result<()> {
+    if let Some(ns) = &*STAGING_NS
+        && unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNS) } != 0
+    {
+        anyhow::bail!(
+            "setns into staging namespace failed: {}",
+            io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+/// 源文件是否位于暂存 tmpfs 中。
+pub(crate) fn is_staged(source: &str) -> bool {
+    source.starts_with(STAGED_PREFIX)
+}
+
+/// 暂存 namespace 中的一个 tmpfs 实例，drop 时卸载。
+/// 已挂到 app namespace 的克隆不受影响，随 app 的 namespace 一起销毁。
+pub(crate) struct StagedSource {
+    dir: OwnedFd,
+    key: String,
+}
+
+impl StagedSource {
+    /// 在暂存 namespace 中挂载名为 `key` 的 tmpfs 实例，没有暂存 namespace 时返回 `None`。
+    pub(crate) fn create(key: &str) -> Result<Option<Self>> {
+        if STAGING_NS.is_none() {
+            return Ok(None);
+        }
+        let mountpoint = CString::new(format!("{}/{key}", staging_dir()))?;
+        let dir = in_staging_ns(|| {
+            if unsafe { libc::mkdir(mountpoint.as_ptr(), 0o755) } != 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::AlreadyExists {
+                    anyhow::bail!("mkdir {key} failed: {err}");
+                }
+            }
+            sys_mount(Some(c"tmpfs"), &mountpoint, Some(c"tmpfs"), MOUNT_FLAGS)?;
+            let fd = unsafe {
+                libc::open(
+                    mountpoint.as_ptr(),
+                    libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                anyhow::bail!("open {key} failed: {}", io::Error::last_os_error());
+            }
+            Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+        })?;
+        Ok(Some(Self {
+            dir,
+            key: key.to_string(),
+        }))
+    }
+
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 实例中 `name` 的路径，在 companion 的任意线程（及其子进程）中有效。
+    pub(crate) fn path(&self, name: &str) -> String {
+        format!("{STAGED_PREFIX}{}/{name}", self.dir.as_raw_fd())
+    }
+}
+
+impl Drop for StagedSource {
+    fn drop(&mut self) {
+        let Ok(mountpoint) = CString::new(format!("{}/{}", staging_dir(), self.key)) else {
+            return;
+        };
+        let result = in_staging_ns(|| {
+            unsafe {
+                libc::umount2(mountpoint.as_ptr(), libc::MNT_DETACH);
+                libc::rmdir(mountpoint.as_ptr());
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("Failed to unmount staged source {}: {e:#}", self.key);
+        }
+    }
+}
+
+/// 在进入暂存 namespace 的临时线程中执行 `f`。
+fn in_staging_ns<T: Send>(f: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    spawn_scoped("df-staging", || {
+        if unsafe { libc::unshare(libc::CLONE_FS) } != 0 {
+            anyhow::bail!("unshare(CLONE_FS) failed: {}", io::Error::last_os_error());
+        }
+        enter_staging_ns()?;
+        f()
+    })
+}
+
+/// 切换 namespace 只影响线程自身，线程用完即弃。
+fn spawn_scoped<T: Send>(name: &str, f: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn_scoped(scope, f)
+            .context("Failed to spawn staging thread")?
+            .join()
+            .map_err(|_| anyhow::anyhow!("staging thread panicked"))?
+    })
+}
+
+fn sys_mount(
+    source: Option<&CStr>,
+    target: &CStr,
+    fstype: Option<&CStr>,
+    flags: libc::c_ulong,
+) -> Result<()> {
+    let ptr = |s: Option<&CStr>| s.map_or(std::ptr::null(), |s| s.as_ptr());
+    // 新建 tmpfs 时使用与常见 tmpfs 相同的根目录权限
+    let data = fstype.map_or(std::ptr::null(), |_| c"mode=0755".as_ptr());
+    let ret = unsafe {
+        libc::mount(
+            ptr(source),
+            target.as_ptr(),
+            ptr(fstype),
+            flags,
+            data.cast(),
+        )
+    };
+    if ret != 0 {
+        anyhow::bail!(
+            "mount {} failed: {}",
+            target.to_string_lossy(),
+            io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+/// 源文件在 tmpfs 中使用的文件名：与挂载目标的文件名相同。
+pub(crate) fn staged_name(target: &str) -> &str {
+    target.rsplit('/').next().unwrap_or(target)
+}
+
+/// mountinfo 中暴露覆盖文件挂载的特征。
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Telltale {
+    /// 源路径位于模块或 shell 目录下
+    SuspiciousRoot { target: String, root: String },
+    /// 源文件名与目标文件名不同
+    NameMismatch { target: String, root: String },
+    /// 文件系统类型与目标所在的文件系统不同，statfs 的 f_type 随之不同
+    /// （如 TMPFS_MAGIC 而非 PROC_SUPER_MAGIC）
+    FsTypeMismatch {
+        target: String,
+        fstype: String,
+        parent: String,
+    },
+    /// 超级块（st_dev）与目标所在的文件系统不同
+    DeviceMismatch {
+        target: String,
+        device: String,
+        parent: String,
+    },
+    /// 同一目标上叠了多层挂载
+    Stacked { target: String, count: usize },
+    /// 挂载 id 比 mountinfo 中前一项小
+    IdOutOfOrder {
+        target: String,
+        id: u32,
+        previous: u32,
+    },
+}
+
+impl std::fmt::Display for Telltale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SuspiciousRoot { target, root } => {
+                write!(f, "{target}: mounted from suspicious path {root}")
+            }
+            Self::NameMismatch { target, root } => {
+                write!(f, "{target}: source name {root} differs from target")
+            }
+            Self::FsTypeMismatch {
+                target,
+                fstype,
+                parent,
+            } => write!(f, "{target}: filesystem {fstype} differs from {parent}"),
+            Self::DeviceMismatch {
+                target,
+                device,
+                parent,
+            } => write!(f, "{target}: device {device} differs from {parent}"),
+            Self::Stacked { target, count } => write!(f, "{target}: {count} stacked mounts"),
+            Self::IdOutOfOrder {
+                target,
+                id,
+                previous,
+            } => write!(f, "{target}: mount id {id} follows {previous}"),
+        }
+    }
+}
+
+/// 源路径中不应出现的片段
+const SUSPICIOUS_ROOTS: &[&str] = &["/adb/", "device_faker", "/local/tmp"];
+
+/// mountinfo 中的一行。
+struct MountEntry<'a> {
+    id: u32,
+    device: &'a str,
+    root: &'a str,
+    mount_point: &'a str,
+    fstype: &'a str,
+}
+
+fn parse_mountinfo(mountinfo: &str) -> Vec<MountEntry<'_>> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // 第 7 列起是数量不定的可选字段，以单独的 `-` 结束，其后是文件系统类型
+            let (fields, fs) = line.split_once(" - ")?;
+            let fields: Vec<&str> = fields.split(' ').collect();
+            Some(MountEntry {
+                id: fields.first()?.parse().ok()?,
+                device: fields.get(2)?,
+                root: fields.get(3)?,
+                mount_point: fields.get(4)?,
+                fstype: fs.split(' ').next()?,
+            })
+        })
+        .collect()
+}
+
+/// 检查 mountinfo 中挂在 `mounts` 目标上的条目。
+///
+/// 覆盖文件与目标所在文件系统的类型、超级块必然不同，staged 挂载也会报告这两项；
+/// 挂载点本身是一个普通文件这一点无法从 mountinfo 中消除，不单独报告。
+pub(crate) fn telltales(mountinfo: &str, mounts: &[OverlayMount]) -> Vec<Telltale> {
+    let entries = parse_mountinfo(mountinfo);
+    let is_overlay = |entry: &MountEntry| mounts.iter().any(|m| m.target == entry.mount_point);
+    let mut found = Vec::new();
+    let mut previous: Option<u32> = None;
+    for entry in &entries {
+        let prev = previous.replace(entry.id);
+        if !is_overlay(entry) {
+            continue;
+        }
+        let target = entry.mount_point.to_string();
+        if SUSPICIOUS_ROOTS
+            .iter()
+            .any(|part| entry.root.contains(part))
+        {
+            found.push(Telltale::SuspiciousRoot {
+                target: target.clone(),
+                root: entry.root.to_string(),
+            });
+        } else if staged_name(entry.root) != staged_name(&target) {
+            found.push(Telltale::NameMismatch {
+                target: target.clone(),
+                root: entry.root.to_string(),
+            });
+        }
+        // 目标所在的文件系统：挂载点是目标上级目录的最深一层（同一挂载点取最上层）
+        let parent = entries
+            .iter()
+            .filter(|other| !is_overlay(other) && contains_path(other.mount_point, &target))
+            .max_by_key(|other| other.mount_point.len());
+        if let Some(parent) = parent {
+            if entry.fstype != parent.fstype {
+                found.push(Telltale::FsTypeMismatch {
+                    target: target.clone(),
+                    fstype: entry.fstype.to_string(),
+                    parent: parent.fstype.to_string(),
+                });
+            }
+            if entry.device != parent.device {
+                found.push(Telltale::DeviceMismatch {
+                    target: target.clone(),
+                    device: entry.device.to_string(),
+                    parent: parent.device.to_string(),
+                });
+            }
+        }
+        if let Some(previous) = prev.filter(|&previous| previous > entry.id) {
+            found.push(Telltale::IdOutOfOrder {
+                target,
+                id: entry.id,
+                previous,
+            });
+        }
+    }
+    for mount in mounts {
+        let count = entries
+            .iter()
+            .filter(|entry| entry.mount_point == mount.target)
+            .count();
+        if count > 1 {
+            found.push(Telltale::Stacked {
+                target: mount.target.clone(),
+                count,
+            });
+        }
+    }
+    found
+}
+
+/// `path` 是否位于挂载点 `dir` 之下。
+fn contains_path(dir: &str, path: &str) -> bool {
+    dir == "/"
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// 挂载前按目标路径排序，使每次挂载的顺序固定、同一批挂载在 mountinfo 中相邻。
+/// 只决定顺序，不影响 mount id。
+pub(crate) fn sort_for_mount(mounts: &mut [OverlayMount]) {
+    mounts.sort_by(|a, b| a.target.cmp(&b.target));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlay(source: &str, target: &str) -> OverlayMount {
+        OverlayMount {
+            source: source.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    /// 按挂载顺序生成覆盖文件在 app mountinfo 中的条目
+    fn render(first_id: u32, roots: &[(&str, &str)]) -> String {
+        roots
+            .iter()
+            .enumerate()
+            .map(|(i, (root, target))| {
+                format!(
+                    "{} 790 0:{} {root} {target} rw,nosuid,nodev,noexec,relatime - tmpfs tmpfs rw,mode=755",
+                    first_id + i as u32,
+                    60 + i
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn staged_mounts_only_reveal_their_filesystem() {
+        let mut mounts = vec![
+            overlay("/proc/self/fd/9/meminfo", "/proc/meminfo"),
+            overlay("/proc/self/fd/7/cpuinfo", "/proc/cpuinfo"),
+            overlay(
+                "/proc/self/fd/8/capacity",
+                "/sys/class/power_supply/battery/capacity",
+            ),
+        ];
+        sort_for_mount(&mut mounts);
+        let roots: Vec<_> = mounts
+            .iter()
+            .map(|mount| {
+                (
+                    format!("/{}", staged_name(&mount.target)),
+                    mount.target.as_str(),
+                )
+            })
+            .collect();
+        let roots: Vec<_> = roots.iter().map(|(r, t)| (r.as_str(), *t)).collect();
+        let mountinfo = format!(
+            "22 1 0:20 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw\n\
+             23 1 0:21 / /sys rw,nosuid,nodev,noexec,relatime shared:2 - sysfs sysfs rw\n{}",
+            render(812, &roots)
+        );
+
+        // 名字与路径不再暴露，剩下每个挂载的文件系统类型与超级块
+        let found = telltales(&mountinfo, &mounts);
+        assert_eq!(found.len(), 6);
+        assert!(found.iter().all(|telltale| matches!(
+            telltale,
+            Telltale::FsTypeMismatch { .. } | Telltale::DeviceMismatch { .. }
+        )));
+        assert!(found.contains(&Telltale::FsTypeMismatch {
+            target: "/proc/cpuinfo".to_string(),
+            fstype: "tmpfs".to_string(),
+            parent: "proc".to_string(),
+        }));
+    }
+
+    #[test]
+    fn legacy_sources_and_reordered_ids_are_reported() {
+        let mounts = [
+            overlay("/data/adb/device_faker/cpu/cpu-00ff", "/proc/cpuinfo"),
+            overlay("/proc/self/fd/8/meminfo", "/proc/meminfo"),
+        ];
+        let mountinfo = format!(
+            "{}\n{}\n{}",
+            render(900, &[("/adb/device_faker/cpu/cpu-00ff", "/proc/cpuinfo")]),
+            render(850, &[("/meminfo", "/proc/meminfo")]),
+            render(851, &[("/meminfo", "/proc/meminfo")]),
+        );
+
+        assert_eq!(
+            telltales(&mountinfo, &mounts),
+            [
+                Telltale::SuspiciousRoot {
+                    target: "/proc/cpuinfo".to_string(),
+                    root: "/adb/device_faker/cpu/cpu-00ff".to_string(),
+                },
+                Telltale::IdOutOfOrder {
+                    target: "/proc/meminfo".to_string(),
+                    id: 850,
+                    previous: 900,
+                },
+                Telltale::Stacked {
+                    target: "/proc/meminfo".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+    }
+}
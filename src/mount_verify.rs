@@ -0,0 +1,150 @@
+// 覆盖文件挂载后的自检。
+//
+// 挂载成功只说明 mount 系统调用返回 0，app 实际读到的未必是伪装内容：挂载可能被
+// 后来的挂载覆盖，源文件的 SELinux label 可能不允许 app 读取（open 返回 EACCES，
+// app 会看到读取失败而不是伪装值）。companion 通过 /proc/<pid>/root 从 app 的
+// namespace 读回内容并比较哈希，核对 mountinfo 中的挂载项，并向 selinuxfs 查询 app
+// 域对源文件 label 的读权限，结果随应答返回给模块。
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+};
+
+use log::warn;
+
+use crate::cpu_spoof::OverlayMount;
+use crate::mount_hiding::telltales;
+use crate::overlay_store::fnv1a;
+use crate::protocol::MountCheck;
+
+/// 用于权限检查的 app 域。specialize 时 app 仍处于 zygote 域，最终域取决于 seinfo，
+/// 这里按限制最多的普通第三方 app 检查。
+const APP_DOMAIN: &str = "u:r:untrusted_app:s0";
+const SELINUXFS: &str = "/sys/fs/selinux";
+
+/// 逐个检查已挂载到 pid 所在 namespace 的覆盖文件，并记录 mountinfo 中暴露挂载的特征。
+pub(crate) fn verify_overlays(pid: u32, mounts: &[OverlayMount]) -> Vec<MountCheck> {
+    let mountinfo = fs::read_to_string(format!("/proc/{pid}/mountinfo")).unwrap_or_default();
+    for telltale in telltales(&mountinfo, mounts) {
+        warn!("Overlay mount of pid {pid} is detectable: {telltale}");
+    }
+    mounts
+        .iter()
+        .map(|mount| {
+            let label = file_label(&mount.source);
+            MountCheck {
+                target: mount.target.clone(),
+                content_matches: content_matches(pid, mount),
+                mountinfo_ok: mounted_from(&mountinfo, mount),
+                label_readable: label.as_deref().and_then(app_can_read),
+                label,
+            }
+        })
+        .collect()
+}
+
+/// mountinfo 中 `mount.target` 上最顶层的挂载是否来自 `mount.source`。
+///
+/// 第 4 列是挂载源在其文件系统内的路径，第 5 列是挂载点。
+pub(crate) fn mounted_from(mountinfo: &str, mount: &OverlayMount) -> bool {
+    mountinfo
+        .lines()
+        .rev()
+        .find_map(|line| {
+            let mut fields = line.split(' ');
+            let root = fields.nth(3)?;
+            (fields.next()? == mount.target).then_some(root)
+        })
+        .is_some_and(|root| root != "/" && mount.source.ends_with(root))
+}
+
+fn content_matches(pid: u32, mount: &OverlayMount) -> bool {
+    let seen = fs::read(format!("/proc/{pid}/root{}", mount.target));
+    match (fs::read(&mount.source), seen) {
+        (Ok(expected), Ok(seen)) => fnv1a(&expected) == fnv1a(&seen),
+        _ => false,
+    }
+}
+
+pub(crate) fn file_label(path: &str) -> Option<String> {
+    let path = std::ffi::CString::new(path).ok()?;
+    let mut buf = [0u8; 256];
+    let len = unsafe {
+        libc::lgetxattr(
+            path.as_ptr(),
+            c"security.selinux".as_ptr(),
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    // 内核返回的值可能包含末尾的 NUL
+    let value = &buf[..len as usize];
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    String::from_utf8(value.to_vec()).ok()
+}
+
+/// 通过 selinuxfs 的 access 接口查询 app 域对 `label` 的 file:read 权限。
+pub(crate) fn app_can_read(label: &str) -> Option<bool> {
+    let class: u32 = read_number(&format!("{SELINUXFS}/class/file/index"))?;
+    let perm: u32 = read_number(&format!("{SELINUXFS}/class/file/perms/read"))?;
+
+    // access 是事务文件：写入查询后在同一个 fd 上读取结果
+    let mut access = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!("{SELINUXFS}/access"))
+        .ok()?;
+    access
+        .write_all(format!("{APP_DOMAIN} {label} {class}").as_bytes())
+        .ok()?;
+    let mut reply = String::new();
+    access.read_to_string(&mut reply).ok()?;
+    let allowed = parse_allowed(&reply)?;
+    let bit = 1u32.checked_shl(perm.checked_sub(1)?)?;
+    Some(allowed & bit != 0)
+}
+
+fn read_number(path: &str) -> Option<u32> {
+    let mut content = String::new();
+    File::open(path).ok()?.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// access 的应答格式为 "allowed decided auditallow auditdeny seqno flags"，数值为十六进制。
+fn parse_allowed(reply: &str) -> Option<u32> {
+    u32::from_str_radix(reply.split_whitespace().next()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mounted_from_checks_topmost_mount() {
+        let mount = OverlayMount {
+            source: "/data/adb/device_faker/cpu/cpu-00ff".to_string(),
+            target: "/proc/cpuinfo".to_string(),
+        };
+        let ours = "812 790 254:40 /adb/device_faker/cpu/cpu-00ff /proc/cpuinfo rw,relatime - f2fs /dev/block/dm-40 rw";
+        let other = "813 790 0:5 /cpuinfo /proc/cpuinfo rw - proc proc rw";
+
+        assert!(mounted_from(ours, &mount));
+        assert!(!mounted_from("", &mount));
+        // 被其他挂载覆盖
+        assert!(!mounted_from(&format!("{ours}\n{other}"), &mount));
+        assert!(mounted_from(&format!("{other}\n{ours}"), &mount));
+    }
+
+    #[test]
+    fn parses_access_reply() {
+        assert_eq!(
+            parse_allowed("1fffff ffffffff 0 ffffffff 12 0"),
+            Some(0x1fffff)
+        );
+        assert_eq!(parse_allowed(""), None);
+    }
+}
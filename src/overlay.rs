@@ -0,0 +1,220 @@
+// 由 companion 通过 bind mount 覆盖到 app namespace 中的伪装文件内容生成。
+
+use crate::config::BatteryConfig;
+
+/// `/proc/meminfo` 中不随物理内存缩放的字段（swap 与虚拟地址空间相关）。
+const MEMINFO_UNSCALED_KEYS: &[&str] = &[
+    "SwapTotal",
+    "SwapFree",
+    "VmallocTotal",
+    "VmallocChunk",
+    "Hugepagesize",
+];
+
+/// 按目标总内存等比缩放 `/proc/meminfo`。
+///
+/// `MemTotal` 被设为 `ram_total_mb`，其余以 kB 为单位的字段按同一比例缩放，
+/// 保证 `MemFree <= MemAvailable <= MemTotal` 等关系与真实文件一致。
+/// 数值列保持原文件的右对齐位置。原文件缺少 `MemTotal` 时返回 `None`。
+pub fn render_meminfo(original: &str, ram_total_mb: u64) -> Option<String> {
+    let real_total_kb = original
+        .lines()
+        .find_map(|line| parse_meminfo_line(line).filter(|(key, ..)| *key == "MemTotal"))
+        .map(|(_, value, _)| value)
+        .filter(|value| *value > 0)?;
+    let fake_total_kb = ram_total_mb.saturating_mul(1024);
+
+    let mut output = String::with_capacity(original.len() + 16);
+    for line in original.lines() {
+        match parse_meminfo_line(line) {
+            Some((key, value, value_end)) if !MEMINFO_UNSCALED_KEYS.contains(&key) => {
+                let scaled = if key == "MemTotal" {
+                    fake_total_kb
+                } else {
+                    (value as u128 * fake_total_kb as u128 / real_total_kb as u128) as u64
+                };
+                let label = &line[..key.len() + 1];
+                let digits = scaled.to_string();
+                let pad = value_end.saturating_sub(label.len() + digits.len()).max(1);
+                output.push_str(label);
+                output.extend(std::iter::repeat_n(' ', pad));
+                output.push_str(&digits);
+                output.push_str(&line[value_end..]);
+            }
+            _ => output.push_str(line),
+        }
+        output.push('\n');
+    }
+
+    Some(output)
+}
+
+/// 解析 `Key:   12345 kB` 形式的行，返回 (key, 数值, 数值结束的字节偏移)。
+/// 不带 kB 单位的行（如 `HugePages_Total`）返回 `None`。
+fn parse_meminfo_line(line: &str) -> Option<(&str, u64, usize)> {
+    let (key, rest) = line.split_once(':')?;
+    let trimmed = rest.trim_start();
+    let digits_len = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+    if digits_len == 0 || trimmed[digits_len..].trim() != "kB" {
+        return None;
+    }
+    let value = trimmed[..digits_len].parse().ok()?;
+    let value_end = key.len() + 1 + (rest.len() - trimmed.len()) + digits_len;
+    Some((key, value, value_end))
+}
+
+/// 生成电池 sysfs 覆盖文件，返回 (文件名, 内容) 列表。
+///
+/// `read_real` 读取 `/sys/class/power_supply/battery/` 下的真实文件。
+/// `charge_full` 始终不超过 `charge_full_design`；未指定时按真实电池的
+/// 健康度（charge_full / charge_full_design）从伪装的设计容量推算，
+/// 避免出现 6000 mAh 设计容量配 4500 mAh 满充这类与模板不符的组合。
+pub fn render_battery_files(
+    battery: &BatteryConfig,
+    read_real: impl Fn(&str) -> Option<String>,
+) -> Vec<(&'static str, String)> {
+    let read_uah = |file: &str| read_real(file).and_then(|v| v.trim().parse::<u64>().ok());
+    let design_uah = battery.charge_full_design_mah.map(|mah| mah * 1000);
+
+    let full_uah = match (battery.charge_full_mah, design_uah) {
+        (Some(full_mah), Some(design)) => Some((full_mah * 1000).min(design)),
+        (Some(full_mah), None) => Some(full_mah * 1000),
+        (None, Some(design)) => Some(
+            match (read_uah("charge_full"), read_uah("charge_full_design")) {
+                (Some(full), Some(real_design)) if real_design > 0 => {
+                    (design as u128 * full.min(real_design) as u128 / real_design as u128) as u64
+                }
+                _ => design,
+            },
+        ),
+        (None, None) => None,
+    };
+
+    let mut files = Vec::new();
+    if let Some(design) = design_uah {
+        files.push(("charge_full_design", format!("{design}\n")));
+    }
+    if let Some(full) = full_uah {
+        files.push(("charge_full", format!("{full}\n")));
+    }
+    if let Some(technology) = battery.technology.as_deref().filter(|t| !t.is_empty()) {
+        files.push(("technology", format!("{technology}\n")));
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_MEMINFO: &str = "\
+MemTotal:        3844752 kB
+MemFree:          145352 kB
+MemAvailable:    1422144 kB
+Buffers:            2412 kB
+Cached:          1388128 kB
+SwapCached:        27504 kB
+Active:           947376 kB
+Inactive:        1569196 kB
+SwapTotal:       2883560 kB
+SwapFree:        1702144 kB
+VmallocTotal:   263061440 kB
+VmallocUsed:      108320 kB
+VmallocChunk:          0 kB
+CmaTotal:         172032 kB
+HugePages_Total:       0
+Hugepagesize:       2048 kB
+";
+
+    fn value_of(meminfo: &str, key: &str) -> u64 {
+        meminfo
+            .lines()
+            .find_map(|line| parse_meminfo_line(line).filter(|(k, ..)| *k == key))
+            .map(|(_, value, _)| value)
+            .unwrap()
+    }
+
+    #[test]
+    fn meminfo_total_is_replaced_and_other_fields_scaled() {
+        let rendered = render_meminfo(SAMPLE_MEMINFO, 12 * 1024).unwrap();
+
+        assert_eq!(value_of(&rendered, "MemTotal"), 12 * 1024 * 1024);
+        // 3844752 kB -> 12582912 kB，比例约 3.27
+        assert_eq!(value_of(&rendered, "MemFree"), 475700);
+        assert_eq!(value_of(&rendered, "MemAvailable"), 4654321);
+        assert!(value_of(&rendered, "MemAvailable") < value_of(&rendered, "MemTotal"));
+        assert!(value_of(&rendered, "MemFree") < value_of(&rendered, "MemAvailable"));
+    }
+
+    #[test]
+    fn meminfo_keeps_swap_vmalloc_and_counters() {
+        let rendered = render_meminfo(SAMPLE_MEMINFO, 16 * 1024).unwrap();
+
+        for key in ["SwapTotal", "SwapFree", "VmallocTotal", "Hugepagesize"] {
+            assert_eq!(value_of(&rendered, key), value_of(SAMPLE_MEMINFO, key));
+        }
+        assert!(rendered.contains("HugePages_Total:       0\n"));
+        assert_eq!(rendered.lines().count(), SAMPLE_MEMINFO.lines().count());
+    }
+
+    #[test]
+    fn meminfo_preserves_column_alignment() {
+        let rendered = render_meminfo(SAMPLE_MEMINFO, 8 * 1024).unwrap();
+
+        for (original, scaled) in SAMPLE_MEMINFO.lines().zip(rendered.lines()) {
+            if original.ends_with(" kB") {
+                assert_eq!(original.len(), scaled.len(), "{original:?} -> {scaled:?}");
+            }
+        }
+        assert!(rendered.starts_with("MemTotal:        8388608 kB\n"));
+    }
+
+    #[test]
+    fn meminfo_without_total_is_rejected() {
+        assert!(render_meminfo("MemFree: 1 kB\n", 8192).is_none());
+    }
+
+    fn battery(design: Option<u64>, full: Option<u64>) -> BatteryConfig {
+        BatteryConfig {
+            charge_full_design_mah: design,
+            charge_full_mah: full,
+            technology: Some("Li-poly".to_string()),
+        }
+    }
+
+    fn real_battery(file: &str) -> Option<String> {
+        match file {
+            "charge_full_design" => Some("4500000\n".to_string()),
+            "charge_full" => Some("4050000\n".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn battery_charge_full_follows_real_health() {
+        let files = render_battery_files(&battery(Some(6000), None), real_battery);
+
+        assert_eq!(
+            files,
+            vec![
+                ("charge_full_design", "6000000\n".to_string()),
+                ("charge_full", "5400000\n".to_string()),
+                ("technology", "Li-poly\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn battery_charge_full_is_clamped_to_design() {
+        let files = render_battery_files(&battery(Some(5000), Some(5200)), |_| None);
+
+        assert!(files.contains(&("charge_full", "5000000\n".to_string())));
+    }
+
+    #[test]
+    fn battery_without_real_data_reports_full_design() {
+        let files = render_battery_files(&battery(Some(5000), None), |_| None);
+
+        assert!(files.contains(&("charge_full", "5000000\n".to_string())));
+    }
+}
@@ -0,0 +1,75 @@
+// 覆盖文件源文件的 SELinux label 选择。
+//
+// app 读取被覆盖的路径时，SELinux 检查的是源文件 inode 的 label。label 不可读时 app
+// 打开 /proc/cpuinfo 会得到 EACCES，这本身就是检测特征，因此 label 在挂载前确定：
+// 首选配置指定的 label，未指定时沿用目标文件自身的 label（如 proc_cpuinfo），不可用
+// 时退回 system_file。候选先经 selinuxfs 确认 app 域可读，写入后再读回确认生效；
+// 全部不可用时放弃挂载。
+
+use anyhow::Result;
+use log::warn;
+
+use crate::cpu_spoof::{SELINUX_CONTEXT, write_selinux_label};
+use crate::mount_verify::{app_can_read, file_label};
+
+/// 配置值：沿用目标文件的 label
+pub(crate) const TARGET_LABEL: &str = "target";
+
+/// 按优先级排列的候选 label，已排除 app 域确定不可读的。
+///
+/// `choice` 为配置中该覆盖文件的 label，`None` 与 `"target"` 相同。
+pub(crate) fn candidates(target: &str, choice: Option<&str>) -> Result<Vec<String>> {
+    let preferred = match choice.unwrap_or(TARGET_LABEL) {
+        TARGET_LABEL => file_label(target),
+        label => Some(label.to_string()),
+    };
+    let mut labels: Vec<String> = preferred
+        .into_iter()
+        .chain([SELINUX_CONTEXT.to_string()])
+        .collect();
+    labels.dedup();
+    // selinuxfs 不可查询时无法判断，保留候选
+    labels.retain(|label| {
+        let readable = app_can_read(label) != Some(false);
+        if !readable {
+            warn!("SELinux label {label} is not readable by apps, skipping it for {target}");
+        }
+        readable
+    });
+    if labels.is_empty() {
+        anyhow::bail!("no SELinux label readable by apps for {target}");
+    }
+    Ok(labels)
+}
+
+/// 依次尝试候选 label，返回实际生效的 label。
+pub(crate) fn apply(path: &str, candidates: &[String]) -> Result<String> {
+    for label in candidates {
+        match write_selinux_label(path, label) {
+            Ok(()) if file_label(path).as_deref() == Some(label.as_str()) => {
+                return Ok(label.clone());
+            }
+            Ok(()) => warn!("SELinux label {label} on {path} did not take effect"),
+            Err(e) => warn!("Failed to set SELinux label {label} on {path}: {e}"),
+        }
+    }
+    anyhow::bail!("none of the SELinux labels {candidates:?} could be applied to {path}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_label_comes_before_fallback() {
+        // 测试环境没有 selinuxfs，可读性未知的候选全部保留
+        assert_eq!(
+            candidates("/proc/cpuinfo", Some("u:object_r:proc_cpuinfo:s0")).unwrap(),
+            ["u:object_r:proc_cpuinfo:s0", SELINUX_CONTEXT]
+        );
+        assert_eq!(
+            candidates("/proc/cpuinfo", Some(SELINUX_CONTEXT)).unwrap(),
+            [SELINUX_CONTEXT]
+        );
+    }
+}
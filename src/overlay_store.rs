@@ -0,0 +1,232 @@
+// 覆盖文件源文件的内容寻址存储。
+//
+// 源文件按内容的 FNV-1a 哈希命名（`<kind>-<hash>`）：同一预设在每次启动 app 时生成的
+// 内容相同，共用一个文件，不必每次重写，路径也不再包含 app 的 pid。文件按引用计数
+// 共享，最后一个引用它的 app 退出后删除。
+//
+// 支持暂存 namespace 时（见 `mount_hiding`）源文件放在各自的 tmpfs 实例中，文件名
+// 与挂载目标相同，最后一个引用释放时卸载实例；否则写入状态目录下的普通文件。
+//
+// companion 异常退出时计数丢失，下次启动时清理目录中不再被任何 mount namespace
+// 引用的文件；仍被运行中的 app 挂载的文件保留到下次启动，避免其 mountinfo 中出现
+// `(deleted)`。tmpfs 实例随 companion 的暂存 namespace 一起消失，无需清理。
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::cpu_spoof::CPU_SPOOF_STATE_DIR;
+use crate::mount_hiding::StagedSource;
+use crate::overlay_label;
+
+struct Source {
+    refs: usize,
+    /// 源文件所在的 tmpfs 实例，普通文件为 `None`
+    staged: Option<StagedSource>,
+}
+
+/// 源文件路径 → 引用
+static SOURCES: LazyLock<Mutex<HashMap<String, Source>>> =
+    LazyLock::new(|| Mutex::new(collect_garbage()));
+
+/// 在 companion 启动时清理残留文件，仅第一次调用生效。
+pub(crate) fn init() {
+    LazyLock::force(&SOURCES);
+}
+
+/// 取得内容为 `content` 的源文件并增加引用，不存在时写入。
+///
+/// `kind`、内容与首选 label 一起决定是否共用，`name` 是 tmpfs 实例中的文件名，
+/// `labels` 是按优先级排列的候选 SELinux label。
+pub(crate) fn acquire(kind: &str, name: &str, content: &[u8], labels: &[String]) -> Result<String> {
+    let preferred = labels.first().map_or(&[][..], |label| label.as_bytes());
+    let key = format!("{kind}-{:016x}", fnv1a(&[content, preferred].concat()));
+    let mut sources = SOURCES.lock().unwrap();
+    let plain = format!("{CPU_SPOOF_STATE_DIR}/{key}");
+    let existing = sources
+        .iter_mut()
+        .find(|(path, source)| match &source.staged {
+            Some(staged) => staged.key() == key,
+            None => **path == plain && fs::metadata(&plain).is_ok(),
+        });
+    if let Some((path, source)) = existing {
+        source.refs += 1;
+        return Ok(path.clone());
+    }
+
+    let (path, staged) = match StagedSource::create(&key)? {
+        Some(staged) => (staged.path(name), Some(staged)),
+        None => (plain, None),
+    };
+    if staged.is_some() {
+        write_source(&path, content, labels)?;
+    } else {
+        // 先写临时文件并设置 label 再改名，app 不会看到写了一半的文件
+        let tmp = format!("{path}.tmp");
+        write_source(&tmp, content, labels)?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to rename {tmp}"))?;
+    }
+    sources
+        .entry(path.clone())
+        .and_modify(|source| source.refs += 1)
+        .or_insert(Source { refs: 1, staged });
+    Ok(path)
+}
+
+/// 取得 pid 独占的源文件，不与其他 app 共用 inode。
+pub(crate) fn acquire_private(
+    pid: u32,
+    name: &str,
+    content: &[u8],
+    labels: &[String],
+) -> Result<String> {
+    let key = format!("{name}-{pid}");
+    let (path, staged) = match StagedSource::create(&key)? {
+        Some(staged) => (staged.path(name), Some(staged)),
+        None => (format!("{CPU_SPOOF_STATE_DIR}/{key}"), None),
+    };
+    write_source(&path, content, labels)?;
+    SOURCES
+        .lock()
+        .unwrap()
+        .insert(path.clone(), Source { refs: 1, staged });
+    Ok(path)
+}
+
+fn write_source(path: &str, content: &[u8], labels: &[String]) -> Result<()> {
+    fs::write(path, content).with_context(|| format!("Failed to write overlay source {path}"))?;
+    if let Err(e) = overlay_label::apply(path, labels) {
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 释放一个引用，归零时删除文件或卸载 tmpfs 实例。
+pub(crate) fn release(path: &str) {
+    let mut sources = SOURCES.lock().unwrap();
+    let Some(source) = sources.get_mut(path) else {
+        warn!("Released untracked overlay source {path}");
+        return;
+    };
+    source.refs -= 1;
+    if source.refs > 0 {
+        return;
+    }
+    if sources
+        .remove(path)
+        .is_some_and(|source| source.staged.is_some())
+    {
+        return;
+    }
+    if let Err(e) = fs::remove_file(path) {
+        warn!("Failed to remove overlay source {path}: {e}");
+    }
+}
+
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    data.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// 删除没有被任何 mount namespace 引用的源文件，返回保留文件的初始计数。
+///
+/// 保留的文件计一个不会释放的引用，本次开机内不再删除。
+fn collect_garbage() -> HashMap<String, Source> {
+    let mut sources = HashMap::new();
+    let Ok(entries) = fs::read_dir(CPU_SPOOF_STATE_DIR) else {
+        return sources;
+    };
+    let mounted = mounted_sources();
+    let (mut kept, mut removed) = (0, 0);
+    for entry in entries.flatten() {
+        // 暂存 namespace 的挂载点目录
+        if entry.file_type().is_ok_and(|ty| ty.is_dir()) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{CPU_SPOOF_STATE_DIR}/{name}");
+        if mounted.contains(&name) {
+            sources.insert(
+                path,
+                Source {
+                    refs: 1,
+                    staged: None,
+                },
+            );
+            kept += 1;
+        } else if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove stale overlay source {path}: {e}");
+        } else {
+            removed += 1;
+        }
+    }
+    if kept + removed > 0 {
+        info!("overlay sources: removed {removed} stale file(s), kept {kept} still mounted");
+    }
+    sources
+}
+
+/// 扫描所有进程的 mountinfo，收集挂载源位于状态目录下的文件名。
+/// 同一 mount namespace 只读取一次。
+fn mounted_sources() -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut seen_ns = HashSet::new();
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return names;
+    };
+    for entry in procs.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(ns) = fs::read_link(format!("/proc/{pid}/ns/mnt")) else {
+            continue;
+        };
+        if !seen_ns.insert(ns) {
+            continue;
+        }
+        if let Ok(mountinfo) = fs::read_to_string(format!("/proc/{pid}/mountinfo")) {
+            names.extend(source_names(&mountinfo));
+        }
+    }
+    names
+}
+
+/// mountinfo 中挂载源（第 4 列）位于状态目录下的文件名。
+fn source_names(mountinfo: &str) -> impl Iterator<Item = String> + '_ {
+    let dir_name = CPU_SPOOF_STATE_DIR.trim_start_matches("/data");
+    mountinfo.lines().filter_map(move |line| {
+        let root = line.split(' ').nth(3)?;
+        let (dir, name) = root.rsplit_once('/')?;
+        (dir.ends_with(dir_name) && !name.is_empty()).then(|| name.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn source_names_pick_files_under_state_dir() {
+        let mountinfo = "\
+812 790 254:40 /adb/device_faker/cpu/cpu-00ff /proc/cpuinfo rw - f2fs /dev/block/dm-40 rw
+813 790 0:5 / /proc rw - proc proc rw
+814 790 254:40 /adb/modules/x/system.prop /system/build.prop ro - f2fs /dev/block/dm-40 rw";
+        let names: Vec<_> = source_names(mountinfo).collect();
+        assert_eq!(names, ["cpu-00ff"]);
+    }
+}
@@ -0,0 +1,177 @@
+// 应用进程登记表：同一应用的所有进程共享一个属性会话，由 supervisor 统一监控。
+//
+// 应用以 `包名@用户` 区分，每个进程在 specialize 时把自己的 pid 登记到所属应用的
+// 进程组。`:push`、`:remote` 等子进程可能比主进程活得更久，也可能在主进程退出后
+// 才启动，因此只有进程组内所有进程都进入后台时才还原，全部退出后才关闭会话。
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    os::fd::OwnedFd,
+};
+
+use crate::config::RestorePolicy;
+use crate::prop_session::SessionId;
+
+pub type GroupId = u64;
+
+/// 一个应用当前登记的进程。
+pub struct ProcessGroup {
+    pub id: GroupId,
+    pub uid: u32,
+    pub pids: BTreeSet<u32>,
+    /// 进程组共享的属性会话，最新登记的进程打开的会话接替旧会话
+    pub session: SessionId,
+    pub policy: RestorePolicy,
+    /// 请求处理线程在回复前打开、尚未交给 supervisor 的 pidfd
+    pub pidfds: HashMap<u32, OwnedFd>,
+}
+
+/// `register` 的结果。
+pub enum Registration {
+    /// 新建了进程组。`evicted` 为同名但 uid 不同
+    /// （应用被重装）而被替换的旧进程组。
+    Created { evicted: Option<ProcessGroup> },
+    /// 加入了已有进程组，`replaced` 为被新会话接替的旧会话。
+    Joined { replaced: SessionId },
+}
+
+#[derive(Default)]
+pub struct ProcessRegistry {
+    groups: HashMap<String, ProcessGroup>,
+    next_id: GroupId,
+}
+
+impl ProcessRegistry {
+    /// 登记进程及其新打开的会话。`pidfd` 为该进程的 pidfd，打开失败时为 `None`。
+    pub fn register(
+        &mut self,
+        package: &str,
+        uid: u32,
+        pid: u32,
+        pidfd: Option<OwnedFd>,
+        session: SessionId,
+        policy: RestorePolicy,
+    ) -> Registration {
+        if let Some(group) = self.groups.get_mut(package)
+            && group.uid == uid
+        {
+            group.pids.insert(pid);
+            group.pidfds.extend(pidfd.map(|pidfd| (pid, pidfd)));
+            group.policy = policy;
+            let replaced = std::mem::replace(&mut group.session, session);
+            return Registration::Joined { replaced };
+        }
+
+        self.next_id += 1;
+        let id = self.next_id;
+        let evicted = self.groups.insert(
+            package.to_string(),
+            ProcessGroup {
+                id,
+                uid,
+                pids: BTreeSet::from([pid]),
+                session,
+                policy,
+                pidfds: pidfd.map(|pidfd| (pid, pidfd)).into_iter().collect(),
+            },
+        );
+        Registration::Created { evicted }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &ProcessGroup)> {
+        self.groups.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut ProcessGroup)> {
+        self.groups.iter_mut()
+    }
+
+    /// 按 id 查找进程组。进程组被移除或替换后返回 `None`。
+    pub fn group(&self, package: &str, id: GroupId) -> Option<&ProcessGroup> {
+        self.groups.get(package).filter(|group| group.id == id)
+    }
+
+    pub fn group_mut(&mut self, package: &str, id: GroupId) -> Option<&mut ProcessGroup> {
+        self.groups.get_mut(package).filter(|group| group.id == id)
+    }
+
+    /// 进程退出后从进程组中移除。
+    pub fn remove_pid(&mut self, package: &str, id: GroupId, pid: u32) {
+        if let Some(group) = self.group_mut(package, id) {
+            group.pids.remove(&pid);
+        }
+    }
+
+    /// 移除整个进程组，调用方负责关闭其会话。
+    pub fn remove(&mut self, package: &str) -> Option<ProcessGroup> {
+        self.groups.remove(package)
+    }
+
+    /// 移除全部进程组，调用方负责关闭会话。
+    pub fn clear(&mut self) {
+        self.groups.clear();
+    }
+
+    /// 进程组已没有登记的进程时移除并返回。
+    ///
+    /// supervisor 判断所有进程退出后调用；与 `register` 在同一把锁下执行，
+    /// 期间新登记的进程会让进程组继续保留。
+    pub fn remove_if_empty(&mut self, package: &str, id: GroupId) -> Option<ProcessGroup> {
+        if self.group(package, id)?.pids.is_empty() {
+            self.groups.remove(package)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processes_of_one_app_share_a_group_until_all_exit() {
+        let mut registry = ProcessRegistry::default();
+        let policy = RestorePolicy::default();
+
+        let Registration::Created { evicted: None } =
+            registry.register("com.a@0", 10100, 100, None, 1, policy)
+        else {
+            panic!("first process should create the group");
+        };
+        let group = registry.iter().next().unwrap().1.id;
+        assert!(matches!(
+            registry.register("com.a@0", 10100, 101, None, 2, policy),
+            Registration::Joined { replaced: 1, .. }
+        ));
+
+        // 主进程退出，:push 进程仍在
+        registry.remove_pid("com.a@0", group, 100);
+        assert!(registry.remove_if_empty("com.a@0", group).is_none());
+        assert_eq!(registry.group("com.a@0", group).unwrap().session, 2);
+
+        registry.remove_pid("com.a@0", group, 101);
+        assert_eq!(
+            registry
+                .remove_if_empty("com.a@0", group)
+                .map(|g| g.session),
+            Some(2)
+        );
+        assert!(registry.group("com.a@0", group).is_none());
+    }
+
+    #[test]
+    fn reinstalled_app_with_new_uid_replaces_stale_group() {
+        let mut registry = ProcessRegistry::default();
+        let policy = RestorePolicy::default();
+        registry.register("com.a@0", 10100, 100, None, 1, policy);
+
+        match registry.register("com.a@0", 10200, 200, None, 2, policy) {
+            Registration::Created { evicted: Some(old) } => {
+                assert_eq!(old.session, 1);
+                assert_eq!(old.pids, BTreeSet::from([100]));
+            }
+            _ => panic!("different uid should start a new group"),
+        }
+    }
+}
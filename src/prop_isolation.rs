@@ -0,0 +1,475 @@
+// 属性隔离（isolated 模式）：伪装值只对目标 app 可见，不修改全局属性区。
+//
+// 属性区是 /dev/__properties__/<context> 下的共享内存文件。companion 为伪装属性
+// 涉及的每个 context 复制一份私有副本（经 `overlay_store` 放在暂存 tmpfs 中，
+// 文件名与原属性区相同），在一个独立 mount namespace 的子进程里把副本
+// bind mount 到原路径上，再用 resetprop 改写副本；随后由 cpu_spoof 的 mount child
+// 把副本挂到 app 的 namespace 中。
+//
+// app 进程从 zygote fork 而来，继承了 zygote 对原属性区的映射，仅靠挂载看不到副本。
+// 因此 app 在 companion 挂载完成后调用 `remap_property_areas`，按路径重新打开属性区
+// 并以 MAP_FIXED 覆盖原映射。之后 exec 出的子进程直接映射到副本。
+//
+// 副本是启动时的快照：init 之后对同一 context 中其他属性的修改不会反映到该 app。
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::CString,
+    fs,
+    os::unix::fs::MetadataExt,
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use prop_rs_android::sys_prop;
+
+use crate::overlay_store;
+
+pub(crate) const PROPERTIES_DIR: &str = "/dev/__properties__";
+
+/// 一个属性区副本：私有源文件 → /dev/__properties__ 下的目标路径。
+pub(crate) struct PropertyAreaCopy {
+    pub(crate) source: String,
+    pub(crate) target: String,
+}
+
+/// 属性区副本的存放与改写方式。生产环境为 `OverlayPropertyAreas`，测试中用临时文件代替。
+pub(crate) trait PropertyAreaStore {
+    /// 属性所属的 context。
+    fn context(&mut self, key: &str) -> Result<String>;
+    /// 以 `content` 创建 context 的私有副本，返回副本路径。
+    fn create(&mut self, context: &str, content: &[u8]) -> Result<String>;
+    fn release(&mut self, source: &str);
+    /// 在副本中写入伪装值。
+    fn rewrite(
+        &mut self,
+        copies: &[PropertyAreaCopy],
+        props: &HashMap<String, String>,
+        delete_props: &[String],
+    ) -> Result<()>;
+}
+
+/// 副本由 `overlay_store` 放在暂存 tmpfs 中，与其他覆盖文件一样不暴露模块目录和 pid。
+pub(crate) struct OverlayPropertyAreas {
+    pub(crate) pid: u32,
+}
+
+impl PropertyAreaStore for OverlayPropertyAreas {
+    fn context(&mut self, key: &str) -> Result<String> {
+        sys_prop::init()
+            .map_err(|e| anyhow::anyhow!("failed to initialize system property API: {e}"))?;
+        sys_prop::get_context(key)
+            .map_err(|e| anyhow::anyhow!("no property context for {key}: {e}"))
+    }
+
+    fn create(&mut self, context: &str, content: &[u8]) -> Result<String> {
+        // 文件名与目标相同；沿用原属性区的 label，app 对副本的访问权限与原文件一致
+        overlay_store::acquire_private(self.pid, context, content, &[context.to_string()])
+    }
+
+    fn release(&mut self, source: &str) {
+        overlay_store::release(source);
+    }
+
+    fn rewrite(
+        &mut self,
+        copies: &[PropertyAreaCopy],
+        props: &HashMap<String, String>,
+        delete_props: &[String],
+    ) -> Result<()> {
+        write_copies_in_child(copies, props, delete_props)
+    }
+}
+
+/// 为伪装属性涉及的每个 context 生成已改写的私有副本。
+///
+/// `properties_dir` 为原属性区所在目录，即 `PROPERTIES_DIR`。失败时释放已创建的副本。
+pub(crate) fn prepare_property_areas(
+    store: &mut impl PropertyAreaStore,
+    properties_dir: &str,
+    props: &HashMap<String, String>,
+    delete_props: &[String],
+) -> Result<Vec<PropertyAreaCopy>> {
+    let mut contexts = BTreeSet::new();
+    for key in props.keys().chain(delete_props) {
+        contexts.insert(store.context(key)?);
+    }
+
+    let mut copies = Vec::new();
+    for context in &contexts {
+        let target = format!("{properties_dir}/{context}");
+        let source = fs::read(&target)
+            .with_context(|| format!("Failed to read {target}"))
+            .and_then(|content| store.create(context, &content));
+        match source {
+            Ok(source) => copies.push(PropertyAreaCopy { source, target }),
+            Err(e) => {
+                release_copies(store, &copies);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Err(e) = store.rewrite(&copies, props, delete_props) {
+        release_copies(store, &copies);
+        return Err(e);
+    }
+    info!(
+        "Prepared {} private property area(s) for {} key(s)",
+        copies.len(),
+        props.len() + delete_props.len()
+    );
+    Ok(copies)
+}
+
+fn release_copies(store: &mut impl PropertyAreaStore, copies: &[PropertyAreaCopy]) {
+    for copy in copies {
+        store.release(&copy.source);
+    }
+}
+
+/// fork 子进程：进入私有 mount namespace，把副本挂到原路径上后用 resetprop 改写。
+///
+/// 子进程退出码为 0 表示全部写入成功。
+fn write_copies_in_child(
+    copies: &[PropertyAreaCopy],
+    props: &HashMap<String, String>,
+    delete_props: &[String],
+) -> Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => anyhow::bail!("fork failed: {}", std::io::Error::last_os_error()),
+        0 => {
+            let code = match write_copies(copies, props, delete_props) {
+                Ok(()) => 0,
+                Err(e) => {
+                    warn!("[prop-child] {e}");
+                    1
+                }
+            };
+            unsafe { libc::_exit(code) }
+        }
+        child_pid => {
+            let mut status = 0i32;
+            if unsafe { libc::waitpid(child_pid, &mut status, 0) } < 0 {
+                anyhow::bail!("waitpid failed: {}", std::io::Error::last_os_error());
+            }
+            if !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+                anyhow::bail!("property area writer failed (status {status})");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_copies(
+    copies: &[PropertyAreaCopy],
+    props: &HashMap<String, String>,
+    delete_props: &[String],
+) -> Result<()> {
+    if unsafe { libc::unshare(libc::CLONE_NEWNS) } != 0 {
+        anyhow::bail!("unshare failed: {}", std::io::Error::last_os_error());
+    }
+    // 先把整个树设为私有，避免挂载传播回 companion 所在的 namespace
+    let root = CString::new("/")?;
+    let ret = unsafe {
+        libc::mount(
+            std::ptr::null(),
+            root.as_ptr(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        )
+    };
+    if ret != 0 {
+        anyhow::bail!("make-rprivate failed: {}", std::io::Error::last_os_error());
+    }
+
+    for copy in copies {
+        let source = CString::new(copy.source.as_str())?;
+        let target = CString::new(copy.target.as_str())?;
+        let ret = unsafe {
+            libc::mount(
+                source.as_ptr(),
+                target.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND,
+                std::ptr::null(),
+            )
+        };
+        if ret != 0 {
+            anyhow::bail!(
+                "bind mount onto {} failed: {}",
+                copy.target,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    // companion 可能已经映射过原属性区（属性会话），让这些映射也指向副本
+    remap_property_areas()?;
+
+    let resetprop = crate::companion::new_resetprop()?;
+    for (key, value) in props {
+        resetprop
+            .set(key, value)
+            .map_err(|e| anyhow::anyhow!("resetprop failed for {key}: {e}"))?;
+    }
+    for key in delete_props {
+        resetprop
+            .delete(key)
+            .map_err(|e| anyhow::anyhow!("resetprop delete failed for {key}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// 本进程中的一段属性区映射（来自 /proc/self/maps）。
+#[derive(Debug, PartialEq, Eq)]
+struct PropertyMapping {
+    start: usize,
+    end: usize,
+    writable: bool,
+    inode: u64,
+    path: String,
+}
+
+fn parse_property_mappings(maps: &str, properties_dir: &str) -> Vec<PropertyMapping> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (range, perms, offset, _dev, inode, path) = (
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            );
+            if !path.starts_with(properties_dir) || offset != "00000000" {
+                return None;
+            }
+            let (start, end) = range.split_once('-')?;
+            Some(PropertyMapping {
+                start: usize::from_str_radix(start, 16).ok()?,
+                end: usize::from_str_radix(end, 16).ok()?,
+                writable: perms.contains('w'),
+                inode: inode.parse().ok()?,
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 把路径已被挂载替换的属性区映射重新指向当前路径对应的文件，返回替换的映射数。
+///
+/// 通过比较映射的 inode 与路径当前的 inode 判断是否被替换，无需知道具体 context。
+pub(crate) fn remap_property_areas() -> Result<usize> {
+    remap_mappings_in(PROPERTIES_DIR)
+}
+
+fn remap_mappings_in(properties_dir: &str) -> Result<usize> {
+    let maps = fs::read_to_string("/proc/self/maps").context("Failed to read /proc/self/maps")?;
+
+    let mut remapped = 0;
+    for mapping in parse_property_mappings(&maps, properties_dir) {
+        let Ok(metadata) = fs::metadata(&mapping.path) else {
+            continue;
+        };
+        if metadata.ino() == mapping.inode {
+            continue;
+        }
+
+        let path = CString::new(mapping.path.as_str())?;
+        let (flags, prot) = if mapping.writable {
+            (libc::O_RDWR, libc::PROT_READ | libc::PROT_WRITE)
+        } else {
+            (libc::O_RDONLY, libc::PROT_READ)
+        };
+        let fd = unsafe { libc::open(path.as_ptr(), flags | libc::O_CLOEXEC) };
+        if fd < 0 {
+            anyhow::bail!(
+                "open {} failed: {}",
+                mapping.path,
+                std::io::Error::last_os_error()
+            );
+        }
+        let addr = unsafe {
+            libc::mmap(
+                mapping.start as *mut libc::c_void,
+                mapping.end - mapping.start,
+                prot,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                fd,
+                0,
+            )
+        };
+        unsafe { libc::close(fd) };
+        if addr == libc::MAP_FAILED {
+            anyhow::bail!(
+                "remap of {} failed: {}",
+                mapping.path,
+                std::io::Error::last_os_error()
+            );
+        }
+        remapped += 1;
+    }
+    Ok(remapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 用文本文件代替属性区：每行 `key=value`，context 为键的第一段。
+    struct FileAreas {
+        dir: String,
+        released: Vec<String>,
+        fail_rewrite: bool,
+    }
+
+    impl PropertyAreaStore for FileAreas {
+        fn context(&mut self, key: &str) -> Result<String> {
+            Ok(key.split('.').next().unwrap().to_string())
+        }
+
+        fn create(&mut self, context: &str, content: &[u8]) -> Result<String> {
+            let source = format!("{}/copy_{context}", self.dir);
+            fs::write(&source, content)?;
+            Ok(source)
+        }
+
+        fn release(&mut self, source: &str) {
+            self.released.push(source.to_string());
+        }
+
+        fn rewrite(
+            &mut self,
+            copies: &[PropertyAreaCopy],
+            props: &HashMap<String, String>,
+            delete_props: &[String],
+        ) -> Result<()> {
+            if self.fail_rewrite {
+                anyhow::bail!("injected failure");
+            }
+            for copy in copies {
+                let content = fs::read_to_string(&copy.source)?;
+                let lines: Vec<String> = content
+                    .lines()
+                    .filter_map(|line| {
+                        let (key, value) = line.split_once('=')?;
+                        if delete_props.iter().any(|k| k == key) {
+                            return None;
+                        }
+                        Some(format!(
+                            "{key}={}",
+                            props.get(key).map_or(value, String::as_str)
+                        ))
+                    })
+                    .collect();
+                fs::write(&copy.source, lines.join("\n"))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("df_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn copies_are_rewritten_without_touching_originals() {
+        let dir = temp_dir("prop_areas");
+        fs::write(format!("{dir}/ro"), "ro.model=Real\nro.serial=123").unwrap();
+        fs::write(format!("{dir}/vendor"), "vendor.x=1").unwrap();
+        let mut store = FileAreas {
+            dir: dir.clone(),
+            released: Vec::new(),
+            fail_rewrite: false,
+        };
+        let props = HashMap::from([("ro.model".to_string(), "Fake".to_string())]);
+
+        let copies =
+            prepare_property_areas(&mut store, &dir, &props, &["ro.serial".to_string()]).unwrap();
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].target, format!("{dir}/ro"));
+        assert_eq!(
+            fs::read_to_string(&copies[0].source).unwrap(),
+            "ro.model=Fake"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{dir}/ro")).unwrap(),
+            "ro.model=Real\nro.serial=123"
+        );
+
+        store.fail_rewrite = true;
+        assert!(prepare_property_areas(&mut store, &dir, &props, &[]).is_err());
+        assert_eq!(store.released, [format!("{dir}/copy_ro")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replaced_area_is_remapped_in_place() {
+        let dir = temp_dir("prop_remap");
+        let area = format!("{dir}/u:object_r:test_prop:s0");
+        fs::write(&area, vec![b'A'; 4096]).unwrap();
+        let path = CString::new(area.as_str()).unwrap();
+        let addr = unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+            assert!(fd >= 0);
+            let addr = libc::mmap(
+                std::ptr::null_mut(),
+                4096,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            libc::close(fd);
+            assert_ne!(addr, libc::MAP_FAILED);
+            addr.cast::<u8>()
+        };
+
+        // 用新 inode 替换路径，相当于副本挂到原属性区上
+        fs::write(format!("{area}.copy"), vec![b'B'; 4096]).unwrap();
+        fs::rename(format!("{area}.copy"), &area).unwrap();
+        assert_eq!(remap_mappings_in(&dir).unwrap(), 1);
+        assert_eq!(unsafe { *addr }, b'B');
+        assert_eq!(remap_mappings_in(&dir).unwrap(), 0);
+
+        unsafe { libc::munmap(addr.cast(), 4096) };
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_whole_property_area_mappings_are_parsed() {
+        let maps = "\
+70a3c00000-70a3c20000 r--s 00000000 00:13 1234 /dev/__properties__/u:object_r:vendor_prop:s0
+70a3c20000-70a3c40000 rw-s 00000000 00:13 1240 /dev/__properties__/properties_serial
+70a3c40000-70a3c41000 r--s 00001000 00:13 1250 /dev/__properties__/u:object_r:build_prop:s0
+70a3d00000-70a3d10000 r-xp 00000000 fd:05 777 /system/lib64/libc.so
+70a3e00000-70a3e10000 rw-p 00000000 00:00 0
+";
+        let mappings = parse_property_mappings(maps, PROPERTIES_DIR);
+
+        assert_eq!(
+            mappings,
+            vec![
+                PropertyMapping {
+                    start: 0x70a3c00000,
+                    end: 0x70a3c20000,
+                    writable: false,
+                    inode: 1234,
+                    path: "/dev/__properties__/u:object_r:vendor_prop:s0".to_string(),
+                },
+                PropertyMapping {
+                    start: 0x70a3c20000,
+                    end: 0x70a3c40000,
+                    writable: true,
+                    inode: 1240,
+                    path: "/dev/__properties__/properties_serial".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,866 @@
+// companion 侧的属性会话管理。
+//
+// 多个 companion 模式的 app 可能同时运行且使用不同模板。每个 app 进程对应一个会话，
+// 会话声明自己要修改的属性；真实原值只在某个属性第一次被声明时读取一次，
+// 之后由所有声明该属性的会话共享，全部会话释放后才写回原值。
+//
+// 冲突规则：同一属性被多个前台会话声明时，最近一次切到前台的会话生效；
+// 没有前台会话声明时恢复原值。
+//
+// 原值表在修改属性之前写入日志（见 journal.rs），companion 异常退出后可据此恢复。
+//
+// 每次状态变化产生的全部写入作为一个批次提交：任一写入失败时撤销本批次已写入的属性，
+// 批次结束后对涉及的属性区只重建一次。
+
+use std::{collections::HashMap, time::Instant};
+
+use anyhow::Result;
+use log::{error, info, warn};
+
+use crate::journal::{Journal, PropOriginal};
+use crate::protocol::SessionStatus;
+
+/// 属性读写后端。生产环境为 resetprop，测试中为内存实现。
+pub trait PropBackend {
+    /// 读取属性，`None` 表示属性不存在。
+    fn get(&mut self, key: &str) -> Result<Option<String>>;
+    fn set(&mut self, key: &str, value: &str) -> Result<()>;
+    /// 删除属性。属性本就不存在时视为成功。
+    fn delete(&mut self, key: &str) -> Result<()>;
+    /// 一批写入完成后调用，用于重建被修改的属性区。
+    fn flush(&mut self, _keys: &[String]) {}
+}
+
+/// 会话希望属性呈现的状态。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Desired {
+    Set(String),
+    Delete,
+}
+
+pub type SessionId = u64;
+
+struct Session {
+    package: String,
+    pid: u32,
+    props: HashMap<String, Desired>,
+    active: bool,
+    /// 最近一次切到前台的序号，用于决定冲突属性的归属
+    activated_at: u64,
+}
+
+pub struct SessionManager<B: PropBackend> {
+    backend: B,
+    sessions: HashMap<SessionId, Session>,
+    /// 被至少一个会话声明的属性的真实原值
+    originals: HashMap<String, PropOriginal>,
+    /// 当前写入到属性区的伪装状态，不在表中表示处于原值
+    applied: HashMap<String, Desired>,
+    next_id: SessionId,
+    clock: u64,
+    journal: Option<Journal>,
+}
+
+impl<B: PropBackend> SessionManager<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            sessions: HashMap::new(),
+            originals: HashMap::new(),
+            applied: HashMap::new(),
+            next_id: 1,
+            clock: 0,
+            journal: None,
+        }
+    }
+
+    /// 启用持久化日志。
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 恢复上一个 companion 进程遗留的原值并清空日志，返回恢复的属性数。
+    ///
+    /// 必须在打开任何会话之前调用。部分属性恢复失败时，未恢复的原值留在原值表和
+    /// 日志中，之后打开的会话沿用它们，不会把伪装值当作原值。
+    pub fn recover(&mut self) -> Result<usize> {
+        let pending = match &self.journal {
+            Some(journal) => journal.pending()?,
+            None => return Ok(0),
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        // 恢复尽力而为，不回滚：写回伪装值没有意义
+        let writes: Vec<PropWrite> = pending
+            .iter()
+            .map(|(key, original)| PropWrite {
+                key: key.clone(),
+                value: original.value().map(str::to_string),
+                previous: None,
+            })
+            .collect();
+        if let Err(e) = self.apply_batch(&writes, false) {
+            self.adopt_unrestored(pending);
+            return Err(e);
+        }
+
+        if let Some(journal) = &self.journal {
+            journal.clear()?;
+        }
+        warn!(
+            "recovered {} property originals left by a previous companion",
+            writes.len()
+        );
+        Ok(writes.len())
+    }
+
+    /// 接管恢复失败后仍未写回的原值。
+    ///
+    /// 仍为伪装值的属性记入 `applied`，最后一个声明它的会话关闭时或 `close_all`
+    /// 会重试写回。
+    fn adopt_unrestored(&mut self, pending: HashMap<String, PropOriginal>) {
+        for (key, original) in pending {
+            match self.backend.get(&key) {
+                Ok(current) if current.as_deref() == original.value() => continue,
+                Ok(Some(value)) => {
+                    self.applied.insert(key.clone(), Desired::Set(value));
+                }
+                Ok(None) => {
+                    self.applied.insert(key.clone(), Desired::Delete);
+                }
+                Err(e) => warn!("failed to read {key} after recovery: {e}"),
+            }
+            self.originals.insert(key, original);
+        }
+        if let Err(e) = self.persist() {
+            error!("failed to update journal after recovery: {e}");
+        }
+    }
+
+    /// 属性在当前属性区中的值：已应用的伪装状态，否则为原值。
+    fn current_value(&self, key: &str) -> Option<String> {
+        match self.applied.get(key) {
+            Some(Desired::Set(value)) => Some(value.clone()),
+            Some(Desired::Delete) => None,
+            None => self
+                .originals
+                .get(key)
+                .and_then(|original| original.value().map(str::to_string)),
+        }
+    }
+
+    /// 把当前原值表写入日志。
+    fn persist(&self) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.record(&self.originals),
+            None => Ok(()),
+        }
+    }
+
+    /// 为 app 进程打开一个处于前台的会话并立即应用。
+    ///
+    /// 首次被声明的属性先读取原值；读取失败时不做任何修改。
+    /// 应用过程中写入失败会关闭该会话，把已写入的属性恢复到其他会话或原值。
+    pub fn open(
+        &mut self,
+        package: &str,
+        pid: u32,
+        props: &HashMap<String, String>,
+        delete_props: &[String],
+    ) -> Result<SessionId> {
+        let mut desired: HashMap<String, Desired> = props
+            .iter()
+            .map(|(key, value)| (key.clone(), Desired::Set(value.clone())))
+            .collect();
+        for key in delete_props {
+            desired.insert(key.clone(), Desired::Delete);
+        }
+
+        let mut captured = HashMap::new();
+        for key in desired.keys() {
+            if !self.originals.contains_key(key) {
+                captured.insert(key.clone(), PropOriginal::from_read(self.backend.get(key)?));
+            }
+        }
+        if !captured.is_empty() {
+            let captured_keys: Vec<String> = captured.keys().cloned().collect();
+            self.originals.extend(captured);
+            // 原值必须先落盘再修改属性，否则异常退出后无从恢复。
+            if let Err(e) = self.persist() {
+                for key in &captured_keys {
+                    self.originals.remove(key);
+                }
+                return Err(e);
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let keys: Vec<String> = desired.keys().cloned().collect();
+        self.clock += 1;
+        self.sessions.insert(
+            id,
+            Session {
+                package: package.to_string(),
+                pid,
+                props: desired,
+                active: true,
+                activated_at: self.clock,
+            },
+        );
+
+        if let Err(e) = self.reconcile(&keys) {
+            self.close(id);
+            return Err(e);
+        }
+
+        info!(
+            "prop session {id} opened for {package} (pid {pid}, {} keys)",
+            keys.len()
+        );
+        Ok(id)
+    }
+
+    /// app 切换前台/后台时调用。已关闭的会话忽略。
+    pub fn set_active(&mut self, id: SessionId, active: bool) -> Result<()> {
+        let Some(session) = self.sessions.get_mut(&id) else {
+            return Ok(());
+        };
+        if session.active == active {
+            return Ok(());
+        }
+        session.active = active;
+        if active {
+            self.clock += 1;
+            session.activated_at = self.clock;
+        }
+        let keys: Vec<String> = session.props.keys().cloned().collect();
+        self.reconcile(&keys)
+    }
+
+    /// 关闭会话，不再被任何会话声明的属性写回原值。
+    pub fn close(&mut self, id: SessionId) {
+        let Some(session) = self.sessions.remove(&id) else {
+            return;
+        };
+        let keys: Vec<String> = session.props.into_keys().collect();
+        if let Err(e) = self.reconcile(&keys) {
+            error!(
+                "prop session {id} ({}) restore failed: {e}",
+                session.package
+            );
+        }
+        if let Err(e) = self.release(&keys) {
+            error!("prop session {id}: failed to update journal: {e}");
+        }
+        info!(
+            "prop session {id} closed for {} (pid {})",
+            session.package, session.pid
+        );
+    }
+
+    /// 关闭某个包名的全部会话。
+    pub fn close_package(&mut self, package: &str) {
+        self.close_matching(|_, session| session.package == package);
+    }
+
+    /// 关闭某个包名除 `keep` 以外的会话，用于新会话接替同一应用的旧会话。
+    pub fn close_package_except(&mut self, package: &str, keep: SessionId) {
+        self.close_matching(|id, session| id != keep && session.package == package);
+    }
+
+    /// 关闭全部会话。
+    pub fn close_all(&mut self) {
+        self.close_matching(|_, _| true);
+
+        // 恢复失败遗留的属性不属于任何会话，在这里重试写回
+        let leftover: Vec<String> = self.originals.keys().cloned().collect();
+        if leftover.is_empty() {
+            return;
+        }
+        if let Err(e) = self.reconcile(&leftover) {
+            error!("restoring leftover originals failed: {e}");
+        }
+        if let Err(e) = self.release(&leftover) {
+            error!("failed to update journal: {e}");
+        }
+    }
+
+    /// 从原值表移除不再被声明且已写回原值的属性，有变化时更新日志。
+    fn release(&mut self, keys: &[String]) -> Result<()> {
+        let released: Vec<&String> = keys
+            .iter()
+            .filter(|key| !self.is_claimed(key) && !self.applied.contains_key(*key))
+            .collect();
+        if released.is_empty() {
+            return Ok(());
+        }
+        for key in released {
+            self.originals.remove(key);
+        }
+        self.persist()
+    }
+
+    /// 仍保存在原值表中的属性数。关闭全部会话后不为 0 表示有属性写回失败。
+    pub fn pending_originals(&self) -> usize {
+        self.originals.len()
+    }
+
+    fn close_matching(&mut self, predicate: impl Fn(SessionId, &Session) -> bool) {
+        let ids: Vec<SessionId> = self
+            .sessions
+            .iter()
+            .filter(|(id, session)| predicate(**id, session))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.close(id);
+        }
+    }
+
+    /// 当前会话列表，按打开顺序排列。
+    pub fn status(&self) -> Vec<SessionStatus> {
+        let mut sessions: Vec<SessionStatus> = self
+            .sessions
+            .iter()
+            .map(|(id, session)| {
+                let mut keys: Vec<String> = session.props.keys().cloned().collect();
+                keys.sort();
+                let originals = keys
+                    .iter()
+                    .filter_map(|key| {
+                        let original = self.originals.get(key)?;
+                        Some((key.clone(), original.value().map(str::to_string)))
+                    })
+                    .collect();
+                SessionStatus {
+                    id: *id,
+                    package: session.package.clone(),
+                    pid: session.pid,
+                    active: session.active,
+                    keys,
+                    originals,
+                }
+            })
+            .collect();
+        sessions.sort_by_key(|session| session.id);
+        sessions
+    }
+
+    fn is_claimed(&self, key: &str) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.props.contains_key(key))
+    }
+
+    /// 当前应生效的伪装状态：最近切到前台且声明了该属性的会话。
+    fn winner(&self, key: &str) -> Option<&Desired> {
+        self.sessions
+            .values()
+            .filter(|session| session.active)
+            .filter_map(|session| session.props.get(key).map(|d| (session.activated_at, d)))
+            .max_by_key(|(activated_at, _)| *activated_at)
+            .map(|(_, desired)| desired)
+    }
+
+    fn write(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        match value {
+            Some(value) => self.backend.set(key, value),
+            None => self.backend.delete(key),
+        }
+    }
+
+    /// 提交一个写入批次，结束后重建一次涉及的属性区。
+    ///
+    /// `rollback` 为 true 时，任一写入失败会按相反顺序把已写入的属性改回 `previous`，
+    /// 使属性区保持批次开始前的状态；否则跳过失败项继续写入。返回第一个写入错误。
+    fn apply_batch(&mut self, writes: &[PropWrite], rollback: bool) -> Result<()> {
+        if writes.is_empty() {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        let mut written = Vec::new();
+        let mut first_err = None;
+        for write in writes {
+            match self.write(&write.key, write.value.as_deref()) {
+                Ok(()) => written.push(write),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                    if rollback {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if rollback && let Some(e) = &first_err {
+            warn!(
+                "prop batch failed after {}/{} writes, rolling back: {e}",
+                written.len(),
+                writes.len()
+            );
+            for write in written.iter().rev() {
+                if let Err(e) = self.write(&write.key, write.previous.as_deref()) {
+                    error!("rollback of {} failed: {e}", write.key);
+                }
+            }
+        }
+        let write_time = started.elapsed();
+
+        let touched: Vec<String> = written.iter().map(|write| write.key.clone()).collect();
+        if !touched.is_empty() {
+            self.backend.flush(&touched);
+        }
+        info!(
+            "prop batch: {}/{} writes in {:?}, rebuild in {:?}",
+            written.len(),
+            writes.len(),
+            write_time,
+            started.elapsed() - write_time
+        );
+
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// 把给定属性写成应有的状态，跳过已经一致的属性。
+    ///
+    /// 全部写入作为一个批次提交，失败时属性区与 `applied` 均保持不变。
+    fn reconcile(&mut self, keys: &[String]) -> Result<()> {
+        let mut writes = Vec::new();
+        let mut targets = Vec::new();
+
+        for key in keys {
+            let target = self.winner(key).cloned();
+            if self.applied.get(key) == target.as_ref() {
+                continue;
+            }
+
+            let value = match &target {
+                Some(Desired::Set(value)) => Some(value.clone()),
+                Some(Desired::Delete) => None,
+                // 无人声明时恢复原值，原本不存在的属性重新删除
+                None => match self.originals.get(key) {
+                    Some(original) => original.value().map(str::to_string),
+                    None => continue,
+                },
+            };
+            writes.push(PropWrite {
+                key: key.clone(),
+                value,
+                previous: self.current_value(key),
+            });
+            targets.push((key.clone(), target));
+        }
+
+        self.apply_batch(&writes, true)?;
+
+        for (key, target) in targets {
+            match target {
+                Some(desired) => self.applied.insert(key, desired),
+                None => self.applied.remove(&key),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// 批次中的一次写入，值为 `None` 表示删除。
+struct PropWrite {
+    key: String,
+    value: Option<String>,
+    /// 写入前的值，用于回滚
+    previous: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeBackend {
+        props: HashMap<String, String>,
+        fail_on: Option<String>,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl PropBackend for FakeBackend {
+        fn get(&mut self, key: &str) -> Result<Option<String>> {
+            Ok(self.props.get(key).cloned())
+        }
+
+        fn set(&mut self, key: &str, value: &str) -> Result<()> {
+            if self.fail_on.as_deref() == Some(key) {
+                anyhow::bail!("injected failure for {key}");
+            }
+            self.writes += 1;
+            self.props.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&mut self, key: &str) -> Result<()> {
+            self.writes += 1;
+            self.props.remove(key);
+            Ok(())
+        }
+
+        fn flush(&mut self, _keys: &[String]) {
+            self.flushes += 1;
+        }
+    }
+
+    fn manager() -> SessionManager<FakeBackend> {
+        let mut backend = FakeBackend::default();
+        backend
+            .props
+            .insert("ro.product.model".to_string(), "Real".to_string());
+        backend
+            .props
+            .insert("ro.product.brand".to_string(), "RealBrand".to_string());
+        SessionManager::new(backend)
+    }
+
+    fn props(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn model(manager: &SessionManager<FakeBackend>) -> Option<&str> {
+        manager
+            .backend
+            .props
+            .get("ro.product.model")
+            .map(String::as_str)
+    }
+
+    #[test]
+    fn overlapping_sessions_keep_true_original() {
+        let mut manager = manager();
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let b = manager
+            .open("com.b", 200, &props(&[("ro.product.model", "B")]), &[])
+            .unwrap();
+        assert_eq!(model(&manager), Some("B"));
+        assert_eq!(
+            manager.originals["ro.product.model"],
+            PropOriginal::Value("Real".to_string())
+        );
+
+        // 先关闭先打开的会话：仍由 B 生效
+        manager.close(a);
+        assert_eq!(model(&manager), Some("B"));
+
+        manager.close(b);
+        assert_eq!(model(&manager), Some("Real"));
+        assert!(manager.originals.is_empty());
+    }
+
+    #[test]
+    fn closing_in_open_order_also_restores_original() {
+        let mut manager = manager();
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let b = manager
+            .open("com.b", 200, &props(&[("ro.product.model", "B")]), &[])
+            .unwrap();
+
+        manager.close(b);
+        assert_eq!(model(&manager), Some("A"));
+        manager.close(a);
+        assert_eq!(model(&manager), Some("Real"));
+    }
+
+    #[test]
+    fn latest_foreground_session_wins() {
+        let mut manager = manager();
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let b = manager
+            .open("com.b", 200, &props(&[("ro.product.model", "B")]), &[])
+            .unwrap();
+
+        manager.set_active(b, false).unwrap();
+        assert_eq!(model(&manager), Some("A"));
+
+        manager.set_active(a, false).unwrap();
+        assert_eq!(model(&manager), Some("Real"));
+
+        manager.set_active(b, true).unwrap();
+        manager.set_active(a, true).unwrap();
+        assert_eq!(model(&manager), Some("A"));
+    }
+
+    #[test]
+    fn disjoint_keys_are_refcounted_independently() {
+        let mut manager = manager();
+        let a = manager
+            .open(
+                "com.a",
+                100,
+                &props(&[("ro.product.model", "A")]),
+                &["ro.product.brand".to_string()],
+            )
+            .unwrap();
+        let b = manager
+            .open("com.b", 200, &props(&[("ro.product.model", "B")]), &[])
+            .unwrap();
+        assert!(!manager.backend.props.contains_key("ro.product.brand"));
+
+        manager.close(a);
+        assert_eq!(
+            manager
+                .backend
+                .props
+                .get("ro.product.brand")
+                .map(String::as_str),
+            Some("RealBrand")
+        );
+        assert!(!manager.originals.contains_key("ro.product.brand"));
+        assert!(manager.originals.contains_key("ro.product.model"));
+
+        manager.close(b);
+        assert_eq!(model(&manager), Some("Real"));
+    }
+
+    #[test]
+    fn multiple_processes_of_one_package_are_closed_together() {
+        let mut manager = manager();
+        manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        manager
+            .open("com.a", 101, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+
+        manager.close_package("com.a");
+        assert_eq!(model(&manager), Some("Real"));
+        assert!(manager.sessions.is_empty());
+    }
+
+    #[test]
+    fn close_all_restores_every_original() {
+        let mut manager = manager();
+        manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        manager
+            .open("com.b", 200, &props(&[]), &["ro.product.brand".to_string()])
+            .unwrap();
+
+        manager.close_all();
+        assert_eq!(model(&manager), Some("Real"));
+        assert_eq!(
+            manager
+                .backend
+                .props
+                .get("ro.product.brand")
+                .map(String::as_str),
+            Some("RealBrand")
+        );
+        assert_eq!(manager.pending_originals(), 0);
+    }
+
+    #[test]
+    fn status_lists_sessions_in_open_order() {
+        let mut manager = manager();
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let b = manager
+            .open(
+                "com.b",
+                200,
+                &props(&[("ro.product.model", "B")]),
+                &["ro.product.brand".to_string()],
+            )
+            .unwrap();
+        manager.set_active(a, false).unwrap();
+
+        let status = manager.status();
+        assert_eq!(
+            status.iter().map(|s| (s.id, s.active)).collect::<Vec<_>>(),
+            vec![(a, false), (b, true)]
+        );
+        assert_eq!(status[1].keys, vec!["ro.product.brand", "ro.product.model"]);
+        assert_eq!(
+            status[1].originals.get("ro.product.model"),
+            Some(&Some("Real".to_string()))
+        );
+    }
+
+    #[test]
+    fn unchanged_state_is_not_rewritten() {
+        let mut manager = manager();
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let writes = manager.backend.writes;
+
+        manager.set_active(a, true).unwrap();
+        manager
+            .open("com.b", 200, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        assert_eq!(manager.backend.writes, writes);
+    }
+
+    #[test]
+    fn failed_open_rolls_back() {
+        let mut manager = manager();
+        manager.backend.fail_on = Some("ro.product.brand".to_string());
+
+        let result = manager.open(
+            "com.a",
+            100,
+            &props(&[("ro.product.model", "A"), ("ro.product.brand", "B")]),
+            &[],
+        );
+        assert!(result.is_err());
+        assert!(manager.sessions.is_empty());
+        assert_eq!(model(&manager), Some("Real"));
+    }
+
+    #[test]
+    fn failed_batch_rolls_back_to_previous_session_state() {
+        let mut manager = manager();
+        manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        manager.backend.fail_on = Some("ro.product.brand".to_string());
+        let flushes = manager.backend.flushes;
+
+        let result = manager.open(
+            "com.b",
+            200,
+            &props(&[("ro.product.model", "B"), ("ro.product.brand", "B")]),
+            &[],
+        );
+        assert!(result.is_err());
+        // 回滚到 A 的伪装值而不是原值，且整个批次只重建一次
+        assert_eq!(model(&manager), Some("A"));
+        assert_eq!(
+            manager.applied["ro.product.model"],
+            Desired::Set("A".into())
+        );
+        assert!(manager.backend.flushes - flushes <= 1);
+    }
+
+    #[test]
+    fn absent_and_empty_originals_are_restored_exactly() {
+        let mut manager = manager();
+        manager
+            .backend
+            .props
+            .insert("ro.boot.verifiedbootstate".to_string(), String::new());
+        let a = manager
+            .open(
+                "com.a",
+                100,
+                &props(&[
+                    ("ro.boot.verifiedbootstate", "green"),
+                    ("ro.boot.flash.locked", "1"),
+                ]),
+                &[],
+            )
+            .unwrap();
+
+        manager.close(a);
+        assert_eq!(
+            manager
+                .backend
+                .props
+                .get("ro.boot.verifiedbootstate")
+                .map(String::as_str),
+            Some("")
+        );
+        assert!(!manager.backend.props.contains_key("ro.boot.flash.locked"));
+    }
+
+    fn temp_journal(name: &str) -> (Journal, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "device_faker_session_{name}_{}.json",
+            std::process::id()
+        ));
+        (Journal::new(&path, "boot"), path)
+    }
+
+    #[test]
+    fn journal_tracks_originals_until_last_session_closes() {
+        let (journal, path) = temp_journal("tracks");
+        let mut manager = manager().with_journal(journal);
+
+        let a = manager
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let recorded = Journal::new(&path, "boot").pending().unwrap();
+        assert_eq!(
+            recorded["ro.product.model"],
+            PropOriginal::Value("Real".to_string())
+        );
+
+        manager.close(a);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn recover_restores_originals_left_by_crashed_companion() {
+        let (journal, path) = temp_journal("recover");
+        let mut crashed = manager().with_journal(journal);
+        crashed
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        // 模拟 companion 被杀：会话状态丢失，属性仍为伪装值
+        let mut backend = FakeBackend::default();
+        backend
+            .props
+            .insert("ro.product.model".to_string(), "A".to_string());
+
+        let (journal, _) = temp_journal("recover");
+        let mut restarted = SessionManager::new(backend).with_journal(journal);
+        assert_eq!(restarted.recover().unwrap(), 1);
+        assert_eq!(model(&restarted), Some("Real"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn failed_recovery_keeps_journaled_originals() {
+        let (journal, path) = temp_journal("recover_failed");
+        let mut crashed = manager().with_journal(journal);
+        crashed
+            .open("com.a", 100, &props(&[("ro.product.model", "A")]), &[])
+            .unwrap();
+        let mut backend = FakeBackend::default();
+        backend
+            .props
+            .insert("ro.product.model".to_string(), "A".to_string());
+        backend.fail_on = Some("ro.product.model".to_string());
+
+        let (journal, _) = temp_journal("recover_failed");
+        let mut restarted = SessionManager::new(backend).with_journal(journal);
+        assert!(restarted.recover().is_err());
+
+        // 新会话不能把残留的伪装值当作原值写进日志
+        restarted.backend.fail_on = None;
+        let b = restarted
+            .open("com.b", 200, &props(&[("ro.product.model", "B")]), &[])
+            .unwrap();
+        let recorded = Journal::new(&path, "boot").pending().unwrap();
+        assert_eq!(
+            recorded["ro.product.model"],
+            PropOriginal::Value("Real".to_string())
+        );
+
+        restarted.close(b);
+        assert_eq!(model(&restarted), Some("Real"));
+        assert!(!path.exists());
+    }
+}
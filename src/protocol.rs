@@ -0,0 +1,378 @@
+// 模块与 companion 之间的线协议：帧格式、版本握手与错误码。
+//
+// 帧格式为 4 字节小端长度前缀 + JSON 负载。每条 companion 连接先交换一次
+// `Hello`，双方协议版本一致后才发送实际请求。本文件只依赖 std 与 serde，
+// 以便 fuzz/ 下的模糊测试直接通过 `#[path]` 引入。
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// 协议版本，请求/响应格式发生不兼容变化时递增。
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// companion 控制 socket，供 device_faker_cli 使用（见 control.rs）。
+pub const CONTROL_SOCKET_PATH: &str = "/data/adb/device_faker/control.sock";
+
+/// 单帧负载上限。最大的正常负载是日志批量写入与自定义 cpuinfo，远小于此值；
+/// 对端声明的长度超过上限时直接拒绝，不做分配。
+pub const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+/// 握手消息：发送方的协议版本与支持的能力列表。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u32,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl Hello {
+    pub fn new(capabilities: &[&str]) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// companion 返回的错误类别。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 帧或 JSON 无法解析、缺少握手
+    InvalidRequest,
+    /// 双方协议版本不一致（通常是更新模块后未重启）
+    UnsupportedVersion,
+    /// companion 不支持请求所需的能力
+    MissingCapability,
+    /// 属性修改或恢复失败
+    PropertyFailed,
+    /// 覆盖文件准备或挂载失败
+    OverlayFailed,
+    /// 日志写入失败
+    LogFailed,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::UnsupportedVersion => "unsupported_version",
+            Self::MissingCapability => "missing_capability",
+            Self::PropertyFailed => "property_failed",
+            Self::OverlayFailed => "overlay_failed",
+            Self::LogFailed => "log_failed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 带错误码的 companion 错误。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CompanionError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CompanionError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CompanionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "companion error [{}]: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CompanionError {}
+
+/// 一个属性会话。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionStatus {
+    pub id: u64,
+    pub package: String,
+    pub pid: u32,
+    /// 是否处于前台（伪装值生效）
+    pub active: bool,
+    /// 会话声明的属性
+    pub keys: Vec<String>,
+    /// 这些属性的真实原值，`None` 表示原本不存在
+    #[serde(default)]
+    pub originals: BTreeMap<String, Option<String>>,
+}
+
+/// supervisor 监控的一个应用进程组。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WatcherStatus {
+    pub package: String,
+    pub uid: u32,
+    pub pids: Vec<u32>,
+    /// 进程组当前使用的会话
+    pub session: u64,
+    pub restore_policy: String,
+}
+
+/// 挂载到某个 app namespace 的覆盖文件。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MountStatus {
+    pub pid: u32,
+    /// app 当前的 mount namespace inode，app 已退出时为 `None`
+    #[serde(default)]
+    pub ns_ino: Option<u64>,
+    pub sources: Vec<String>,
+    pub targets: Vec<String>,
+    /// namespace 切换或挂载被移除后 supervisor 重新挂载的次数
+    #[serde(default)]
+    pub remounts: u32,
+}
+
+/// 覆盖文件挂载后的自检结果，随 `CpuSpoof` 的应答返回。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MountCheck {
+    pub target: String,
+    /// 经 app 的 namespace 读到的内容哈希与源文件一致
+    pub content_matches: bool,
+    /// mountinfo 中目标路径上最顶层的挂载来自源文件
+    pub mountinfo_ok: bool,
+    /// 源文件的 SELinux label
+    #[serde(default)]
+    pub label: Option<String>,
+    /// app 域能否读取该 label；selinuxfs 不可查询时为 `None`
+    #[serde(default)]
+    pub label_readable: Option<bool>,
+}
+
+impl MountCheck {
+    pub fn passed(&self) -> bool {
+        self.content_matches && self.mountinfo_ok && self.label_readable != Some(false)
+    }
+}
+
+impl fmt::Display for MountCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut problems = Vec::new();
+        if !self.content_matches {
+            problems.push("content differs from source".to_string());
+        }
+        if !self.mountinfo_ok {
+            problems.push("not the topmost mount in mountinfo".to_string());
+        }
+        if self.label_readable == Some(false) {
+            let label = self.label.as_deref().unwrap_or("<none>");
+            problems.push(format!("label {label} not readable by apps"));
+        }
+        if problems.is_empty() {
+            write!(f, "{}: ok", self.target)
+        } else {
+            write!(f, "{}: {}", self.target, problems.join(", "))
+        }
+    }
+}
+
+/// `Status` 请求的应答。
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompanionStatus {
+    #[serde(default)]
+    pub sessions: Vec<SessionStatus>,
+    #[serde(default)]
+    pub watchers: Vec<WatcherStatus>,
+    #[serde(default)]
+    pub mounts: Vec<MountStatus>,
+}
+
+/// companion 响应。旧版本的 `status`/`message` 字段会因 `deny_unknown_fields`
+/// 解析失败，从而把版本不一致暴露为握手错误而不是被误判为成功。
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CompanionResponse {
+    #[serde(default)]
+    pub error: Option<CompanionError>,
+    /// 握手应答
+    #[serde(default)]
+    pub hello: Option<Hello>,
+    /// `Status` 请求的应答
+    #[serde(default)]
+    pub companion_status: Option<CompanionStatus>,
+    /// `CpuSpoof` 请求的挂载自检结果
+    #[serde(default)]
+    pub overlay_checks: Option<Vec<MountCheck>>,
+}
+
+impl CompanionResponse {
+    pub fn ok() -> Self {
+        Self::default()
+    }
+
+    pub fn err(code: ErrorCode, msg: impl Into<String>) -> Self {
+        Self {
+            error: Some(CompanionError::new(code, msg)),
+            ..Self::default()
+        }
+    }
+
+    pub fn hello(hello: Hello) -> Self {
+        Self {
+            hello: Some(hello),
+            ..Self::default()
+        }
+    }
+
+    /// 响应带有错误时返回 `Err`。
+    pub fn into_result(self) -> Result<Self, CompanionError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self),
+        }
+    }
+}
+
+/// 读帧失败的原因。
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    Empty,
+    TooLarge(usize),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "frame I/O error: {e}"),
+            Self::Empty => f.write_str("empty frame"),
+            Self::TooLarge(len) => {
+                write!(f, "frame of {len} bytes exceeds limit of {MAX_FRAME_SIZE}")
+            }
+            Self::Json(e) => write!(f, "malformed frame payload: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FrameError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// 读取一帧原始负载。长度为 0 或超过 `MAX_FRAME_SIZE` 时不读取负载直接返回错误。
+pub fn read_frame(reader: &mut impl Read) -> Result<Vec<u8>, FrameError> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf)?;
+    let len = u32::from_le_bytes(len_buf) as usize;
+    if len == 0 {
+        return Err(FrameError::Empty);
+    }
+    if len > MAX_FRAME_SIZE {
+        return Err(FrameError::TooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// 写入一帧原始负载。
+pub fn write_frame(writer: &mut impl Write, payload: &[u8]) -> Result<(), FrameError> {
+    if payload.is_empty() {
+        return Err(FrameError::Empty);
+    }
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(FrameError::TooLarge(payload.len()));
+    }
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// 读取一帧并按 JSON 解码。
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> Result<T, FrameError> {
+    let payload = read_frame(reader)?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// 按 JSON 编码后写入一帧。
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<(), FrameError> {
+    let payload = serde_json::to_vec(message)?;
+    write_frame(writer, &payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn frame_roundtrip() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &Hello::new(&["apply"])).unwrap();
+
+        let hello: Hello = read_message(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(hello.version, PROTOCOL_VERSION);
+        assert!(hello.supports("apply"));
+        assert!(!hello.supports("restore"));
+    }
+
+    #[test]
+    fn oversized_length_is_rejected_without_reading_payload() {
+        let mut buf = u32::MAX.to_le_bytes().to_vec();
+        buf.extend_from_slice(b"{}");
+
+        match read_frame(&mut Cursor::new(buf)) {
+            Err(FrameError::TooLarge(len)) => assert_eq!(len, u32::MAX as usize),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_and_truncated_frames_are_errors() {
+        assert!(matches!(
+            read_frame(&mut Cursor::new(0u32.to_le_bytes())),
+            Err(FrameError::Empty)
+        ));
+
+        let mut truncated = 8u32.to_le_bytes().to_vec();
+        truncated.extend_from_slice(b"{}");
+        assert!(matches!(
+            read_frame(&mut Cursor::new(truncated)),
+            Err(FrameError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn error_code_serializes_as_snake_case() {
+        let error = CompanionError::new(ErrorCode::UnsupportedVersion, "v2 != v1");
+        let json = serde_json::to_string(&error).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"code":"unsupported_version","message":"v2 != v1"}"#
+        );
+        assert_eq!(
+            error.to_string(),
+            "companion error [unsupported_version]: v2 != v1"
+        );
+    }
+}
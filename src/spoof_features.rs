@@ -0,0 +1,149 @@
+// 可组合的伪装功能。
+//
+// `mode` 只是预设：每个 app 实际启用的功能由模式预设加上 `spoof_features` 中逐项覆盖
+// 得到，`do_handle_app_specialize` 和 companion 请求都只按功能分派，不再区分模式。
+
+use anyhow::Result;
+
+use crate::config::{MergedAppConfig, NativePropBackend, SpoofFeatureOverrides};
+
+/// 一个 app 启用的伪装功能。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpoofFeatures {
+    /// 改写 Build 类静态字段
+    pub build_fields: bool,
+    /// SystemProperties 及内存、存储、屏幕、系统特性的 Java hook，模块常驻
+    pub java_prop_hook: bool,
+    /// `__system_property_get` 等 native 属性读取及 GPU 标识，方式见 `native_prop_backend`
+    pub native_prop_hook: bool,
+    /// 通过 companion resetprop 修改全局属性
+    pub resetprop: bool,
+    /// 通过 companion 挂载伪装的 /proc/cpuinfo
+    pub cpuinfo: bool,
+    /// 通过 companion 挂载 /proc/meminfo、电池与温控区 sysfs 文件
+    pub overlays: bool,
+    /// 属性写入 app 私有的属性区副本，不修改全局属性
+    pub isolated_props: bool,
+    /// `native_prop_hook` 的实现方式
+    pub native_prop_backend: NativePropBackend,
+}
+
+impl SpoofFeatures {
+    /// 模式名对应的预设。未知模式返回错误，加载配置时即被拒绝。
+    pub fn preset(mode: &str) -> Result<Self> {
+        let lite = Self {
+            build_fields: true,
+            cpuinfo: true,
+            ..Self::default()
+        };
+        Ok(match mode {
+            "lite" => lite,
+            "full" => Self {
+                java_prop_hook: true,
+                native_prop_hook: true,
+                ..lite
+            },
+            "companion" => Self {
+                resetprop: true,
+                overlays: true,
+                ..lite
+            },
+            "isolated" => Self {
+                overlays: true,
+                isolated_props: true,
+                ..lite
+            },
+            other => {
+                anyhow::bail!("unknown mode '{other}' (expected lite, full, companion or isolated)")
+            }
+        })
+    }
+
+    /// `mode` 预设加上 `spoof_features` 覆盖。冲突的组合返回错误，加载配置时即被拒绝。
+    pub fn resolve(mode: &str, overrides: &SpoofFeatureOverrides) -> Result<Self> {
+        let features = Self::preset(mode)?.with_overrides(overrides);
+        // 同时启用时 resetprop 会让伪装值泄漏到全局，与隔离的目的相反
+        if features.resetprop && features.isolated_props {
+            anyhow::bail!("spoof_features: resetprop cannot be combined with isolated_props");
+        }
+        Ok(features)
+    }
+
+    /// app 的最终功能。
+    pub fn for_app(merged: &MergedAppConfig) -> Result<Self> {
+        Ok(Self {
+            native_prop_backend: merged.native_prop_backend,
+            ..Self::resolve(&merged.mode, &merged.spoof_features)?
+        })
+    }
+
+    fn with_overrides(self, overrides: &SpoofFeatureOverrides) -> Self {
+        Self {
+            build_fields: overrides.build_fields.unwrap_or(self.build_fields),
+            java_prop_hook: overrides.java_prop_hook.unwrap_or(self.java_prop_hook),
+            native_prop_hook: overrides.native_prop_hook.unwrap_or(self.native_prop_hook),
+            resetprop: overrides.resetprop.unwrap_or(self.resetprop),
+            cpuinfo: overrides.cpuinfo.unwrap_or(self.cpuinfo),
+            overlays: overrides.overlays.unwrap_or(self.overlays),
+            isolated_props: overrides.isolated_props.unwrap_or(self.isolated_props),
+            native_prop_backend: self.native_prop_backend,
+        }
+    }
+
+    /// native 属性是否通过 PLT hook 伪装。
+    pub fn native_plt_hook(&self) -> bool {
+        self.native_prop_hook && self.native_prop_backend == NativePropBackend::PltHook
+    }
+
+    /// 是否有 hook 需要模块常驻内存；否则伪装完成后卸载模块。
+    pub fn resident(&self) -> bool {
+        self.java_prop_hook || self.native_plt_hook()
+    }
+
+    /// 属性是否通过 companion 挂载的私有属性区副本提供。`property_area` 方式的
+    /// native 属性伪装同样使用副本。
+    pub fn property_area_copies(&self) -> bool {
+        self.isolated_props || (self.native_prop_hook && !self.native_plt_hook())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_apply_on_top_of_mode_preset() {
+        let overrides = SpoofFeatureOverrides {
+            cpuinfo: Some(false),
+            isolated_props: Some(true),
+            ..SpoofFeatureOverrides::default()
+        };
+        let err = SpoofFeatures::resolve("companion", &overrides).unwrap_err();
+        assert!(err.to_string().contains("isolated_props"));
+
+        let overrides = SpoofFeatureOverrides {
+            resetprop: Some(false),
+            ..overrides
+        };
+        let features = SpoofFeatures::resolve("companion", &overrides).unwrap();
+        assert_eq!(
+            features,
+            SpoofFeatures {
+                build_fields: true,
+                overlays: true,
+                isolated_props: true,
+                ..SpoofFeatures::default()
+            }
+        );
+        assert!(!features.resident());
+
+        let full = SpoofFeatures::preset("full").unwrap();
+        assert!(full.resident() && !full.property_area_copies());
+        let native_only = SpoofFeatures {
+            java_prop_hook: false,
+            native_prop_backend: NativePropBackend::PropertyArea,
+            ..full
+        };
+        assert!(!native_only.resident() && native_only.property_area_copies());
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
-    sync::{LazyLock, Mutex, atomic::AtomicBool},
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicU64},
+    },
 };
 
 /// 用于恢复真实属性值的 native_get 原始函数签名。
@@ -11,14 +14,23 @@ pub type OriginalNativeGet = unsafe extern "C" fn(
     def: jni::sys::jstring,
 ) -> jni::sys::jstring;
 
+/// 用于转发真实查询的 libcore.io.Linux.statvfs 原始函数签名。
+pub type OriginalStatvfs = unsafe extern "C" fn(
+    env: *mut jni::sys::JNIEnv,
+    this: jni::sys::jobject,
+    path: jni::sys::jstring,
+) -> jni::sys::jobject;
+
 pub static FAKE_PROPS: LazyLock<Mutex<HashMap<String, String>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 pub static IS_FULL_MODE: AtomicBool = AtomicBool::new(false);
 pub static ACTIVE_RESET_SESSION: Mutex<Option<ActiveResetSession>> = Mutex::new(None);
 pub static ORIGINAL_NATIVE_GET: Mutex<Option<OriginalNativeGet>> = Mutex::new(None);
+pub static FAKE_TOTAL_MEMORY_BYTES: AtomicU64 = AtomicU64::new(0);
+pub static FAKE_STORAGE_TOTAL_BYTES: AtomicU64 = AtomicU64::new(0);
+pub static ORIGINAL_STATVFS: Mutex<Option<OriginalStatvfs>> = Mutex::new(None);
 
 #[derive(Clone)]
 pub struct ActiveResetSession {
     pub package: String,
-    pub backups: HashMap<String, String>,
 }
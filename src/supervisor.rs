@@ -0,0 +1,909 @@
+// companion 常驻监控线程。
+//
+// companion 进程在一个开机周期内常驻。此前每个应用各占一个 restore watcher 线程，
+// 每次覆盖文件挂载还各有一个阻塞到 app 退出的连接线程与 mount 子进程，数量随应用
+// 切换不断增长。现在由一个线程在单个 epoll 循环中监听：
+// - 所有被跟踪进程的 pidfd：属性进程组成员、覆盖文件所属 app
+// - 进程组成员 /proc/<pid>/oom_score_adj 的 inotify
+// - 覆盖文件所属 app 的 /proc/<pid>/mountinfo（挂载表变化时 EPOLLPRI）
+// - 读取后卸载的 cpuinfo 源文件的 inotify（app 读完关闭时卸载）
+// - 唤醒 eventfd：请求处理线程修改共享表后写入
+//
+// 覆盖文件挂载后，KernelSU 等可能稍后才切换 app 的 mount namespace 或卸载其中的
+// 挂载，慢设备上可能远超固定的观察窗口。因此在 app 整个生命周期内跟踪：mountinfo
+// 变化时核对挂载是否仍在，namespace inode 按退避间隔检查，切换后重新挂载。
+//
+// 监听集合完全由共享表（`PROCESS_REGISTRY` 与 `OVERLAYS`）推导，循环每轮把 fd 与表
+// 对齐，因此请求处理线程只需修改表并调用 `wake`。内核不支持 pidfd 或 inotify 时，
+// 相应对象退化为按轮询间隔检查。
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Read, Seek},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    sync::{LazyLock, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use log::{error, info, warn};
+
+use crate::companion::{PROCESS_REGISTRY, close_session, set_session_active};
+use crate::config::RestorePolicy;
+use crate::cpu_spoof::{OverlayMount, mount_overlays};
+use crate::mount_child::read_ns_ino;
+use crate::mount_verify::mounted_from;
+use crate::process_registry::GroupId;
+use crate::prop_session::SessionId;
+use crate::protocol::MountStatus;
+
+/// 无事件源可用时的检查间隔。
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// namespace inode 检查的退避区间：切换后从最短间隔重新开始。
+const NS_CHECK_MIN_INTERVAL: Duration = Duration::from_millis(25);
+const NS_CHECK_MAX_INTERVAL: Duration = Duration::from_secs(5);
+
+const INOTIFY_TOKEN: u64 = u64::MAX;
+const WAKER_TOKEN: u64 = u64::MAX - 1;
+/// mountinfo 的 token 为该位与 app pid 的组合
+const MOUNTINFO_TOKEN_FLAG: u64 = 1 << 32;
+
+/// 已挂载到 app namespace 的覆盖文件，app 退出后删除源文件。
+struct TrackedOverlays {
+    mounts: Vec<OverlayMount>,
+    /// 最近一次挂载时 app 所在 namespace 的 inode
+    ns_ino: u64,
+    /// supervisor 重新挂载的次数
+    remounts: u32,
+    /// app 读取后卸载的目标路径，卸载后清空
+    unmount_after_read: Option<String>,
+}
+
+/// app pid → 覆盖文件
+static OVERLAYS: Mutex<BTreeMap<u32, TrackedOverlays>> = Mutex::new(BTreeMap::new());
+
+/// 唤醒 eventfd。首次访问时启动监控线程；eventfd 创建失败时监控线程按轮询间隔同步。
+static WAKER: LazyLock<Option<OwnedFd>> = LazyLock::new(|| {
+    let waker = owned_fd(
+        unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) },
+        "eventfd",
+    )
+    .inspect_err(|e| warn!("supervisor: {e}, falling back to polling"))
+    .ok();
+    let waker_fd = waker.as_ref().map(AsRawFd::as_raw_fd);
+
+    if let Err(e) = thread::Builder::new()
+        .name("df-supervisor".to_string())
+        .spawn(move || run(waker_fd))
+    {
+        error!("Failed to start supervisor thread: {e}");
+    }
+    waker
+});
+
+/// 通知监控线程重新同步共享表，监控线程未运行时启动它。
+pub(crate) fn wake() {
+    if let Some(waker) = &*WAKER {
+        let value = 1u64;
+        unsafe {
+            libc::write(
+                waker.as_raw_fd(),
+                &value as *const u64 as *const libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+    }
+}
+
+/// 登记已挂载到 namespace `ns_ino` 的覆盖文件，由监控线程负责跟随 namespace 切换与清理。
+///
+/// `unmount_after_read` 为目标路径时，app 读取该文件后卸载对应挂载且不再重新挂载，
+/// 其源文件须为 app 独占。
+pub(crate) fn track_overlays(
+    pid: u32,
+    mounts: Vec<OverlayMount>,
+    ns_ino: u64,
+    unmount_after_read: Option<String>,
+) {
+    OVERLAYS.lock().unwrap().insert(
+        pid,
+        TrackedOverlays {
+            mounts,
+            ns_ino,
+            remounts: 0,
+            unmount_after_read,
+        },
+    );
+    wake();
+}
+
+/// 当前登记的覆盖文件挂载。
+pub(crate) fn overlay_status() -> Vec<MountStatus> {
+    OVERLAYS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(pid, tracked)| MountStatus {
+            pid: *pid,
+            ns_ino: read_ns_ino(*pid),
+            sources: tracked.mounts.iter().map(|m| m.source.clone()).collect(),
+            targets: tracked.mounts.iter().map(|m| m.target.clone()).collect(),
+            remounts: tracked.remounts,
+        })
+        .collect()
+}
+
+fn run(waker: Option<RawFd>) {
+    let mut supervisor = match Supervisor::new(waker) {
+        Ok(supervisor) => supervisor,
+        Err(e) => {
+            error!("Supervisor failed to start: {e}");
+            return;
+        }
+    };
+    info!("supervisor started (inotify: {})", supervisor.ifd.is_some());
+
+    loop {
+        if let Err(e) = supervisor.tick() {
+            error!("supervisor: {e}");
+            thread::sleep(DEFAULT_POLL_INTERVAL);
+        }
+    }
+}
+
+/// 按还原策略得到的前后台判定参数。
+#[derive(Clone, Copy)]
+struct BackgroundRule {
+    /// oom_score_adj 不低于该值视为后台
+    threshold: i32,
+    /// 持续处于后台超过该时长才还原
+    debounce: Duration,
+}
+
+impl BackgroundRule {
+    /// 判定参数与轮询间隔。on_exit_only 不判定前后台，只在进程退出时还原。
+    fn from_policy(policy: RestorePolicy) -> (Option<Self>, Duration) {
+        match policy {
+            RestorePolicy::OnBackground {
+                threshold,
+                debounce_ms,
+                poll_interval_ms,
+            } => (
+                Some(Self {
+                    threshold,
+                    debounce: Duration::from_millis(debounce_ms),
+                }),
+                Duration::from_millis(poll_interval_ms.max(1)),
+            ),
+            // never 策略不登记进程组
+            RestorePolicy::OnExitOnly | RestorePolicy::Never => (None, DEFAULT_POLL_INTERVAL),
+        }
+    }
+}
+
+/// 监控线程每轮从登记表读取的进程组状态。
+struct GroupSnapshot {
+    package: String,
+    id: GroupId,
+    pids: BTreeSet<u32>,
+    session: SessionId,
+    policy: RestorePolicy,
+}
+
+/// 读取进程组状态，同时取走请求处理线程打开的 pidfd。
+fn snapshot_groups() -> (Vec<GroupSnapshot>, HashMap<u32, OwnedFd>) {
+    let mut opened = HashMap::new();
+    let groups = PROCESS_REGISTRY
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .map(|(package, group)| {
+            opened.extend(group.pidfds.drain());
+            GroupSnapshot {
+                package: package.clone(),
+                id: group.id,
+                pids: group.pids.clone(),
+                session: group.session,
+                policy: group.policy,
+            }
+        })
+        .collect();
+    (groups, opened)
+}
+
+/// 进程组整体的前后台状态。
+struct GroupState {
+    session: SessionId,
+    is_spoof_applied: bool,
+    background_since: Option<Instant>,
+}
+
+impl GroupState {
+    fn new(session: SessionId) -> Self {
+        Self {
+            session,
+            is_spoof_applied: true,
+            background_since: None,
+        }
+    }
+
+    /// 跟随接替的新会话。新会话打开时处于前台。
+    fn follow(&mut self, session: SessionId) {
+        if self.session != session {
+            *self = Self::new(session);
+        }
+    }
+
+    /// 按进程组是否整体处于后台更新会话，返回 debounce 的剩余时间。
+    fn update(
+        &mut self,
+        package: &str,
+        in_background: bool,
+        rule: Option<BackgroundRule>,
+    ) -> Result<Option<Duration>> {
+        let Some(rule) = rule.filter(|_| in_background) else {
+            self.background_since = None;
+            if !self.is_spoof_applied {
+                set_session_active(self.session, true)?;
+                self.is_spoof_applied = true;
+                info!("supervisor re-applied spoof props for {package}");
+            }
+            return Ok(None);
+        };
+
+        let since = *self.background_since.get_or_insert_with(Instant::now);
+        let remaining = rule.debounce.saturating_sub(since.elapsed());
+        if !remaining.is_zero() {
+            return Ok(Some(remaining));
+        }
+        if self.is_spoof_applied {
+            set_session_active(self.session, false)?;
+            self.is_spoof_applied = false;
+            info!("supervisor restored props for {package}");
+        }
+        Ok(None)
+    }
+}
+
+struct Supervisor {
+    efd: OwnedFd,
+    /// inotify 不可用时为 `None`，进程组改用 cgroup 轮询判断前后台
+    ifd: Option<OwnedFd>,
+    waker: Option<RawFd>,
+    /// 被跟踪进程的 pidfd；值为 `None` 表示内核不支持 pidfd，按轮询检查存活
+    pidfds: HashMap<u32, Option<OwnedFd>>,
+    /// 进程组成员 oom_score_adj 的 inotify watch
+    oom_watches: HashMap<u32, u32>,
+    groups: HashMap<GroupId, GroupState>,
+    /// 覆盖文件所属 app 的挂载跟踪
+    mount_watches: HashMap<u32, MountWatch>,
+    /// 读取后卸载的源文件的 inotify watch → app pid
+    read_watches: HashMap<i32, u32>,
+    /// 已读取、待卸载的 app
+    read_apps: Vec<u32>,
+}
+
+/// 一个 app 的覆盖文件挂载跟踪状态。
+struct MountWatch {
+    /// 当前 namespace 的 /proc/<pid>/mountinfo，打开失败时只按间隔检查 namespace
+    mountinfo: Option<File>,
+    next_check: Instant,
+    backoff: Duration,
+    /// 挂载表有变化，需要核对挂载是否仍在
+    dirty: bool,
+    /// 重新挂载后 mountinfo 中仍找不到挂载时置 false，此后只在 namespace 切换时重新挂载
+    verify: bool,
+}
+
+impl Supervisor {
+    fn new(waker: Option<RawFd>) -> Result<Self> {
+        let efd = owned_fd(
+            unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) },
+            "epoll_create1",
+        )?;
+        if let Some(waker) = waker {
+            epoll_add(efd.as_raw_fd(), waker, WAKER_TOKEN)?;
+        }
+        // 优先使用 inotify 监听 oom_score_adj（事件驱动，零轮询）。
+        // 回退到 /proc/<pid>/cgroup 轮询（inotify 在部分设备/内核上不可用）。
+        let ifd = owned_fd(
+            unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) },
+            "inotify_init1",
+        )
+        .and_then(|ifd| {
+            epoll_add(efd.as_raw_fd(), ifd.as_raw_fd(), INOTIFY_TOKEN)?;
+            Ok(ifd)
+        })
+        .inspect_err(|e| warn!("inotify on oom_score_adj unavailable ({e}), using cgroup polling"))
+        .ok();
+
+        Ok(Self {
+            efd,
+            ifd,
+            waker,
+            pidfds: HashMap::new(),
+            oom_watches: HashMap::new(),
+            groups: HashMap::new(),
+            mount_watches: HashMap::new(),
+            read_watches: HashMap::new(),
+            read_apps: Vec::new(),
+        })
+    }
+
+    /// 同步监听集合、更新进程组状态，然后阻塞到下一个事件或超时。
+    fn tick(&mut self) -> Result<()> {
+        let (groups, opened) = snapshot_groups();
+        let apps: BTreeSet<u32> = OVERLAYS.lock().unwrap().keys().copied().collect();
+
+        let group_pids: BTreeSet<u32> =
+            groups.iter().flat_map(|g| g.pids.iter().copied()).collect();
+        let mut tracked = group_pids.clone();
+        tracked.extend(apps.iter().copied());
+
+        let exited = self.sync_pidfds(&tracked, opened);
+        if !exited.is_empty() {
+            // 表已变化，下一轮重新同步
+            handle_exits(&exited);
+            self.forget(&exited);
+            return Ok(());
+        }
+        self.sync_oom_watches(&group_pids);
+        self.sync_mount_watches(&apps);
+        self.sync_read_watches();
+        for pid in std::mem::take(&mut self.read_apps) {
+            self.unmount_after_read(pid);
+        }
+
+        let mut timeout = self.waker.map_or(Some(DEFAULT_POLL_INTERVAL), |_| None);
+        if let Some(next_check) = self.check_mounts() {
+            timeout = min_timeout(timeout, next_check);
+        }
+        if self.pidfds.values().any(Option::is_none) {
+            timeout = min_timeout(timeout, DEFAULT_POLL_INTERVAL);
+        }
+
+        for group in &groups {
+            if group.pids.is_empty() {
+                close_if_all_exited(&group.package, group.id);
+                continue;
+            }
+            let state = self
+                .groups
+                .entry(group.id)
+                .or_insert_with(|| GroupState::new(group.session));
+            state.follow(group.session);
+
+            let (rule, poll_interval) = BackgroundRule::from_policy(group.policy);
+            let event_driven = group
+                .pids
+                .iter()
+                .all(|pid| self.oom_watches.contains_key(pid));
+            let in_background = rule.is_some_and(|rule| {
+                if event_driven {
+                    group
+                        .pids
+                        .iter()
+                        .all(|&pid| read_oom_score_adj(pid).unwrap_or(0) >= rule.threshold)
+                } else {
+                    // 不在 top-app 且 oom_score_adj 达到阈值（无法读取时不作要求）才视为后台
+                    group.pids.iter().all(|&pid| {
+                        !is_process_in_top_app(pid)
+                            && read_oom_score_adj(pid).is_none_or(|oom| oom >= rule.threshold)
+                    })
+                }
+            });
+            if rule.is_some() && !event_driven {
+                timeout = min_timeout(timeout, poll_interval);
+            }
+
+            match state.update(&group.package, in_background, rule) {
+                Ok(Some(remaining)) => timeout = min_timeout(timeout, remaining),
+                Ok(None) => {}
+                Err(e) => error!("supervisor: failed to update {}: {e}", group.package),
+            }
+        }
+        self.groups
+            .retain(|id, _| groups.iter().any(|group| group.id == *id));
+
+        let exited = self.wait(timeout)?;
+        if !exited.is_empty() {
+            handle_exits(&exited);
+            self.forget(&exited);
+        }
+        Ok(())
+    }
+
+    /// 为新进程打开 pidfd（优先使用 `opened` 中预先打开的）、关闭不再跟踪的 pidfd，
+    /// 返回已退出的进程。
+    fn sync_pidfds(
+        &mut self,
+        tracked: &BTreeSet<u32>,
+        mut opened: HashMap<u32, OwnedFd>,
+    ) -> Vec<u32> {
+        self.pidfds.retain(|pid, _| tracked.contains(pid));
+
+        let mut exited = Vec::new();
+        for &pid in tracked {
+            match self.pidfds.get(&pid) {
+                Some(Some(_)) => continue,
+                Some(None) => {
+                    if process_exited(pid) {
+                        exited.push(pid);
+                    }
+                    continue;
+                }
+                None => {}
+            }
+
+            let pidfd = match opened.remove(&pid).map_or_else(|| pidfd_open(pid), Ok) {
+                Ok(pidfd) => pidfd,
+                Err(err) if err.raw_os_error() == Some(libc::ESRCH) => {
+                    exited.push(pid);
+                    continue;
+                }
+                Err(err) => {
+                    warn!("supervisor: pidfd_open({pid}) failed ({err}), polling /proc");
+                    self.pidfds.insert(pid, None);
+                    continue;
+                }
+            };
+            match epoll_add(self.efd.as_raw_fd(), pidfd.as_raw_fd(), pid as u64) {
+                Ok(()) => {
+                    self.pidfds.insert(pid, Some(pidfd));
+                }
+                Err(e) => {
+                    warn!("supervisor: {e}, polling /proc for pid {pid}");
+                    self.pidfds.insert(pid, None);
+                }
+            }
+        }
+        exited
+    }
+
+    /// 为进程组成员添加 oom_score_adj watch、移除已离开的成员的 watch。
+    fn sync_oom_watches(&mut self, pids: &BTreeSet<u32>) {
+        let Some(ifd) = self.ifd.as_ref().map(AsRawFd::as_raw_fd) else {
+            return;
+        };
+        self.oom_watches.retain(|pid, wd| {
+            let keep = pids.contains(pid);
+            if !keep {
+                let wd = *wd;
+                unsafe { libc::inotify_rm_watch(ifd, wd) };
+            }
+            keep
+        });
+        for &pid in pids {
+            if self.oom_watches.contains_key(&pid) {
+                continue;
+            }
+            let Ok(path) = CString::new(format!("/proc/{pid}/oom_score_adj")) else {
+                continue;
+            };
+            let wd = unsafe { libc::inotify_add_watch(ifd, path.as_ptr(), libc::IN_MODIFY) };
+            if wd >= 0 {
+                self.oom_watches.insert(pid, wd as u32);
+            }
+        }
+    }
+
+    /// 为新登记的 app 打开 mountinfo、移除已不再跟踪的 app。
+    fn sync_mount_watches(&mut self, apps: &BTreeSet<u32>) {
+        self.mount_watches.retain(|pid, _| apps.contains(pid));
+        for &pid in apps {
+            if !self.mount_watches.contains_key(&pid) {
+                let watch = MountWatch {
+                    mountinfo: self.open_mountinfo(pid),
+                    next_check: Instant::now(),
+                    backoff: NS_CHECK_MIN_INTERVAL,
+                    dirty: true,
+                    verify: true,
+                };
+                self.mount_watches.insert(pid, watch);
+            }
+        }
+    }
+
+    /// 为登记了读取后卸载的 app 监听源文件被读取后关闭。
+    fn sync_read_watches(&mut self) {
+        let Some(ifd) = self.ifd.as_ref().map(AsRawFd::as_raw_fd) else {
+            return;
+        };
+        let pending: Vec<(u32, String)> = OVERLAYS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(pid, tracked)| {
+                let target = tracked.unmount_after_read.as_ref()?;
+                let mount = tracked.mounts.iter().find(|m| m.target == *target)?;
+                Some((*pid, mount.source.clone()))
+            })
+            .collect();
+        self.read_watches
+            .retain(|_, pid| pending.iter().any(|(p, _)| p == pid));
+        for (pid, source) in pending {
+            if self.read_watches.values().any(|p| *p == pid) {
+                continue;
+            }
+            let Ok(path) = CString::new(source) else {
+                continue;
+            };
+            let wd = unsafe { libc::inotify_add_watch(ifd, path.as_ptr(), libc::IN_CLOSE_NOWRITE) };
+            if wd >= 0 {
+                self.read_watches.insert(wd, pid);
+            } else {
+                warn!(
+                    "supervisor: cannot watch cpuinfo reads of pid {pid}: {}",
+                    io::Error::last_os_error()
+                );
+            }
+        }
+    }
+
+    /// app 已读取覆盖文件：卸载挂载、停止跟踪并释放源文件。
+    fn unmount_after_read(&mut self, pid: u32) {
+        if let Some(ifd) = &self.ifd {
+            let ifd = ifd.as_raw_fd();
+            self.read_watches.retain(|wd, p| {
+                let keep = *p != pid;
+                if !keep {
+                    unsafe { libc::inotify_rm_watch(ifd, *wd) };
+                }
+                keep
+            });
+        }
+        let mount = {
+            let mut overlays = OVERLAYS.lock().unwrap();
+            let Some(tracked) = overlays.get_mut(&pid) else {
+                return;
+            };
+            let Some(target) = tracked.unmount_after_read.take() else {
+                return;
+            };
+            let Some(index) = tracked.mounts.iter().position(|m| m.target == target) else {
+                return;
+            };
+            tracked.mounts.remove(index)
+        };
+        match crate::detached_mount::unmount(pid, std::slice::from_ref(&mount.target)) {
+            Ok(()) => info!("supervisor: pid {pid} read {}, unmounted", mount.target),
+            Err(e) => warn!(
+                "supervisor: failed to unmount {} for pid {pid}: {e:#}",
+                mount.target
+            ),
+        }
+        crate::overlay_store::release(&mount.source);
+    }
+
+    fn open_mountinfo(&self, pid: u32) -> Option<File> {
+        let file = File::open(format!("/proc/{pid}/mountinfo")).ok()?;
+        let token = MOUNTINFO_TOKEN_FLAG | pid as u64;
+        match epoll_watch(
+            self.efd.as_raw_fd(),
+            file.as_raw_fd(),
+            libc::EPOLLPRI,
+            token,
+        ) {
+            Ok(()) => Some(file),
+            Err(e) => {
+                warn!("supervisor: cannot watch mountinfo of pid {pid}: {e}");
+                None
+            }
+        }
+    }
+
+    /// 检查到期或挂载表有变化的 app，返回距下一次检查的时间。
+    fn check_mounts(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        let due: Vec<u32> = self
+            .mount_watches
+            .iter()
+            .filter(|(_, watch)| watch.dirty || watch.next_check <= now)
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in due {
+            self.check_mount(pid, now);
+        }
+        self.mount_watches
+            .values()
+            .map(|watch| watch.next_check.saturating_duration_since(now))
+            .min()
+    }
+
+    /// 跟随 namespace 切换，并在覆盖文件不再挂载时重新挂载。
+    fn check_mount(&mut self, pid: u32, now: Instant) {
+        // 读不到 namespace 时进程正在退出，由 pidfd 报告
+        let Some(ns_ino) = read_ns_ino(pid) else {
+            return;
+        };
+        let Some((mounts, mounted_ino)) = OVERLAYS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .map(|tracked| (tracked.mounts.clone(), tracked.ns_ino))
+        else {
+            return;
+        };
+
+        let ns_changed = ns_ino != mounted_ino;
+        let reopened = ns_changed.then(|| self.open_mountinfo(pid));
+        let Some(watch) = self.mount_watches.get_mut(&pid) else {
+            return;
+        };
+        if let Some(mountinfo) = reopened {
+            info!("supervisor: mount namespace of pid {pid} changed: {mounted_ino} -> {ns_ino}");
+            watch.mountinfo = mountinfo;
+            watch.backoff = NS_CHECK_MIN_INTERVAL;
+            watch.dirty = true;
+        } else if !watch.dirty {
+            watch.backoff = (watch.backoff * 2).min(NS_CHECK_MAX_INTERVAL);
+        }
+        watch.next_check = now + watch.backoff;
+        if !std::mem::take(&mut watch.dirty) {
+            return;
+        }
+
+        // 读取 mountinfo 同时清除 EPOLLPRI
+        let missing = match read_mountinfo(watch) {
+            Some(mountinfo) if watch.verify => !all_mounted(&mountinfo, &mounts),
+            _ => ns_changed,
+        };
+        if !missing {
+            if ns_changed {
+                update_mounted_ns(pid, ns_ino, false);
+            }
+            return;
+        }
+
+        match mount_overlays(pid, &mounts) {
+            Ok(()) => {
+                info!(
+                    "supervisor: re-mounted {} overlay(s) for pid {pid}",
+                    mounts.len()
+                );
+                update_mounted_ns(pid, ns_ino, true);
+                if watch.verify
+                    && read_mountinfo(watch).is_some_and(|info| !all_mounted(&info, &mounts))
+                {
+                    warn!(
+                        "supervisor: overlays of pid {pid} not visible in mountinfo, only following namespace changes"
+                    );
+                    watch.verify = false;
+                }
+            }
+            Err(e) => warn!("supervisor: failed to re-mount overlays for pid {pid}: {e:#}"),
+        }
+    }
+
+    fn forget(&mut self, pids: &[u32]) {
+        for pid in pids {
+            self.pidfds.remove(pid);
+            self.mount_watches.remove(pid);
+            self.read_watches.retain(|_, p| p != pid);
+            if let (Some(wd), Some(ifd)) = (self.oom_watches.remove(pid), &self.ifd) {
+                let ifd = ifd.as_raw_fd();
+                unsafe { libc::inotify_rm_watch(ifd, wd) };
+            }
+        }
+    }
+
+    /// 阻塞等待事件，返回 pidfd 报告已退出的进程。
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<u32>> {
+        // 向上取整，避免剩余不足 1ms 时空转
+        let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128 - 1) as i32 + 1);
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 32];
+        let nfds = unsafe {
+            libc::epoll_wait(
+                self.efd.as_raw_fd(),
+                events.as_mut_ptr(),
+                events.len() as i32,
+                timeout,
+            )
+        };
+        if nfds < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            anyhow::bail!("epoll_wait failed: {err}");
+        }
+
+        let mut exited = Vec::new();
+        for ev in events.iter().take(nfds as usize) {
+            // epoll_event 在 x86_64 上是 packed 的，先复制出来
+            let token = ev.u64;
+            match token {
+                INOTIFY_TOKEN => {
+                    if let Some(ifd) = &self.ifd {
+                        for wd in read_inotify_wds(ifd.as_raw_fd()) {
+                            if let Some(&pid) = self.read_watches.get(&wd) {
+                                self.read_apps.push(pid);
+                            }
+                        }
+                    }
+                }
+                WAKER_TOKEN => {
+                    if let Some(waker) = self.waker {
+                        drain_fd(waker);
+                    }
+                }
+                token if token & MOUNTINFO_TOKEN_FLAG != 0 => {
+                    if let Some(watch) = self.mount_watches.get_mut(&(token as u32)) {
+                        watch.dirty = true;
+                    }
+                }
+                token => exited.push(token as u32),
+            }
+        }
+        Ok(exited)
+    }
+}
+
+/// 处理已退出的进程：移出进程组（全部退出时关闭会话）、删除覆盖源文件。
+fn handle_exits(pids: &[u32]) {
+    let affected: Vec<(String, GroupId)> = {
+        let mut registry = PROCESS_REGISTRY.lock().unwrap();
+        let affected: Vec<(String, GroupId)> = registry
+            .iter()
+            .filter(|(_, group)| pids.iter().any(|pid| group.pids.contains(pid)))
+            .map(|(package, group)| (package.clone(), group.id))
+            .collect();
+        for (package, id) in &affected {
+            for &pid in pids {
+                registry.remove_pid(package, *id, pid);
+            }
+        }
+        affected
+    };
+    for (package, id) in &affected {
+        close_if_all_exited(package, *id);
+    }
+
+    let mut overlays = OVERLAYS.lock().unwrap();
+    for &pid in pids {
+        if let Some(tracked) = overlays.remove(&pid) {
+            info!("supervisor: app pid {pid} exited, releasing overlay sources");
+            for mount in &tracked.mounts {
+                crate::overlay_store::release(&mount.source);
+            }
+        }
+    }
+}
+
+fn update_mounted_ns(pid: u32, ns_ino: u64, remounted: bool) {
+    if let Some(tracked) = OVERLAYS.lock().unwrap().get_mut(&pid) {
+        tracked.ns_ino = ns_ino;
+        if remounted {
+            tracked.remounts += 1;
+        }
+    }
+}
+
+fn read_mountinfo(watch: &mut MountWatch) -> Option<String> {
+    let file = watch.mountinfo.as_mut()?;
+    let mut content = String::new();
+    let result = file
+        .rewind()
+        .and_then(|_| file.read_to_string(&mut content));
+    match result {
+        Ok(_) => Some(content),
+        Err(_) => {
+            // namespace 已销毁等情况，之后只按间隔检查
+            watch.mountinfo = None;
+            None
+        }
+    }
+}
+
+fn all_mounted(mountinfo: &str, mounts: &[OverlayMount]) -> bool {
+    mounts.iter().all(|mount| mounted_from(mountinfo, mount))
+}
+
+/// 进程组已没有存活进程时移除并关闭其会话。
+fn close_if_all_exited(package: &str, id: GroupId) {
+    let removed = PROCESS_REGISTRY
+        .lock()
+        .unwrap()
+        .remove_if_empty(package, id);
+    if let Some(removed) = removed {
+        close_session(removed.session);
+        info!("supervisor: all processes of {package} exited");
+    }
+}
+
+fn min_timeout(current: Option<Duration>, candidate: Duration) -> Option<Duration> {
+    Some(current.map_or(candidate, |current| current.min(candidate)))
+}
+
+fn owned_fd(fd: RawFd, what: &str) -> Result<OwnedFd> {
+    if fd < 0 {
+        anyhow::bail!("{what} failed: {}", io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// 打开进程的 pidfd。进程已退出时返回 `ESRCH`。
+pub(crate) fn pidfd_open(pid: u32) -> io::Result<OwnedFd> {
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0u32) };
+    if pidfd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(pidfd as RawFd) })
+}
+
+fn epoll_add(efd: RawFd, fd: RawFd, token: u64) -> Result<()> {
+    epoll_watch(efd, fd, libc::EPOLLIN, token)
+}
+
+fn epoll_watch(efd: RawFd, fd: RawFd, events: libc::c_int, token: u64) -> Result<()> {
+    let mut ev = libc::epoll_event {
+        events: events as u32,
+        u64: token,
+    };
+    if unsafe { libc::epoll_ctl(efd, libc::EPOLL_CTL_ADD, fd, &mut ev) } < 0 {
+        anyhow::bail!("epoll_ctl failed: {}", io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 读空非阻塞 inotify fd，返回产生事件的 watch。
+fn read_inotify_wds(fd: RawFd) -> Vec<i32> {
+    const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+    let mut wds = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if len <= 0 {
+            return wds;
+        }
+        let mut offset = 0;
+        while offset + HEADER <= len as usize {
+            let event = unsafe {
+                std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+            };
+            wds.push(event.wd);
+            offset += HEADER + event.len as usize;
+        }
+    }
+}
+
+/// 读空非阻塞 eventfd。
+fn drain_fd(fd: RawFd) {
+    let mut buf = [0u8; 512];
+    while unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
+}
+
+/// 进程不存在或已成为僵尸（mount 子进程退出后等待回收）。
+fn process_exited(pid: u32) -> bool {
+    match fs::read_to_string(format!("/proc/{pid}/stat")) {
+        // 格式："pid (comm) state ..."，comm 可能含空格与括号
+        Ok(stat) => stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.trim_start().chars().next())
+            .is_none_or(|state| state == 'Z'),
+        Err(_) => true,
+    }
+}
+
+/// 读取 /proc/<pid>/oom_score_adj。
+///
+/// Android 的 OomAdjuster 在 app 前后台切换时写入 oom_score_adj：
+/// 前台 0，可见 100，后台/缓存 200-900+。
+fn read_oom_score_adj(pid: u32) -> Option<i32> {
+    let path = format!("/proc/{pid}/oom_score_adj");
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| s.trim().parse::<i32>().ok())
+}
+
+fn is_process_in_top_app(pid: u32) -> bool {
+    let cgroup_path = format!("/proc/{pid}/cgroup");
+    match fs::read_to_string(&cgroup_path) {
+        Ok(content) => content.lines().any(|line| line.contains("top-app")),
+        Err(_) => true,
+    }
+}
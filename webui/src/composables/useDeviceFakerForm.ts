@@ -1,5 +1,5 @@
 import { inject, provide, ref, type InjectionKey, type Ref } from 'vue'
-import type { Template, AppConfig, SpoofMode } from '../types'
+import type { Template, AppConfig, BatteryInfo, SpoofMode } from '../types'
 
 export const DEVICE_FAKER_FORM_KEY: InjectionKey<Ref<DeviceFakerFormData>> =
   Symbol('deviceFakerForm')
@@ -22,6 +22,11 @@ export interface DeviceFakerFormData {
   packages: string[]
   cpu_spoof: string
   cpu_spoof_custom: string
+  battery_charge_full_design_mah: string
+  battery_charge_full_mah: string
+  battery_technology: string
+  // 每行一项 `真实 type = 伪装 type`
+  thermal_zones: string
 }
 
 function createEmptyFormData(): DeviceFakerFormData {
@@ -43,9 +48,46 @@ function createEmptyFormData(): DeviceFakerFormData {
     packages: [],
     cpu_spoof: '',
     cpu_spoof_custom: '',
+    battery_charge_full_design_mah: '',
+    battery_charge_full_mah: '',
+    battery_technology: '',
+    thermal_zones: '',
   }
 }
 
+function parseCapacity(value: string): number | undefined {
+  const capacity = Number(value)
+  return value && Number.isInteger(capacity) && capacity > 0 ? capacity : undefined
+}
+
+function batteryFromFormData(formData: DeviceFakerFormData): BatteryInfo | undefined {
+  const battery: BatteryInfo = {}
+  const designMah = parseCapacity(formData.battery_charge_full_design_mah)
+  if (designMah !== undefined) battery.charge_full_design_mah = designMah
+  const fullMah = parseCapacity(formData.battery_charge_full_mah)
+  if (fullMah !== undefined) battery.charge_full_mah = fullMah
+  if (formData.battery_technology.trim()) battery.technology = formData.battery_technology.trim()
+  return Object.keys(battery).length > 0 ? battery : undefined
+}
+
+function thermalZonesFromText(text: string): Record<string, string> | undefined {
+  const zones: Record<string, string> = {}
+  for (const line of text.split('\n')) {
+    const index = line.indexOf('=')
+    if (index < 0) continue
+    const real = line.slice(0, index).trim()
+    const fake = line.slice(index + 1).trim()
+    if (real && fake) zones[real] = fake
+  }
+  return Object.keys(zones).length > 0 ? zones : undefined
+}
+
+function thermalZonesToText(zones?: Record<string, string>): string {
+  return Object.entries(zones || {})
+    .map(([real, fake]) => `${real} = ${fake}`)
+    .join('\n')
+}
+
 export function formDataToTemplate(formData: DeviceFakerFormData, base?: Template): Template {
   const template: Template = {
     ...(base || {}),
@@ -126,6 +168,20 @@ export function formDataToTemplate(formData: DeviceFakerFormData, base?: Templat
     delete template.cpu_spoof_custom
   }
 
+  const battery = batteryFromFormData(formData)
+  if (battery) {
+    template.battery = battery
+  } else {
+    delete template.battery
+  }
+
+  const thermalZones = thermalZonesFromText(formData.thermal_zones)
+  if (thermalZones) {
+    template.thermal_zones = thermalZones
+  } else {
+    delete template.thermal_zones
+  }
+
   return template
 }
 
@@ -148,6 +204,14 @@ export function templateToFormData(template: Template): DeviceFakerFormData {
     packages: template.packages || [],
     cpu_spoof: template.cpu_spoof || '',
     cpu_spoof_custom: template.cpu_spoof_custom || '',
+    battery_charge_full_design_mah: template.battery?.charge_full_design_mah
+      ? String(template.battery.charge_full_design_mah)
+      : '',
+    battery_charge_full_mah: template.battery?.charge_full_mah
+      ? String(template.battery.charge_full_mah)
+      : '',
+    battery_technology: template.battery?.technology || '',
+    thermal_zones: thermalZonesToText(template.thermal_zones),
   }
 }
 
@@ -170,6 +234,14 @@ export function appConfigToFormData(appConfig: AppConfig): DeviceFakerFormData {
     packages: [],
     cpu_spoof: appConfig.cpu_spoof || '',
     cpu_spoof_custom: appConfig.cpu_spoof_custom || '',
+    battery_charge_full_design_mah: appConfig.battery?.charge_full_design_mah
+      ? String(appConfig.battery.charge_full_design_mah)
+      : '',
+    battery_charge_full_mah: appConfig.battery?.charge_full_mah
+      ? String(appConfig.battery.charge_full_mah)
+      : '',
+    battery_technology: appConfig.battery?.technology || '',
+    thermal_zones: thermalZonesToText(appConfig.thermal_zones),
   }
 }
 
@@ -192,6 +264,8 @@ export function formDataToAppConfig(formData: DeviceFakerFormData, packageName:
     mode: formData.mode || undefined,
     cpu_spoof: formData.cpu_spoof || undefined,
     cpu_spoof_custom: formData.cpu_spoof_custom || undefined,
+    battery: batteryFromFormData(formData),
+    thermal_zones: thermalZonesFromText(formData.thermal_zones),
   }
 }
 
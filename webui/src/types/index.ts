@@ -1,4 +1,5 @@
-export type SpoofMode = 'lite' | 'full' | 'companion'
+export type SpoofMode = 'lite' | 'full' | 'companion' | 'isolated'
+export type NativePropBackend = 'plt_hook' | 'property_area'
 export type OnlineTemplateSource = 'gitee' | 'github'
 export type OnlineTemplateLoadState = 'idle' | 'loading' | 'ready' | 'error'
 export type OnlineTemplateDetailsState = 'idle' | 'loading' | 'partial' | 'complete' | 'error'
@@ -9,6 +10,49 @@ export interface CustomProps {
   [key: string]: string
 }
 
+// 屏幕参数伪装
+export interface DisplayInfo {
+  width?: number
+  height?: number
+  density_dpi?: number
+  refresh_rate?: number
+  supported_refresh_rates?: number[]
+}
+
+// 电池参数伪装（companion 模式）
+export interface BatteryInfo {
+  charge_full_design_mah?: number
+  charge_full_mah?: number
+  technology?: string
+}
+
+// 伪装属性的还原策略（companion 模式）
+export type RestorePolicy =
+  | { mode: 'on_exit_only' }
+  | { mode: 'on_background'; threshold?: number; debounce_ms?: number; poll_interval_ms?: number }
+  | { mode: 'never' }
+
+// 逐项覆盖模式预设的伪装功能
+export interface SpoofFeatureFlags {
+  build_fields?: boolean
+  java_prop_hook?: boolean
+  native_prop_hook?: boolean
+  resetprop?: boolean
+  cpuinfo?: boolean
+  overlays?: boolean
+  isolated_props?: boolean
+}
+
+// GPU 标识伪装（仅 full 模式）
+export interface GpuInfo {
+  vendor?: string
+  renderer?: string
+  version?: string
+  vulkan_device_name?: string
+  vulkan_device_id?: number
+  vulkan_vendor_id?: number
+}
+
 // 设备信息接口
 export interface DeviceInfo {
   manufacturer?: string
@@ -27,6 +71,20 @@ export interface DeviceInfo {
   force_denylist_unmount?: boolean
   cpu_spoof?: string
   cpu_spoof_custom?: string
+  gpu?: GpuInfo
+  display?: DisplayInfo
+  ram_total_mb?: number
+  meminfo_overlay?: boolean
+  storage_total_gb?: number
+  battery?: BatteryInfo
+  thermal_zones?: Record<string, string>
+  cpuinfo_unmount_after_read?: boolean
+  overlay_labels?: Record<string, string>
+  add_features?: string[]
+  remove_features?: string[]
+  restore_policy?: RestorePolicy
+  spoof_features?: SpoofFeatureFlags
+  native_prop_backend?: NativePropBackend
 }
 
 // 机型模板接口
@@ -1,8 +1,14 @@
 import type {
   AppConfig,
+  BatteryInfo,
   Config,
   CustomProps,
   DeviceInfo,
+  DisplayInfo,
+  GpuInfo,
+  NativePropBackend,
+  RestorePolicy,
+  SpoofFeatureFlags,
   SpoofMode,
   Template,
   TemplateMeta,
@@ -10,7 +16,8 @@ import type {
 
 type UnknownRecord = Record<string, unknown>
 
-const VALID_MODES: SpoofMode[] = ['lite', 'full', 'companion']
+const VALID_MODES: SpoofMode[] = ['lite', 'full', 'companion', 'isolated']
+const VALID_NATIVE_PROP_BACKENDS: NativePropBackend[] = ['plt_hook', 'property_area']
 
 function isRecord(value: unknown): value is UnknownRecord {
   return typeof value === 'object' && value !== null && !Array.isArray(value)
@@ -36,21 +43,41 @@ function asOptionalInteger(value: unknown): number | undefined {
   return value
 }
 
+function asOptionalPositiveNumber(value: unknown): number | undefined {
+  return typeof value === 'number' && Number.isFinite(value) && value > 0 ? value : undefined
+}
+
 function asOptionalMode(value: unknown): SpoofMode | undefined {
   return typeof value === 'string' && VALID_MODES.includes(value as SpoofMode)
     ? (value as SpoofMode)
     : undefined
 }
 
-function normalizePackages(value: unknown): string[] | undefined {
+function asOptionalNativePropBackend(value: unknown): NativePropBackend | undefined {
+  return typeof value === 'string' &&
+    VALID_NATIVE_PROP_BACKENDS.includes(value as NativePropBackend)
+    ? (value as NativePropBackend)
+    : undefined
+}
+
+function normalizeStringList(value: unknown): string[] | undefined {
   if (!Array.isArray(value)) {
     return undefined
   }
 
-  const packages = value.filter(
+  const items = value.filter(
     (item): item is string => typeof item === 'string' && item.trim().length > 0
   )
-  return packages.length > 0 ? packages : undefined
+  return items.length > 0 ? items : undefined
+}
+
+function normalizePositiveNumberList(value: unknown): number[] | undefined {
+  if (!Array.isArray(value)) {
+    return undefined
+  }
+
+  const items = value.filter((item): item is number => asOptionalPositiveNumber(item) !== undefined)
+  return items.length > 0 ? items : undefined
 }
 
 function normalizeCustomProps(value: unknown): CustomProps | undefined {
@@ -71,6 +98,130 @@ function normalizeCustomProps(value: unknown): CustomProps | undefined {
   return Object.keys(customProps).length > 0 ? customProps : undefined
 }
 
+function normalizeGpu(value: unknown): GpuInfo | undefined {
+  if (!isRecord(value)) {
+    return undefined
+  }
+
+  const gpu: GpuInfo = {}
+
+  const vendor = asOptionalString(value.vendor)
+  if (vendor !== undefined) gpu.vendor = vendor
+
+  const renderer = asOptionalString(value.renderer)
+  if (renderer !== undefined) gpu.renderer = renderer
+
+  const version = asOptionalString(value.version)
+  if (version !== undefined) gpu.version = version
+
+  const vulkanDeviceName = asOptionalString(value.vulkan_device_name)
+  if (vulkanDeviceName !== undefined) gpu.vulkan_device_name = vulkanDeviceName
+
+  const vulkanDeviceId = asOptionalInteger(value.vulkan_device_id)
+  if (vulkanDeviceId !== undefined) gpu.vulkan_device_id = vulkanDeviceId
+
+  const vulkanVendorId = asOptionalInteger(value.vulkan_vendor_id)
+  if (vulkanVendorId !== undefined) gpu.vulkan_vendor_id = vulkanVendorId
+
+  return Object.keys(gpu).length > 0 ? gpu : undefined
+}
+
+function normalizeDisplay(value: unknown): DisplayInfo | undefined {
+  if (!isRecord(value)) {
+    return undefined
+  }
+
+  const display: DisplayInfo = {}
+
+  const width = asOptionalInteger(value.width)
+  if (width !== undefined) display.width = width
+
+  const height = asOptionalInteger(value.height)
+  if (height !== undefined) display.height = height
+
+  const densityDpi = asOptionalInteger(value.density_dpi)
+  if (densityDpi !== undefined) display.density_dpi = densityDpi
+
+  const refreshRate = asOptionalPositiveNumber(value.refresh_rate)
+  if (refreshRate !== undefined) display.refresh_rate = refreshRate
+
+  const supportedRefreshRates = normalizePositiveNumberList(value.supported_refresh_rates)
+  if (supportedRefreshRates !== undefined) display.supported_refresh_rates = supportedRefreshRates
+
+  return Object.keys(display).length > 0 ? display : undefined
+}
+
+function normalizeBattery(value: unknown): BatteryInfo | undefined {
+  if (!isRecord(value)) {
+    return undefined
+  }
+
+  const battery: BatteryInfo = {}
+
+  const chargeFullDesignMah = asOptionalInteger(value.charge_full_design_mah)
+  if (chargeFullDesignMah !== undefined) battery.charge_full_design_mah = chargeFullDesignMah
+
+  const chargeFullMah = asOptionalInteger(value.charge_full_mah)
+  if (chargeFullMah !== undefined) battery.charge_full_mah = chargeFullMah
+
+  const technology = asOptionalString(value.technology)
+  if (technology !== undefined) battery.technology = technology
+
+  return Object.keys(battery).length > 0 ? battery : undefined
+}
+
+const SPOOF_FEATURE_KEYS = [
+  'build_fields',
+  'java_prop_hook',
+  'native_prop_hook',
+  'resetprop',
+  'cpuinfo',
+  'overlays',
+  'isolated_props',
+] as const
+
+function normalizeSpoofFeatures(value: unknown): SpoofFeatureFlags | undefined {
+  if (!isRecord(value)) {
+    return undefined
+  }
+
+  const features: SpoofFeatureFlags = {}
+  for (const key of SPOOF_FEATURE_KEYS) {
+    const enabled = asOptionalBoolean(value[key])
+    if (enabled !== undefined) features[key] = enabled
+  }
+
+  return Object.keys(features).length > 0 ? features : undefined
+}
+
+function normalizeRestorePolicy(value: unknown): RestorePolicy | undefined {
+  if (!isRecord(value)) {
+    return undefined
+  }
+
+  switch (value.mode) {
+    case 'on_exit_only':
+    case 'never':
+      return { mode: value.mode }
+    case 'on_background': {
+      const policy: RestorePolicy = { mode: 'on_background' }
+
+      const threshold = asOptionalInteger(value.threshold)
+      if (threshold !== undefined) policy.threshold = threshold
+
+      const debounceMs = asOptionalInteger(value.debounce_ms)
+      if (debounceMs !== undefined) policy.debounce_ms = debounceMs
+
+      const pollIntervalMs = asOptionalInteger(value.poll_interval_ms)
+      if (pollIntervalMs !== undefined) policy.poll_interval_ms = pollIntervalMs
+
+      return policy
+    }
+    default:
+      return undefined
+  }
+}
+
 function normalizeDeviceInfoFields(source: UnknownRecord): Partial<DeviceInfo> {
   const normalized: Partial<DeviceInfo> = {}
 
@@ -124,6 +275,50 @@ function normalizeDeviceInfoFields(source: UnknownRecord): Partial<DeviceInfo> {
   const cpuSpoofCustom = asOptionalString(source.cpu_spoof_custom)
   if (cpuSpoofCustom !== undefined) normalized.cpu_spoof_custom = cpuSpoofCustom
 
+  const gpu = normalizeGpu(source.gpu)
+  if (gpu !== undefined) normalized.gpu = gpu
+
+  const display = normalizeDisplay(source.display)
+  if (display !== undefined) normalized.display = display
+
+  const ramTotalMb = asOptionalInteger(source.ram_total_mb)
+  if (ramTotalMb !== undefined) normalized.ram_total_mb = ramTotalMb
+
+  const meminfoOverlay = asOptionalBoolean(source.meminfo_overlay)
+  if (meminfoOverlay !== undefined) normalized.meminfo_overlay = meminfoOverlay
+
+  const storageTotalGb = asOptionalInteger(source.storage_total_gb)
+  if (storageTotalGb !== undefined) normalized.storage_total_gb = storageTotalGb
+
+  const battery = normalizeBattery(source.battery)
+  if (battery !== undefined) normalized.battery = battery
+
+  const thermalZones = normalizeCustomProps(source.thermal_zones)
+  if (thermalZones !== undefined) normalized.thermal_zones = thermalZones
+
+  const cpuinfoUnmountAfterRead = asOptionalBoolean(source.cpuinfo_unmount_after_read)
+  if (cpuinfoUnmountAfterRead !== undefined) {
+    normalized.cpuinfo_unmount_after_read = cpuinfoUnmountAfterRead
+  }
+
+  const overlayLabels = normalizeCustomProps(source.overlay_labels)
+  if (overlayLabels !== undefined) normalized.overlay_labels = overlayLabels
+
+  const addFeatures = normalizeStringList(source.add_features)
+  if (addFeatures !== undefined) normalized.add_features = addFeatures
+
+  const removeFeatures = normalizeStringList(source.remove_features)
+  if (removeFeatures !== undefined) normalized.remove_features = removeFeatures
+
+  const restorePolicy = normalizeRestorePolicy(source.restore_policy)
+  if (restorePolicy !== undefined) normalized.restore_policy = restorePolicy
+
+  const spoofFeatures = normalizeSpoofFeatures(source.spoof_features)
+  if (spoofFeatures !== undefined) normalized.spoof_features = spoofFeatures
+
+  const nativePropBackend = asOptionalNativePropBackend(source.native_prop_backend)
+  if (nativePropBackend !== undefined) normalized.native_prop_backend = nativePropBackend
+
   return normalized
 }
 
@@ -133,7 +328,7 @@ export function sanitizeTemplate(input: unknown): Template {
     ...normalizeDeviceInfoFields(source),
   }
 
-  const packages = normalizePackages(source.packages)
+  const packages = normalizeStringList(source.packages)
   if (packages !== undefined) {
     normalized.packages = packages
   }
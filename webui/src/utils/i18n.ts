@@ -65,6 +65,7 @@ export const messages = {
         lite: '轻量模式',
         full: '完整模式',
         companion: 'Companion 模式',
+        isolated: 'Isolated 模式',
       },
     },
     templates: {
@@ -96,6 +97,11 @@ export const messages = {
         system: '系统',
         cpu_spoof: 'CPU 伪装预设',
         cpu_spoof_custom: '自定义 CPU 信息',
+        battery: '电池与温控区',
+        battery_charge_full_design_mah: '电池设计容量 (mAh)',
+        battery_charge_full_mah: '电池满充容量 (mAh，可选)',
+        battery_technology: '电池技术',
+        thermal_zones: '温控区名称',
       },
       placeholders: {
         name: '例如：redmagic_9_pro',
@@ -116,11 +122,16 @@ export const messages = {
         packages: '输入或搜索应用包名',
         cpu_spoof: '选择 config.toml 中的 CPU 预设（companion 模式生效）',
         cpu_spoof_custom: '留空则使用上方预设；填写后将完全自定义 /proc/cpuinfo 内容',
+        battery_charge_full_design_mah: '例如：5800',
+        battery_charge_full_mah: '留空则按真实电池健康度推算',
+        battery_technology: '例如：Li-poly',
+        thermal_zones: '每行一项：真实 type = 伪装 type，例如 cpu-0-0-usr = cpu-0-0',
       },
       options: {
         mode_lite: 'lite - 轻量模式（推荐，隐蔽性好）',
         mode_full: 'full - 完整模式（全面伪装，可能被检测）',
         mode_companion: 'companion - Companion 模式（属性 + CPU 伪装）',
+        mode_isolated: 'isolated - 隔离模式（属性仅对本应用可见 + CPU 伪装）',
       },
       empty: {
         title: '暂无机型模板',
@@ -157,6 +168,7 @@ export const messages = {
         lite: 'lite (轻量)',
         full: 'full (完整)',
         companion: 'companion (Companion)',
+        isolated: 'isolated (隔离)',
       },
       search: {
         placeholder: '搜索模板名称、品牌或型号',
@@ -358,6 +370,7 @@ export const messages = {
           lite: '轻量模式 (推荐)',
           full: '完整模式',
           companion: 'Companion 模式',
+          isolated: '隔离模式',
         },
         force_denylist_unmount: {
           label: '默认强制卸载挂载点',
@@ -459,6 +472,7 @@ export const messages = {
         lite: 'Lite Mode',
         full: 'Full Mode',
         companion: 'Companion Mode',
+        isolated: 'Isolated Mode',
       },
     },
     templates: {
@@ -490,6 +504,11 @@ export const messages = {
         system: 'System',
         cpu_spoof: 'CPU Spoof Preset',
         cpu_spoof_custom: 'Custom CPU Info',
+        battery: 'Battery & Thermal Zones',
+        battery_charge_full_design_mah: 'Battery Design Capacity (mAh)',
+        battery_charge_full_mah: 'Battery Full Capacity (mAh, optional)',
+        battery_technology: 'Battery Technology',
+        thermal_zones: 'Thermal Zone Names',
       },
       placeholders: {
         name: 'e.g. redmagic_9_pro',
@@ -511,11 +530,16 @@ export const messages = {
         cpu_spoof: 'Select a CPU preset from config.toml (companion mode)',
         cpu_spoof_custom:
           'Leave empty to use the preset above; fill in to fully customize /proc/cpuinfo',
+        battery_charge_full_design_mah: 'e.g. 5800',
+        battery_charge_full_mah: 'Leave empty to derive from the real battery health',
+        battery_technology: 'e.g. Li-poly',
+        thermal_zones: 'One per line: real type = fake type, e.g. cpu-0-0-usr = cpu-0-0',
       },
       options: {
         mode_lite: 'lite - Lite Mode\n(Recommended, Stealthy)',
         mode_full: 'full - Full Mode\n(Complete Spoofing, Detectable)',
         mode_companion: 'companion - Companion Mode\n(Properties + CPU spoof)',
+        mode_isolated: 'isolated - Isolated Mode\n(App-private properties + CPU spoof)',
       },
       empty: {
         title: 'No Templates Found',
@@ -552,6 +576,7 @@ export const messages = {
         lite: 'lite (Lite)',
         full: 'full (Full)',
         companion: 'companion (Companion)',
+        isolated: 'isolated (Isolated)',
       },
       search: {
         placeholder: 'Search by name, brand, or model',
@@ -755,6 +780,7 @@ export const messages = {
           lite: 'Lite Mode (Recommended)',
           full: 'Full Mode',
           companion: 'Companion Mode',
+          isolated: 'Isolated Mode',
         },
         force_denylist_unmount: {
           label: 'Default Force Denylist Unmount',
@@ -856,6 +882,7 @@ export const messages = {
         lite: 'Basit Mod',
         full: 'Tam Mod',
         companion: 'Companion Modu',
+        isolated: 'İzole Mod',
       },
     },
     templates: {
@@ -887,6 +914,11 @@ export const messages = {
         system: 'Sistem',
         cpu_spoof: 'CPU Sahtekarlık Preseti',
         cpu_spoof_custom: 'Özel CPU Bilgisi',
+        battery: 'Pil ve Termal Bölgeler',
+        battery_charge_full_design_mah: 'Pil Tasarım Kapasitesi (mAh)',
+        battery_charge_full_mah: 'Pil Tam Kapasitesi (mAh, isteğe bağlı)',
+        battery_technology: 'Pil Teknolojisi',
+        thermal_zones: 'Termal Bölge Adları',
       },
       placeholders: {
         name: 'redmagic_9_pro vs.',
@@ -907,11 +939,16 @@ export const messages = {
         cpu_spoof: 'config.toml dosyasından bir CPU preset seçin (companion modu)',
         cpu_spoof_custom:
           'Yukarıdaki preset kullanmak için boş bırakın; /proc/cpuinfo içeriğini tamamen özelleştirmek için doldurun',
+        battery_charge_full_design_mah: 'örn. 5800',
+        battery_charge_full_mah: 'Gerçek pil sağlığından hesaplamak için boş bırakın',
+        battery_technology: 'örn. Li-poly',
+        thermal_zones: 'Her satıra bir tane: gerçek type = sahte type, örn. cpu-0-0-usr = cpu-0-0',
       },
       options: {
         mode_lite: 'lite - Basit Mod\n(Önerilen, Sağlıklı)',
         mode_full: 'full - Tam Mod\n(Tam Kimlik Sahtekarlığı, Tespit Edilemez)',
         mode_companion: 'companion - Companion Modu\n(Özellikler + CPU taklidi)',
+        mode_isolated: 'isolated - İzole Mod\n(Uygulamaya özel özellikler + CPU taklidi)',
       },
       empty: {
         title: 'Hiç Şablon Bulunamadı',
@@ -949,6 +986,7 @@ export const messages = {
         lite: 'lite (Sade)',
         full: 'full (Tam)',
         companion: 'companion (Companion)',
+        isolated: 'isolated (İzole)',
       },
       search: {
         placeholder: 'İsme, markaya veya modele göre arayın',
@@ -1159,6 +1197,7 @@ export const messages = {
           lite: 'Basit Mod (Önerilir)',
           full: 'Tam Mod',
           companion: 'Companion Modu',
+          isolated: 'İzole Mod',
         },
         force_denylist_unmount: {
           label: 'Varsayılan Denylist Listesinden Çıkarma',